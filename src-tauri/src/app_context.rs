@@ -0,0 +1,123 @@
+// 界面和守护模式共用的运行环境
+//
+// 隧道、进程守护、日志和控制接口只通过 AppContext 取得应用数据目录、共享状态和
+// 发送事件。图形界面下包装 tauri::AppHandle；守护模式不创建 Tauri 应用，也就不会
+// 启动 Linux 上依赖 GTK 和图形环境的事件循环，共享状态保存在 HeadlessApp 中，
+// 事件没有接收方，直接丢弃。
+
+use serde::Serialize;
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use tauri::{Emitter, Manager};
+
+#[derive(Clone)]
+pub enum AppContext {
+    Tauri(tauri::AppHandle),
+    Headless(Arc<HeadlessApp>),
+}
+
+/// 守护模式的运行环境
+pub struct HeadlessApp {
+    app_dir: PathBuf,
+    // 与 Tauri 一样按类型保存，状态在进程退出前一直有效
+    states: RwLock<HashMap<TypeId, &'static (dyn Any + Send + Sync)>>,
+}
+
+impl AppContext {
+    pub fn headless(app_dir: PathBuf) -> Self {
+        Self::Headless(Arc::new(HeadlessApp {
+            app_dir,
+            states: RwLock::new(HashMap::new()),
+        }))
+    }
+
+    pub fn app_data_dir(&self) -> Result<PathBuf, String> {
+        match self {
+            Self::Tauri(app_handle) => app_handle
+                .path()
+                .app_data_dir()
+                .map_err(|e| format!("获取应用目录失败: {}", e)),
+            Self::Headless(app) => Ok(app.app_dir.clone()),
+        }
+    }
+
+    /// 注册共享状态，同一类型只保留第一次注册的值
+    pub fn manage<T: Send + Sync + 'static>(&self, state: T) {
+        match self {
+            Self::Tauri(app_handle) => {
+                app_handle.manage(state);
+            }
+            Self::Headless(app) => {
+                let mut states = match app.states.write() {
+                    Ok(states) => states,
+                    Err(poisoned) => poisoned.into_inner(),
+                };
+                states.entry(TypeId::of::<T>()).or_insert_with(|| {
+                    let state: &'static (dyn Any + Send + Sync) = Box::leak(Box::new(state));
+                    state
+                });
+            }
+        }
+    }
+
+    /// 取得共享状态，没有注册时 panic，与 tauri::Manager::state 一致
+    pub fn state<T: Send + Sync + 'static>(&self) -> &T {
+        match self {
+            Self::Tauri(app_handle) => app_handle.state::<T>().inner(),
+            Self::Headless(app) => {
+                let states = match app.states.read() {
+                    Ok(states) => states,
+                    Err(poisoned) => poisoned.into_inner(),
+                };
+                let state = states
+                    .get(&TypeId::of::<T>())
+                    .and_then(|state| state.downcast_ref::<T>());
+                match state {
+                    Some(state) => state,
+                    None => panic!("状态 {} 没有注册", std::any::type_name::<T>()),
+                }
+            }
+        }
+    }
+
+    /// 向前端发送事件，守护模式下没有前端，直接返回
+    pub fn emit<S: Serialize + Clone>(&self, event: &str, payload: S) -> Result<(), String> {
+        match self {
+            Self::Tauri(app_handle) => app_handle.emit(event, payload).map_err(|e| e.to_string()),
+            Self::Headless(_) => Ok(()),
+        }
+    }
+}
+
+impl From<tauri::AppHandle> for AppContext {
+    fn from(app_handle: tauri::AppHandle) -> Self {
+        Self::Tauri(app_handle)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn headless_keeps_first_managed_state() {
+        let app = AppContext::headless(PathBuf::from("/tmp/chmlfrp"));
+        app.manage(Mutex::new(1));
+        app.manage(Mutex::new(2));
+        *app.state::<Mutex<i32>>().lock().unwrap() += 1;
+
+        assert_eq!(*app.state::<Mutex<i32>>().lock().unwrap(), 2);
+        assert_eq!(app.app_data_dir().unwrap(), PathBuf::from("/tmp/chmlfrp"));
+        assert!(app.emit("frpc-log", "ignored").is_ok());
+    }
+
+    #[test]
+    #[should_panic(expected = "没有注册")]
+    fn headless_panics_on_missing_state() {
+        let app = AppContext::headless(PathBuf::new());
+        app.state::<Mutex<u8>>();
+    }
+}
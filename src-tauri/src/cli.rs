@@ -0,0 +1,274 @@
+// 命令行入口
+//
+// 不带子命令时启动图形界面；daemon 以无界面的守护模式运行。list、start、stop、
+// logs 通过本地控制接口管理正在运行的守护进程或图形界面，地址和令牌从应用数据
+// 目录的 settings.json 和 control_api_token 中读取；download-frpc 直接下载
+// frpc，不需要启动器在运行。无法识别的参数，例如深度链接的地址，同样启动图形界面。
+
+use crate::control_http::{self, ListenAddr};
+use crate::models::TunnelKey;
+use crate::settings::{Settings, SETTINGS_FILE};
+use serde_json::Value;
+use std::io::{BufRead, Read, Write};
+use std::path::PathBuf;
+
+/// 应用标识，与 tauri.conf.json 中的 identifier 一致，用于定位应用数据目录
+const APP_IDENTIFIER: &str = "net.chmlfrp.launcher";
+
+/// 无法连接控制接口时的提示
+const NOT_RUNNING_HINT: &str = "请先运行 daemon 子命令，或在图形界面的设置中开启本地控制接口";
+
+const USAGE: &str = "用法:
+  <程序>                          启动图形界面
+  <程序> daemon                   以无界面的守护模式运行
+  <程序> list                     列出隧道及状态
+  <程序> start <隧道>             启动隧道
+  <程序> stop <隧道> [--grace <秒>] 停止隧道
+  <程序> logs [-f] <隧道>         查看隧道日志，-f 持续输出新日志
+  <程序> download-frpc            下载 frpc
+
+<隧道> 为 API 隧道的 ID、api_<ID> 或 custom_<名称>。
+list、start、stop 和 logs 需要守护模式在运行，或在图形界面的设置中开启本地控制接口。";
+
+/// 程序入口，解析子命令后分发
+pub fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let command = args.first().map(String::as_str).unwrap_or_default();
+    if !matches!(
+        command,
+        "daemon" | "list" | "start" | "stop" | "logs" | "download-frpc" | "help" | "--help" | "-h"
+    ) {
+        crate::run();
+        return;
+    }
+
+    attach_console();
+    let code = match command {
+        "daemon" => crate::daemon::run(),
+        "help" | "--help" | "-h" => {
+            println!("{}", USAGE);
+            0
+        }
+        _ => match run_command(command, &args[1..]) {
+            Ok(()) => 0,
+            Err(e) => {
+                eprintln!("错误: {}", e);
+                1
+            }
+        },
+    };
+    std::process::exit(code);
+}
+
+fn run_command(command: &str, args: &[String]) -> Result<(), String> {
+    match command {
+        "list" => list(),
+        "start" => {
+            let tunnel_key = parse_tunnel_key(single_arg(args)?)?;
+            let result = request("POST", &tunnel_path(&tunnel_key, "/start"))?;
+            println!("{}", message(&result));
+            Ok(())
+        }
+        "stop" => stop(args),
+        "logs" => logs(args),
+        "download-frpc" => download_frpc(),
+        _ => Err(format!("未知的命令: {}\n\n{}", command, USAGE)),
+    }
+}
+
+fn list() -> Result<(), String> {
+    let tunnels = request("GET", "/v1/tunnels")?;
+    let tunnels = tunnels.as_array().cloned().unwrap_or_default();
+    if tunnels.is_empty() {
+        println!("没有隧道");
+        return Ok(());
+    }
+    for tunnel in tunnels {
+        let state = &tunnel["state"];
+        let state = match (state["state"].as_str(), state["reason"].as_str()) {
+            (Some(state), Some(reason)) => format!("{}: {}", state, reason),
+            (Some(state), None) => state.to_string(),
+            _ => "-".to_string(),
+        };
+        println!(
+            "{:<24} {:<6} {:<16} {}",
+            tunnel["tunnel_key"].as_str().unwrap_or_default(),
+            if tunnel["running"].as_bool() == Some(true) {
+                "运行中"
+            } else {
+                "未运行"
+            },
+            state,
+            tunnel["name"].as_str().unwrap_or_default()
+        );
+    }
+    Ok(())
+}
+
+fn stop(args: &[String]) -> Result<(), String> {
+    let mut tunnel = None;
+    let mut grace_period_secs = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--grace" => {
+                let secs = args.next().ok_or("--grace 需要指定秒数")?;
+                grace_period_secs = Some(
+                    secs.parse::<u64>()
+                        .map_err(|_| format!("等待时间格式不正确: {}", secs))?,
+                );
+            }
+            _ if tunnel.is_none() => tunnel = Some(arg.as_str()),
+            _ => return Err(format!("多余的参数: {}", arg)),
+        }
+    }
+
+    let tunnel_key = parse_tunnel_key(tunnel.ok_or("请指定隧道")?)?;
+    let mut path = tunnel_path(&tunnel_key, "/stop");
+    if let Some(secs) = grace_period_secs {
+        path.push_str(&format!("?grace_period_secs={}", secs));
+    }
+    let result = request("POST", &path)?;
+    println!("{}", message(&result));
+    Ok(())
+}
+
+fn logs(args: &[String]) -> Result<(), String> {
+    let follow = args.iter().any(|arg| arg == "-f" || arg == "--follow");
+    let rest: Vec<String> = args
+        .iter()
+        .filter(|arg| *arg != "-f" && *arg != "--follow")
+        .cloned()
+        .collect();
+    let tunnel_key = parse_tunnel_key(single_arg(&rest)?)?;
+
+    if !follow {
+        let batch = request("GET", &tunnel_path(&tunnel_key, "/logs"))?;
+        for entry in batch["entries"].as_array().into_iter().flatten() {
+            print_log_entry(entry);
+        }
+        return Ok(());
+    }
+
+    // 先输出缓冲中的日志，之后持续输出新日志，直到守护进程停止或按 Ctrl+C
+    let mut reader = send("GET", &tunnel_path(&tunnel_key, "/logs/stream"))?;
+    let mut line = String::new();
+    loop {
+        line.clear();
+        match reader.read_line(&mut line) {
+            Ok(0) => return Ok(()),
+            Ok(_) => {}
+            Err(e) => return Err(format!("读取日志失败: {}", e)),
+        }
+        if let Some(data) = line.trim_end().strip_prefix("data: ") {
+            if let Ok(entry) = serde_json::from_str::<Value>(data) {
+                print_log_entry(&entry);
+            }
+        }
+    }
+}
+
+fn print_log_entry(entry: &Value) {
+    let stdout = std::io::stdout();
+    let mut stdout = stdout.lock();
+    let _ = writeln!(
+        stdout,
+        "{} {}",
+        entry["timestamp"].as_str().unwrap_or_default(),
+        entry["message"].as_str().unwrap_or_default()
+    );
+    let _ = stdout.flush();
+}
+
+fn download_frpc() -> Result<(), String> {
+    let app_dir = app_data_dir()?;
+    let path =
+        tauri::async_runtime::block_on(crate::commands::download_frpc_to(&app_dir, |progress| {
+            eprint!("\r下载中 {:.1}%", progress.percentage);
+            let _ = std::io::stderr().flush();
+        }));
+    eprintln!();
+    println!("frpc 已下载到 {}", path?);
+    Ok(())
+}
+
+fn single_arg(args: &[String]) -> Result<&str, String> {
+    match args {
+        [arg] => Ok(arg),
+        [] => Err("请指定隧道".to_string()),
+        _ => Err(format!("多余的参数: {}", args[1..].join(" "))),
+    }
+}
+
+// 纯数字视为 API 隧道的 ID
+fn parse_tunnel_key(arg: &str) -> Result<TunnelKey, String> {
+    match arg.parse::<i32>() {
+        Ok(tunnel_id) => Ok(TunnelKey::Api(tunnel_id)),
+        Err(_) => TunnelKey::try_from(arg.to_string()),
+    }
+}
+
+fn tunnel_path(tunnel_key: &TunnelKey, action: &str) -> String {
+    format!(
+        "/v1/tunnels/{}{}",
+        control_http::percent_encode(&tunnel_key.to_string()),
+        action
+    )
+}
+
+fn message(result: &Value) -> &str {
+    result["message"].as_str().unwrap_or_default()
+}
+
+// 发送请求并解析 JSON 响应
+fn request(method: &str, path: &str) -> Result<Value, String> {
+    let mut reader = send(method, path)?;
+    let mut body = String::new();
+    reader
+        .read_to_string(&mut body)
+        .map_err(|e| format!("读取响应失败: {}", e))?;
+    serde_json::from_str(&body).map_err(|e| format!("解析响应失败: {}", e))
+}
+
+// 发送请求，返回响应体，失败时返回接口给出的错误
+fn send(method: &str, path: &str) -> Result<impl BufRead, String> {
+    let app_dir = app_data_dir()?;
+    let settings: Settings =
+        crate::json_store::read_json(&app_dir.join(SETTINGS_FILE))?.unwrap_or_default();
+    let addr = ListenAddr::parse(&settings.control_api.listen)?;
+    let token = std::fs::read_to_string(app_dir.join(crate::control_api::TOKEN_FILE))
+        .map(|token| token.trim().to_string())
+        .map_err(|_| format!("未找到控制接口令牌，{}", NOT_RUNNING_HINT))?;
+
+    let (status, mut reader) = control_http::send_request(&addr, method, path, &token)
+        .map_err(|e| format!("{}，{}", e, NOT_RUNNING_HINT))?;
+    if status != 200 {
+        return Err(error_message(status, &mut reader));
+    }
+    Ok(reader)
+}
+
+fn error_message(status: u16, reader: &mut impl Read) -> String {
+    let mut body = String::new();
+    let _ = reader.read_to_string(&mut body);
+    serde_json::from_str::<Value>(&body)
+        .ok()
+        .and_then(|body| body["error"].as_str().map(str::to_string))
+        .unwrap_or_else(|| format!("请求失败，状态码 {}", status))
+}
+
+// 与 Tauri 的 app_data_dir 相同：系统数据目录下以应用标识命名的目录
+pub(crate) fn app_data_dir() -> Result<PathBuf, String> {
+    dirs::data_dir()
+        .map(|dir| dir.join(APP_IDENTIFIER))
+        .ok_or_else(|| "获取应用目录失败".to_string())
+}
+
+// Windows 上界面程序没有控制台，命令行子命令附加到启动它的控制台以输出内容
+fn attach_console() {
+    #[cfg(windows)]
+    unsafe {
+        use windows_sys::Win32::System::Console::{AttachConsole, ATTACH_PARENT_PROCESS};
+        AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
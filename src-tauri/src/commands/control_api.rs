@@ -0,0 +1,30 @@
+use crate::app_context::AppContext;
+use crate::control_api::{self, ControlApiServer, ControlApiStatus};
+use crate::settings::SettingsState;
+use tauri::State;
+
+/// 获取本地控制接口的运行状态，开关和监听地址通过 set_settings 修改
+#[tauri::command]
+pub async fn get_control_api_status(
+    server: State<'_, ControlApiServer>,
+    settings: State<'_, SettingsState>,
+) -> Result<ControlApiStatus, String> {
+    Ok(server.status(&settings.get().control_api))
+}
+
+/// 获取控制接口令牌，没有时生成一个
+#[tauri::command]
+pub async fn get_control_api_token(app_handle: tauri::AppHandle) -> Result<String, String> {
+    let app_handle = AppContext::from(app_handle);
+    control_api::load_or_create_token(&app_handle)
+}
+
+/// 重新生成控制接口令牌，旧令牌立即失效
+#[tauri::command]
+pub async fn regenerate_control_api_token(
+    app_handle: tauri::AppHandle,
+    server: State<'_, ControlApiServer>,
+) -> Result<String, String> {
+    let app_handle = AppContext::from(app_handle);
+    server.regenerate_token(&app_handle)
+}
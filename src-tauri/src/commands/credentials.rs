@@ -0,0 +1,22 @@
+use crate::app_context::AppContext;
+use crate::credentials;
+
+/// 保存用户令牌，登录后调用
+#[tauri::command]
+pub async fn set_credentials(
+    app_handle: tauri::AppHandle,
+    user_token: String,
+) -> Result<(), String> {
+    let app_handle = AppContext::from(app_handle);
+    if user_token.trim().is_empty() {
+        return Err("令牌不能为空".to_string());
+    }
+    credentials::save_token(&app_handle, user_token.trim())
+}
+
+/// 删除保存的用户令牌，退出登录时调用
+#[tauri::command]
+pub async fn clear_credentials(app_handle: tauri::AppHandle) -> Result<(), String> {
+    let app_handle = AppContext::from(app_handle);
+    credentials::clear_token(&app_handle)
+}
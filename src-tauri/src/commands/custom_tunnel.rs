@@ -1,14 +1,37 @@
-use crate::models::{FrpcProcesses, LogMessage, ProcessGuardState};
+use crate::app_context::AppContext;
+use crate::commands::process::{
+    emit_tunnel_log, prepare_frpc, spawn_exit_waiter, spawn_log_readers, stop_grace_period,
+    stop_process_gracefully,
+};
+use crate::commands::tunnel_state::set_tunnel_state;
+use crate::config_convert::{convert_config, ConfigConversion};
+use crate::config_validate::{validate_config, ConfigValidation};
+use crate::frpc_config::{parse_config, split_config, split_list, ConfigFormat, ProxyDescriptor};
+use crate::frpc_output::OutputFiles;
+use crate::json_store::{self, LockedFile};
+use crate::models::{
+    FrpcProcess, FrpcProcesses, ProcessGuardInfo, ProcessGuardState, StopOutcome, TunnelKey,
+    TunnelState,
+};
+use crate::process_registry::ProcessRecord;
+use crate::run_config;
 use serde::{Deserialize, Serialize};
 use std::fs;
-use std::io::{BufRead, BufReader};
-use std::process::{Command as StdCommand, Stdio};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Command as StdCommand, Output, Stdio};
 use std::thread;
-use tauri::{Emitter, Manager, State};
+use std::time::{Duration, Instant};
+use tauri::State;
 
 #[cfg(target_os = "windows")]
 use std::os::windows::process::CommandExt;
 
+const CUSTOM_TUNNEL_LIST_FILE: &str = "custom_tunnels.json";
+
+/// frpc verify 的最长运行时间
+const VERIFY_TIMEOUT: Duration = Duration::from_secs(15);
+
 /// 自定义隧道信息
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct CustomTunnel {
@@ -17,39 +40,59 @@ pub struct CustomTunnel {
     pub config_file: String,
     pub server_addr: Option<String>,
     pub server_port: Option<u16>,
-    pub tunnels: Vec<String>,
-    pub tunnel_type: Option<String>,
-    pub custom_domains: Option<String>,
-    pub subdomain: Option<String>,
-    pub local_ip: Option<String>,
-    pub local_port: Option<u16>,
-    pub remote_port: Option<u16>,
+    /// 配置中的代理，通常只有一个
+    #[serde(default)]
+    pub proxies: Vec<ProxyDescriptor>,
     pub created_at: String,
 }
 
-/// 保存自定义隧道配置
+/// frpc verify 的检查结果
+#[derive(Serialize, Clone, Debug)]
+pub struct ConfigVerification {
+    pub passed: bool,
+    /// frpc 输出的内容，已去除颜色代码
+    pub messages: Vec<String>,
+}
+
+/// 旧版记录只保存了一组代理信息，有多个代理时是最后一个代理的信息
+#[derive(Deserialize)]
+struct LegacyProxyFields {
+    #[serde(default)]
+    tunnels: Vec<String>,
+    tunnel_type: Option<String>,
+    custom_domains: Option<String>,
+    subdomain: Option<String>,
+    local_ip: Option<String>,
+    local_port: Option<u16>,
+    remote_port: Option<u16>,
+}
+
+/// 保存自定义隧道配置，支持 INI、TOML、YAML 和 JSON，包含多个代理时拆分为多个隧道。
+/// 配置校验有错误时拒绝保存，force 为 true 时跳过校验
 #[tauri::command]
 pub async fn save_custom_tunnel(
     app_handle: tauri::AppHandle,
     _tunnel_name: String,
     config_content: String,
+    force: Option<bool>,
 ) -> Result<Vec<CustomTunnel>, String> {
-    let split = split_ini_config(&config_content)?;
+    let app_handle = AppContext::from(app_handle);
+    let format = ConfigFormat::detect(&config_content);
+    ensure_valid_config(&config_content, format, force)?;
+    let tunnels = split_config(&config_content, format)?;
 
-    if split.tunnels.is_empty() {
+    if tunnels.is_empty() {
         return Err("配置文件中未找到隧道名称".to_string());
     }
 
-    let app_dir = app_handle
-        .path()
-        .app_data_dir()
-        .map_err(|e| format!("获取应用目录失败: {}", e))?;
+    let app_dir = app_handle.app_data_dir()?;
 
     fs::create_dir_all(&app_dir).map_err(|e| format!("创建目录失败: {}", e))?;
 
-    let mut created = Vec::with_capacity(split.tunnels.len());
+    let existing_tunnels = load_custom_tunnel_list(&app_dir)?;
+    let mut created = Vec::with_capacity(tunnels.len());
 
-    for (tunnel_name, tunnel_block) in split.tunnels {
+    for (tunnel_name, single_config) in tunnels {
         if !tunnel_name
             .chars()
             .all(|c| c.is_alphanumeric() || c == '_' || c == '-')
@@ -57,21 +100,20 @@ pub async fn save_custom_tunnel(
             return Err("配置文件中的隧道名称只能包含字母、数字、下划线和连字符".to_string());
         }
 
-        let single_ini = if split.common.trim().is_empty() {
-            tunnel_block
-        } else {
-            format!("{}\n\n{}", split.common, tunnel_block)
-        };
-
-        let parsed_info = parse_ini_config(&single_ini)?;
+        let parsed_info = parse_config(&single_config, format)?;
 
-        let config_file_name = format!("{}.ini", tunnel_name);
+        let config_file_name = format!("{}.{}", tunnel_name, format.extension());
         let config_file_path = app_dir.join(&config_file_name);
 
         eprintln!("[自定义隧道] 配置文件路径: {:?}", config_file_path);
 
-        fs::write(&config_file_path, &single_ini)
-            .map_err(|e| format!("写入配置文件失败: {}", e))?;
+        // 配置中有令牌，与其他状态文件一样原子写入且只有当前用户可以读写
+        json_store::write_file(&config_file_path, single_config.as_bytes())?;
+
+        // 同名隧道之前使用其他格式保存时删除旧文件
+        if let Some(existing) = existing_tunnels.iter().find(|t| t.id == tunnel_name) {
+            remove_stale_config(&app_dir, &existing.config_file, &config_file_name);
+        }
 
         let custom_tunnel = CustomTunnel {
             id: tunnel_name.clone(),
@@ -79,13 +121,7 @@ pub async fn save_custom_tunnel(
             config_file: config_file_name,
             server_addr: parsed_info.server_addr,
             server_port: parsed_info.server_port,
-            tunnels: parsed_info.tunnel_names,
-            tunnel_type: parsed_info.tunnel_type,
-            custom_domains: parsed_info.custom_domains,
-            subdomain: parsed_info.subdomain,
-            local_ip: parsed_info.local_ip,
-            local_port: parsed_info.local_port,
-            remote_port: parsed_info.remote_port,
+            proxies: parsed_info.proxies,
             created_at: chrono::Local::now().to_rfc3339(),
         };
 
@@ -100,41 +136,23 @@ pub async fn save_custom_tunnel(
 /// 获取所有自定义隧道列表
 #[tauri::command]
 pub async fn get_custom_tunnels(app_handle: tauri::AppHandle) -> Result<Vec<CustomTunnel>, String> {
-    let app_dir = app_handle
-        .path()
-        .app_data_dir()
-        .map_err(|e| format!("获取应用目录失败: {}", e))?;
-
-    let list_file = app_dir.join("custom_tunnels.json");
-
-    if !list_file.exists() {
-        return Ok(Vec::new());
-    }
+    load_custom_tunnels(&AppContext::from(app_handle))
+}
 
-    let content =
-        fs::read_to_string(&list_file).map_err(|e| format!("读取自定义隧道列表失败: {}", e))?;
+/// 读取自定义隧道列表，服务器地址和代理以配置文件中的为准
+pub fn load_custom_tunnels(app_handle: &AppContext) -> Result<Vec<CustomTunnel>, String> {
+    let app_dir = app_handle.app_data_dir()?;
 
-    let tunnels: Vec<CustomTunnel> =
-        serde_json::from_str(&content).map_err(|e| format!("解析自定义隧道列表失败: {}", e))?;
+    let tunnels = load_custom_tunnel_list(&app_dir)?;
 
     let mut updated = Vec::with_capacity(tunnels.len());
     for mut t in tunnels {
         let config_path = app_dir.join(&t.config_file);
         if let Ok(cfg) = fs::read_to_string(&config_path) {
-            if let Ok(parsed) = parse_ini_config(&cfg) {
+            if let Ok(parsed) = parse_config(&cfg, ConfigFormat::from_path(&config_path)) {
                 t.server_addr = parsed.server_addr.or(t.server_addr);
                 t.server_port = parsed.server_port.or(t.server_port);
-                t.tunnels = if parsed.tunnel_names.is_empty() {
-                    t.tunnels
-                } else {
-                    parsed.tunnel_names
-                };
-                t.tunnel_type = parsed.tunnel_type.or(t.tunnel_type);
-                t.custom_domains = parsed.custom_domains.or(t.custom_domains);
-                t.subdomain = parsed.subdomain.or(t.subdomain);
-                t.local_ip = parsed.local_ip.or(t.local_ip);
-                t.local_port = parsed.local_port.or(t.local_port);
-                t.remote_port = parsed.remote_port.or(t.remote_port);
+                t.proxies = parsed.proxies;
             }
         }
         updated.push(t);
@@ -143,51 +161,20 @@ pub async fn get_custom_tunnels(app_handle: tauri::AppHandle) -> Result<Vec<Cust
     Ok(updated)
 }
 
-struct IniSplitResult {
-    common: String,
-    tunnels: Vec<(String, String)>,
-}
-
-fn split_ini_config(content: &str) -> Result<IniSplitResult, String> {
-    let mut common_lines: Vec<String> = Vec::new();
-    let mut tunnels: Vec<(String, Vec<String>)> = Vec::new();
+/// 自定义隧道的配置文件路径，列表中没有记录时使用旧版的 <id>.ini
+pub fn custom_tunnel_config_path(
+    app_handle: &AppContext,
+    tunnel_id: &str,
+) -> Result<PathBuf, String> {
+    let app_dir = app_handle.app_data_dir()?;
 
-    let mut current_section: Option<String> = None;
-
-    for raw in content.lines() {
-        let trimmed = raw.trim();
-
-        if trimmed.starts_with('[') && trimmed.ends_with(']') {
-            let name = trimmed[1..trimmed.len() - 1].trim().to_string();
-            current_section = Some(name.clone());
-
-            if name == "common" {
-                common_lines.push(format!("[{}]", name));
-            } else if !name.is_empty() {
-                tunnels.push((name.clone(), vec![format!("[{}]", name)]));
-            }
-            continue;
-        }
-
-        match current_section.as_deref() {
-            Some("common") => common_lines.push(raw.to_string()),
-            Some(sec) if !sec.is_empty() && sec != "common" => {
-                if let Some((_, lines)) = tunnels.last_mut() {
-                    lines.push(raw.to_string());
-                }
-            }
-            _ => {
-            }
-        }
-    }
-
-    let common = common_lines.join("\n").trim().to_string();
-    let tunnels = tunnels
+    let config_file = load_custom_tunnel_list(&app_dir)?
         .into_iter()
-        .map(|(name, lines)| (name, lines.join("\n").trim().to_string()))
-        .collect::<Vec<_>>();
+        .find(|t| t.id == tunnel_id)
+        .map(|t| t.config_file)
+        .unwrap_or_else(|| format!("{}.ini", tunnel_id));
 
-    Ok(IniSplitResult { common, tunnels })
+    Ok(app_dir.join(config_file))
 }
 
 /// 获取自定义隧道配置文件内容
@@ -196,12 +183,8 @@ pub async fn get_custom_tunnel_config(
     app_handle: tauri::AppHandle,
     tunnel_id: String,
 ) -> Result<String, String> {
-    let app_dir = app_handle
-        .path()
-        .app_data_dir()
-        .map_err(|e| format!("获取应用目录失败: {}", e))?;
-
-    let config_file_path = app_dir.join(format!("{}.ini", tunnel_id));
+    let app_handle = AppContext::from(app_handle);
+    let config_file_path = custom_tunnel_config_path(&app_handle, &tunnel_id)?;
 
     if !config_file_path.exists() {
         return Err("配置文件不存在".to_string());
@@ -211,67 +194,247 @@ pub async fn get_custom_tunnel_config(
         .map_err(|e| format!("读取配置文件失败: {}", e))
 }
 
-/// 更新自定义隧道配置
+/// 更新自定义隧道配置，格式改变时配置文件的扩展名随之改变。
+/// 配置校验有错误时拒绝更新，force 为 true 时跳过校验
 #[tauri::command]
 pub async fn update_custom_tunnel(
     app_handle: tauri::AppHandle,
     tunnel_id: String,
     config_content: String,
+    force: Option<bool>,
 ) -> Result<CustomTunnel, String> {
-    let app_dir = app_handle
-        .path()
-        .app_data_dir()
-        .map_err(|e| format!("获取应用目录失败: {}", e))?;
+    let app_handle = AppContext::from(app_handle);
+    let format = ConfigFormat::detect(&config_content);
+    ensure_valid_config(&config_content, format, force)?;
+    let updated_tunnel =
+        store_custom_tunnel_config(&app_handle, &tunnel_id, &config_content, format)?;
+
+    eprintln!("[自定义隧道] 更新成功: {}", tunnel_id);
+    Ok(updated_tunnel)
+}
+
+/// 转换自定义隧道的配置格式，apply 为 false 时只返回预览，为 true 时写回配置文件
+#[tauri::command]
+pub async fn convert_custom_tunnel_config(
+    app_handle: tauri::AppHandle,
+    tunnel_id: String,
+    target_format: ConfigFormat,
+    apply: Option<bool>,
+) -> Result<ConfigConversion, String> {
+    let app_handle = AppContext::from(app_handle);
+    let config_path = custom_tunnel_config_path(&app_handle, &tunnel_id)?;
+    let content =
+        fs::read_to_string(&config_path).map_err(|e| format!("读取配置文件失败: {}", e))?;
+
+    let mut conversion = convert_config(
+        &content,
+        ConfigFormat::from_path(&config_path),
+        target_format,
+    )?;
+
+    if apply.unwrap_or(false) {
+        store_custom_tunnel_config(&app_handle, &tunnel_id, &conversion.content, target_format)?;
+        conversion.applied = true;
+        eprintln!(
+            "[自定义隧道] 已将 {} 的配置转换为 {} 格式",
+            tunnel_id,
+            target_format.extension()
+        );
+    }
+
+    Ok(conversion)
+}
+
+/// 校验自定义隧道配置，返回错误和警告及其所在段落和行号
+#[tauri::command]
+pub async fn validate_custom_tunnel_config(
+    config_content: String,
+) -> Result<ConfigValidation, String> {
+    Ok(validate_config(
+        &config_content,
+        ConfigFormat::detect(&config_content),
+    ))
+}
+
+/// 用 frpc verify 检查尚未保存的配置，能发现启动器自身解析不到的问题
+#[tauri::command]
+pub async fn verify_custom_tunnel_config(
+    app_handle: tauri::AppHandle,
+    config_content: String,
+) -> Result<ConfigVerification, String> {
+    let app_handle = AppContext::from(app_handle);
+    let app_dir = app_handle.app_data_dir()?;
+    let frpc_path = prepare_frpc(&app_dir)?;
+
+    // 配置中可能有令牌，临时文件写入只有当前用户可读的 run 目录；工作目录仍为
+    // 应用目录，配置中的相对路径与正式启动时一致。扩展名决定 frpc 按哪种格式解析
+    let format = ConfigFormat::detect(&config_content);
+    let temp_path = run_config::write_named(
+        &app_dir,
+        &format!(
+            "verify-{}.{}",
+            run_config::timestamp_millis(),
+            format.extension()
+        ),
+        &config_content,
+    )?;
+
+    let mut cmd = StdCommand::new(&frpc_path);
+    cmd.current_dir(&app_dir)
+        .arg("verify")
+        .arg("-c")
+        .arg(&temp_path);
+
+    #[cfg(target_os = "windows")]
+    {
+        cmd.creation_flags(0x08000000);
+    }
+
+    // frpc 卡住时不能一直占用异步线程，临时文件也要在超时后及时删除
+    let output = tokio::task::spawn_blocking(move || output_with_timeout(cmd, VERIFY_TIMEOUT))
+        .await
+        .map_err(|e| format!("运行 frpc verify 失败: {}", e))
+        .and_then(|output| output);
+    run_config::remove(&temp_path);
+    let output = output?;
+
+    let messages = [&output.stdout, &output.stderr]
+        .into_iter()
+        .flat_map(|bytes| {
+            let text = String::from_utf8_lossy(bytes);
+            strip_ansi_escapes::strip_str(&text)
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+        })
+        .collect();
+
+    Ok(ConfigVerification {
+        passed: output.status.success(),
+        messages,
+    })
+}
+
+// 运行命令并收集输出，超过 timeout 仍未结束时结束进程
+fn output_with_timeout(mut cmd: StdCommand, timeout: Duration) -> Result<Output, String> {
+    let mut child = cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("运行 frpc verify 失败: {}", e))?;
+
+    // 在单独的线程中读取输出，避免管道写满后 frpc 阻塞
+    let stdout = child.stdout.take().map(read_pipe);
+    let stderr = child.stderr.take().map(read_pipe);
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if Instant::now() < deadline => thread::sleep(Duration::from_millis(50)),
+            result => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(match result {
+                    Err(e) => format!("等待 frpc verify 失败: {}", e),
+                    _ => format!(
+                        "frpc verify 超过 {} 秒未结束，已强制停止",
+                        timeout.as_secs()
+                    ),
+                });
+            }
+        }
+    };
+
+    let collect = |handle: Option<thread::JoinHandle<Vec<u8>>>| {
+        handle
+            .and_then(|handle| handle.join().ok())
+            .unwrap_or_default()
+    };
+    Ok(Output {
+        status,
+        stdout: collect(stdout),
+        stderr: collect(stderr),
+    })
+}
+
+fn read_pipe(mut pipe: impl Read + Send + 'static) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut bytes = Vec::new();
+        let _ = pipe.read_to_end(&mut bytes);
+        bytes
+    })
+}
+
+// 校验有错误时拒绝保存，force 为 true 时只记录日志
+fn ensure_valid_config(
+    config_content: &str,
+    format: ConfigFormat,
+    force: Option<bool>,
+) -> Result<(), String> {
+    let validation = validate_config(config_content, format);
+    if validation.valid {
+        return Ok(());
+    }
+    if force.unwrap_or(false) {
+        eprintln!(
+            "[自定义隧道] 配置校验未通过，已强制保存: {}",
+            validation.error_summary()
+        );
+        return Ok(());
+    }
+    Err(format!("配置校验未通过: {}", validation.error_summary()))
+}
+
+// 写入配置文件并更新隧道列表
+fn store_custom_tunnel_config(
+    app_handle: &AppContext,
+    tunnel_id: &str,
+    config_content: &str,
+    format: ConfigFormat,
+) -> Result<CustomTunnel, String> {
+    let app_dir = app_handle.app_data_dir()?;
 
     // 解析新的配置
-    let parsed_info = parse_ini_config(&config_content)?;
+    let parsed_info = parse_config(config_content, format)?;
 
     // 配置文件路径
-    let config_file_name = format!("{}.ini", tunnel_id);
+    let config_file_name = format!("{}.{}", tunnel_id, format.extension());
     let config_file_path = app_dir.join(&config_file_name);
 
     // 写入新的配置内容
-    fs::write(&config_file_path, &config_content)
-        .map_err(|e| format!("写入配置文件失败: {}", e))?;
+    json_store::write_file(&config_file_path, config_content.as_bytes())?;
 
     // 获取现有的隧道信息以保留创建时间
-    let list_file = app_dir.join("custom_tunnels.json");
-    let existing_tunnels: Vec<CustomTunnel> = if list_file.exists() {
-        let content = fs::read_to_string(&list_file)
-            .map_err(|e| format!("读取自定义隧道列表失败: {}", e))?;
-        serde_json::from_str(&content)
-            .map_err(|e| format!("解析自定义隧道列表失败: {}", e))?
-    } else {
-        Vec::new()
-    };
+    let existing_tunnels = load_custom_tunnel_list(&app_dir)?;
+    let existing = existing_tunnels.iter().find(|t| t.id == tunnel_id);
 
-    let created_at = existing_tunnels
-        .iter()
-        .find(|t| t.id == tunnel_id)
+    let previous_config_file = existing
+        .map(|t| t.config_file.clone())
+        .unwrap_or_else(|| format!("{}.ini", tunnel_id));
+    remove_stale_config(&app_dir, &previous_config_file, &config_file_name);
+
+    let created_at = existing
         .map(|t| t.created_at.clone())
         .unwrap_or_else(|| chrono::Local::now().to_rfc3339());
 
     // 创建更新后的隧道对象
     let updated_tunnel = CustomTunnel {
-        id: tunnel_id.clone(),
-        name: tunnel_id.clone(),
+        id: tunnel_id.to_string(),
+        name: tunnel_id.to_string(),
         config_file: config_file_name,
         server_addr: parsed_info.server_addr,
         server_port: parsed_info.server_port,
-        tunnels: parsed_info.tunnel_names,
-        tunnel_type: parsed_info.tunnel_type,
-        custom_domains: parsed_info.custom_domains,
-        subdomain: parsed_info.subdomain,
-        local_ip: parsed_info.local_ip,
-        local_port: parsed_info.local_port,
-        remote_port: parsed_info.remote_port,
+        proxies: parsed_info.proxies,
         created_at,
     };
 
     // 保存到列表
-    save_custom_tunnel_list(&app_handle, &updated_tunnel)?;
+    save_custom_tunnel_list(app_handle, &updated_tunnel)?;
 
-    eprintln!("[自定义隧道] 更新成功: {}", tunnel_id);
     Ok(updated_tunnel)
 }
 
@@ -280,50 +443,22 @@ pub async fn update_custom_tunnel(
 pub async fn delete_custom_tunnel(
     app_handle: tauri::AppHandle,
     tunnel_id: String,
-    processes: State<'_, FrpcProcesses>,
 ) -> Result<(), String> {
-    // 停止隧道（如果正在运行）
-    let custom_tunnel_id = format!("custom_{}", tunnel_id);
-    let tunnel_id_hash = string_to_i32(&custom_tunnel_id);
-
-    {
-        let mut procs = processes
-            .processes
-            .lock()
-            .map_err(|e| format!("获取进程锁失败: {}", e))?;
-
-        if let Some(mut child) = procs.remove(&tunnel_id_hash) {
-            let _ = child.kill();
-            let _ = child.wait();
-        }
-    }
+    let app_handle = AppContext::from(app_handle);
+    // 与手动停止一样取消守护后停止隧道，避免退出被当作崩溃重启；未在运行时忽略
+    let _ = stop_custom(&app_handle, tunnel_id.clone(), None).await;
 
-    let app_dir = app_handle
-        .path()
-        .app_data_dir()
-        .map_err(|e| format!("获取应用目录失败: {}", e))?;
+    let app_dir = app_handle.app_data_dir()?;
 
     // 删除配置文件
-    let config_file = app_dir.join(format!("{}.ini", tunnel_id));
+    let config_file = custom_tunnel_config_path(&app_handle, &tunnel_id)?;
     if config_file.exists() {
         fs::remove_file(&config_file).map_err(|e| format!("删除配置文件失败: {}", e))?;
     }
 
     // 从列表中移除
-    let list_file = app_dir.join("custom_tunnels.json");
-    if list_file.exists() {
-        let content =
-            fs::read_to_string(&list_file).map_err(|e| format!("读取自定义隧道列表失败: {}", e))?;
-
-        let mut tunnels: Vec<CustomTunnel> =
-            serde_json::from_str(&content).map_err(|e| format!("解析自定义隧道列表失败: {}", e))?;
-
-        tunnels.retain(|t| t.id != tunnel_id);
-
-        let content = serde_json::to_string_pretty(&tunnels)
-            .map_err(|e| format!("序列化自定义隧道列表失败: {}", e))?;
-
-        fs::write(&list_file, content).map_err(|e| format!("保存自定义隧道列表失败: {}", e))?;
+    if app_dir.join(CUSTOM_TUNNEL_LIST_FILE).exists() {
+        update_custom_tunnel_list(&app_dir, |tunnels| tunnels.retain(|t| t.id != tunnel_id))?;
     }
 
     eprintln!("[自定义隧道] 删除成功: {}", tunnel_id);
@@ -335,64 +470,54 @@ pub async fn delete_custom_tunnel(
 pub async fn start_custom_tunnel(
     app_handle: tauri::AppHandle,
     tunnel_id: String,
-    processes: State<'_, FrpcProcesses>,
-    guard_state: State<'_, ProcessGuardState>,
 ) -> Result<String, String> {
-    // 生成一个唯一的进程ID
-    let custom_tunnel_id = format!("custom_{}", tunnel_id);
-    let tunnel_id_hash = string_to_i32(&custom_tunnel_id);
+    start_custom(&AppContext::from(app_handle), tunnel_id).await
+}
+
+/// 启动自定义隧道，界面、控制接口和守护进程的重启都经过这里
+pub async fn start_custom(app_handle: &AppContext, tunnel_id: String) -> Result<String, String> {
+    let tunnel_key = TunnelKey::Custom(tunnel_id.clone());
+    let processes = app_handle.state::<FrpcProcesses>();
 
     {
         let procs = processes
             .processes
             .lock()
             .map_err(|e| format!("获取进程锁失败: {}", e))?;
-        if procs.contains_key(&tunnel_id_hash) {
+        if procs.get(&tunnel_key).is_some_and(|p| p.is_running()) {
             return Err("该隧道已在运行中".to_string());
         }
     }
 
-    let app_dir = app_handle
-        .path()
-        .app_data_dir()
-        .map_err(|e| e.to_string())?;
-
-    let frpc_path = if cfg!(target_os = "windows") {
-        app_dir.join("frpc.exe")
-    } else {
-        app_dir.join("frpc")
-    };
-
-    if !frpc_path.exists() {
-        return Err("frpc 未找到，请先下载".to_string());
+    set_tunnel_state(app_handle, &tunnel_key, TunnelState::Starting);
+    let result = spawn_custom_tunnel(app_handle, tunnel_id).await;
+    if let Err(e) = &result {
+        set_tunnel_state(app_handle, &tunnel_key, TunnelState::Failed(e.clone()));
     }
+    result
+}
 
-    // Unix系统上确保有执行权限
-    #[cfg(unix)]
-    {
-        use std::os::unix::fs::PermissionsExt;
-        let metadata = fs::metadata(&frpc_path).map_err(|e| e.to_string())?;
-        let mut perms = metadata.permissions();
-        if perms.mode() & 0o111 == 0 {
-            perms.set_mode(0o755);
-            fs::set_permissions(&frpc_path, perms).map_err(|e| e.to_string())?;
-        }
-    }
+async fn spawn_custom_tunnel(app_handle: &AppContext, tunnel_id: String) -> Result<String, String> {
+    let tunnel_key = TunnelKey::Custom(tunnel_id.clone());
+    let app_dir = app_handle.app_data_dir()?;
+
+    let frpc_path = prepare_frpc(&app_dir)?;
 
-    let config_file = format!("{}.ini", tunnel_id);
-    let config_path = app_dir.join(&config_file);
+    let config_path = custom_tunnel_config_path(app_handle, &tunnel_id)?;
 
     if !config_path.exists() {
         return Err("配置文件不存在".to_string());
     }
 
-    // 启动 frpc 进程
+    let (output, stdout, stderr) = OutputFiles::create(&app_dir, &tunnel_key)?;
+
+    // 启动 frpc 进程，frpc 根据扩展名识别配置格式
     let mut cmd = StdCommand::new(&frpc_path);
     cmd.current_dir(&app_dir)
         .arg("-c")
-        .arg(&config_file)
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped());
+        .arg(&config_path)
+        .stdout(stdout)
+        .stderr(stderr);
 
     // Windows上隐藏控制台窗口
     #[cfg(target_os = "windows")]
@@ -400,107 +525,60 @@ pub async fn start_custom_tunnel(
         cmd.creation_flags(0x08000000);
     }
 
-    let mut child = cmd.spawn().map_err(|e| format!("启动 frpc 失败: {}", e))?;
+    let child = match cmd.spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            output.remove();
+            return Err(format!("启动 frpc 失败: {}", e));
+        }
+    };
 
     let pid = child.id();
+    // 在日志读取线程启动前切换，避免覆盖日志触发的状态
+    set_tunnel_state(app_handle, &tunnel_key, TunnelState::LoggingIn);
 
     // 发送启动日志
-    let timestamp = chrono::Local::now().format("%H:%M:%S").to_string();
-    let _ = app_handle.emit(
-        "frpc-log",
-        LogMessage {
-            tunnel_id: tunnel_id_hash,
-            message: format!("自定义隧道 {} 进程已启动 (PID: {})", tunnel_id, pid),
-            timestamp,
-        },
+    let _ = emit_tunnel_log(
+        app_handle,
+        &tunnel_key,
+        format!("自定义隧道 {} 进程已启动 (PID: {})", tunnel_id, pid),
     );
 
-    // 捕获 stdout
-    if let Some(stdout) = child.stdout.take() {
-        let app_handle_clone = app_handle.clone();
-        thread::Builder::new()
-            .name(format!("custom-frpc-stdout-{}", tunnel_id))
-            .spawn(move || {
-                let reader = BufReader::new(stdout);
-                for line in reader.lines().flatten() {
-                    let clean_line = strip_ansi_escapes::strip_str(&line);
-                    let timestamp = chrono::Local::now().format("%H:%M:%S").to_string();
-
-                    // 检查日志是否需要停止守护
-                    let guard_state_for_check = app_handle_clone.state::<ProcessGuardState>();
-                    let _ = tauri::async_runtime::block_on(async {
-                        crate::commands::process_guard::check_log_and_stop_guard(
-                            app_handle_clone.clone(),
-                            tunnel_id_hash,
-                            clean_line.clone(),
-                            guard_state_for_check,
-                        )
-                        .await
-                    });
-
-                    let _ = app_handle_clone.emit(
-                        "frpc-log",
-                        LogMessage {
-                            tunnel_id: tunnel_id_hash,
-                            message: clean_line,
-                            timestamp,
-                        },
-                    );
-                }
-            })
-            .ok();
+    let record = ProcessRecord::new(&tunnel_key, pid, &frpc_path, Some(&config_path), &output);
+    if let Err(e) = crate::process_registry::register(app_handle, record) {
+        eprintln!("[进程登记] 登记自定义隧道 {} 失败: {}", tunnel_id, e);
     }
 
-    // 捕获 stderr
-    if let Some(stderr) = child.stderr.take() {
-        let app_handle_clone = app_handle.clone();
-        thread::Builder::new()
-            .name(format!("custom-frpc-stderr-{}", tunnel_id))
-            .spawn(move || {
-                let reader = BufReader::new(stderr);
-                for line in reader.lines().flatten() {
-                    let clean_line = strip_ansi_escapes::strip_str(&line);
-                    let timestamp = chrono::Local::now().format("%H:%M:%S").to_string();
-
-                    // 检查错误日志是否需要停止守护
-                    let guard_state_for_check = app_handle_clone.state::<ProcessGuardState>();
-                    let _ = tauri::async_runtime::block_on(async {
-                        crate::commands::process_guard::check_log_and_stop_guard(
-                            app_handle_clone.clone(),
-                            tunnel_id_hash,
-                            clean_line.clone(),
-                            guard_state_for_check,
-                        )
-                        .await
-                    });
-
-                    let _ = app_handle_clone.emit(
-                        "frpc-log",
-                        LogMessage {
-                            tunnel_id: tunnel_id_hash,
-                            message: format!("[ERR] {}", clean_line),
-                            timestamp,
-                        },
-                    );
-                }
-            })
-            .ok();
-    }
+    let process = FrpcProcess::new(pid);
+    let log_readers = spawn_log_readers(app_handle, &tunnel_key, &process, output, false);
 
+    let exit = process.exit.clone();
+    let recent_logs = process.recent_logs.clone();
     {
-        let mut procs = processes
+        let mut procs = app_handle
+            .state::<FrpcProcesses>()
             .processes
             .lock()
             .map_err(|e| format!("获取进程锁失败: {}", e))?;
-        procs.insert(tunnel_id_hash, child);
+        procs.insert(tunnel_key.clone(), process);
     }
 
-    let _ = crate::commands::process_guard::add_guarded_custom_tunnel(
-        tunnel_id_hash,
-        tunnel_id.clone(),
-        guard_state,
-    )
-    .await;
+    spawn_exit_waiter(
+        app_handle.clone(),
+        tunnel_key,
+        child,
+        log_readers,
+        exit,
+        recent_logs,
+        None,
+    );
+
+    let _ = crate::commands::process_guard::add_guarded(
+        app_handle.state::<ProcessGuardState>(),
+        ProcessGuardInfo::Custom {
+            tunnel_id: tunnel_id.clone(),
+        },
+    );
 
     Ok(format!("自定义隧道已启动 (PID: {})", pid))
 }
@@ -508,35 +586,58 @@ pub async fn start_custom_tunnel(
 /// 停止自定义隧道
 #[tauri::command]
 pub async fn stop_custom_tunnel(
+    app_handle: tauri::AppHandle,
     tunnel_id: String,
-    processes: State<'_, FrpcProcesses>,
-    guard_state: State<'_, ProcessGuardState>,
+    grace_period_secs: Option<u64>,
 ) -> Result<String, String> {
-    let custom_tunnel_id = format!("custom_{}", tunnel_id);
-    let tunnel_id_hash = string_to_i32(&custom_tunnel_id);
+    stop_custom(&AppContext::from(app_handle), tunnel_id, grace_period_secs).await
+}
 
-    let _ =
-        crate::commands::process_guard::remove_guarded_process(tunnel_id_hash, guard_state, true)
-            .await;
+/// 停止自定义隧道并取消守护
+pub async fn stop_custom(
+    app_handle: &AppContext,
+    tunnel_id: String,
+    grace_period_secs: Option<u64>,
+) -> Result<String, String> {
+    let tunnel_key = TunnelKey::Custom(tunnel_id);
 
-    let mut procs = processes
-        .processes
-        .lock()
-        .map_err(|e| format!("获取进程锁失败: {}", e))?;
+    let _ = crate::commands::process_guard::remove_guarded(
+        app_handle.state::<ProcessGuardState>(),
+        tunnel_key.clone(),
+        true,
+    );
 
-    if let Some(mut child) = procs.remove(&tunnel_id_hash) {
-        match child.kill() {
-            Ok(_) => {
-                let _ = child.wait();
-                Ok("自定义隧道已停止".to_string())
-            }
-            Err(e) => {
-                let _ = child.wait();
-                Err(format!("停止进程失败: {}", e))
+    let process = {
+        let mut procs = app_handle
+            .state::<FrpcProcesses>()
+            .processes
+            .lock()
+            .map_err(|e| format!("获取进程锁失败: {}", e))?;
+        procs.remove(&tunnel_key)
+    };
+
+    match process {
+        Some(process) => {
+            set_tunnel_state(app_handle, &tunnel_key, TunnelState::Stopping);
+            let grace_period = stop_grace_period(grace_period_secs);
+            let outcome = stop_process_gracefully(process, grace_period).await;
+            set_tunnel_state(app_handle, &tunnel_key, TunnelState::Stopped);
+            let outcome = outcome?;
+            let _ = crate::process_registry::unregister(app_handle, &tunnel_key);
+            match outcome {
+                StopOutcome::Graceful => Ok("自定义隧道已正常退出".to_string()),
+                StopOutcome::Forced => Ok(format!(
+                    "自定义隧道未在 {} 秒内退出，已强制停止",
+                    grace_period.as_secs()
+                )),
+                StopOutcome::AlreadyExited => Ok("自定义隧道已停止".to_string()),
             }
         }
-    } else {
-        Err("该隧道未在运行".to_string())
+        None => {
+            // 可能正在等待守护进程重启
+            set_tunnel_state(app_handle, &tunnel_key, TunnelState::Stopped);
+            Err("该隧道未在运行".to_string())
+        }
     }
 }
 
@@ -546,168 +647,130 @@ pub async fn is_custom_tunnel_running(
     tunnel_id: String,
     processes: State<'_, FrpcProcesses>,
 ) -> Result<bool, String> {
-    let custom_tunnel_id = format!("custom_{}", tunnel_id);
-    let tunnel_id_hash = string_to_i32(&custom_tunnel_id);
-
-    let mut procs = processes
+    let procs = processes
         .processes
         .lock()
         .map_err(|e| format!("获取进程锁失败: {}", e))?;
 
-    if let Some(child) = procs.get_mut(&tunnel_id_hash) {
-        match child.try_wait() {
-            Ok(Some(_)) => {
-                procs.remove(&tunnel_id_hash);
-                Ok(false)
-            }
-            Ok(None) => Ok(true),
-            Err(_) => {
-                procs.remove(&tunnel_id_hash);
-                Ok(false)
-            }
-        }
-    } else {
-        Ok(false)
-    }
+    Ok(procs
+        .get(&TunnelKey::Custom(tunnel_id))
+        .is_some_and(|p| p.is_running()))
 }
 
-/// INI配置解析结果
-struct IniParsedInfo {
-    server_addr: Option<String>,
-    server_port: Option<u16>,
-    tunnel_names: Vec<String>,
-    tunnel_type: Option<String>,
-    custom_domains: Option<String>,
-    subdomain: Option<String>,
-    local_ip: Option<String>,
-    local_port: Option<u16>,
-    remote_port: Option<u16>,
+/// 读取自定义隧道列表，旧版记录会迁移为代理列表并写回
+fn load_custom_tunnel_list(app_dir: &Path) -> Result<Vec<CustomTunnel>, String> {
+    let file = json_store::lock(&app_dir.join(CUSTOM_TUNNEL_LIST_FILE))?;
+    read_custom_tunnel_list(app_dir, &file)
 }
 
-/// 解析INI配置文件
-fn parse_ini_config(content: &str) -> Result<IniParsedInfo, String> {
-    let mut server_addr = None;
-    let mut server_port = None;
-    let mut tunnel_names = Vec::new();
-    let mut tunnel_type = None;
-    let mut custom_domains = None;
-    let mut subdomain = None;
-    let mut local_ip = None;
-    let mut local_port = None;
-    let mut remote_port = None;
-
-    let mut current_section = String::new();
-    let mut tunnel_count = 0;
-
-    for line in content.lines() {
-        let line = line.trim();
-
-        // 跳过空行和注释
-        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
-            continue;
-        }
-
-        // 检测段落
-        if line.starts_with('[') && line.ends_with(']') {
-            current_section = line[1..line.len() - 1].to_string();
+// 在文件锁内读取、修改并写回自定义隧道列表
+fn update_custom_tunnel_list(
+    app_dir: &Path,
+    update: impl FnOnce(&mut Vec<CustomTunnel>),
+) -> Result<(), String> {
+    let file = json_store::lock(&app_dir.join(CUSTOM_TUNNEL_LIST_FILE))?;
+    let mut tunnels = read_custom_tunnel_list(app_dir, &file)?;
+    update(&mut tunnels);
+    file.write(&tunnels)
+}
 
-            // 如果不是common段，则是隧道段
-            if current_section != "common" && !current_section.is_empty() {
-                tunnel_count += 1;
-                tunnel_names.push(current_section.clone());
-            }
-            continue;
-        }
+fn read_custom_tunnel_list(app_dir: &Path, file: &LockedFile) -> Result<Vec<CustomTunnel>, String> {
+    let records: Vec<serde_json::Value> = match file.read()? {
+        Some(records) => records,
+        None => return Ok(Vec::new()),
+    };
 
-        // 解析键值对
-        if let Some(pos) = line.find('=') {
-            let key = line[..pos].trim();
-            let value = line[pos + 1..].trim();
-
-            if current_section == "common" {
-                match key {
-                    "server_addr" => server_addr = Some(value.to_string()),
-                    "server_port" => {
-                        server_port = value.parse::<u16>().ok();
-                    }
-                    _ => {}
-                }
-            } else {
-                // 解析隧道段的配置
-                match key {
-                    "type" => tunnel_type = Some(value.to_string()),
-                    "custom_domains" => custom_domains = Some(value.to_string()),
-                    "subdomain" => subdomain = Some(value.to_string()),
-                    "local_ip" => local_ip = Some(value.to_string()),
-                    "local_port" => local_port = value.parse::<u16>().ok(),
-                    "remote_port" => remote_port = value.parse::<u16>().ok(),
-                    _ => {}
-                }
-            }
+    let mut tunnels = Vec::with_capacity(records.len());
+    let mut migrated = 0;
+    for record in records {
+        let legacy = record.get("proxies").is_none();
+        let mut tunnel: CustomTunnel = serde_json::from_value(record.clone())
+            .map_err(|e| format!("解析自定义隧道列表失败: {}", e))?;
+        if legacy {
+            let fields: LegacyProxyFields = serde_json::from_value(record)
+                .map_err(|e| format!("解析自定义隧道列表失败: {}", e))?;
+            tunnel.proxies = migrate_proxies(app_dir, &tunnel, fields);
+            migrated += 1;
         }
+        tunnels.push(tunnel);
     }
 
-    if tunnel_count == 0 {
-        return Err("配置文件必须包含至少一个隧道段".to_string());
+    if migrated > 0 {
+        file.write(&tunnels)?;
+        eprintln!("[自定义隧道] 已迁移 {} 条旧版隧道记录", migrated);
     }
 
-    Ok(IniParsedInfo {
-        server_addr,
-        server_port,
-        tunnel_names,
-        tunnel_type,
-        custom_domains,
-        subdomain,
-        local_ip,
-        local_port,
-        remote_port,
-    })
+    Ok(tunnels)
 }
 
-/// 保存自定义隧道到列表
-fn save_custom_tunnel_list(
-    app_handle: &tauri::AppHandle,
+// 优先从配置文件读取代理信息，配置文件无法解析时使用旧记录中的信息
+fn migrate_proxies(
+    app_dir: &Path,
     tunnel: &CustomTunnel,
-) -> Result<(), String> {
-    let app_dir = app_handle
-        .path()
-        .app_data_dir()
-        .map_err(|e| format!("获取应用目录失败: {}", e))?;
-
-    let list_file = app_dir.join("custom_tunnels.json");
-
-    let mut tunnels: Vec<CustomTunnel> = if list_file.exists() {
-        let content =
-            fs::read_to_string(&list_file).map_err(|e| format!("读取自定义隧道列表失败: {}", e))?;
-        serde_json::from_str(&content).map_err(|e| format!("解析自定义隧道列表失败: {}", e))?
-    } else {
-        Vec::new()
-    };
-
-    // 如果已存在，则更新；否则添加
-    if let Some(existing) = tunnels.iter_mut().find(|t| t.id == tunnel.id) {
-        *existing = tunnel.clone();
-    } else {
-        tunnels.push(tunnel.clone());
+    fields: LegacyProxyFields,
+) -> Vec<ProxyDescriptor> {
+    let config_path = app_dir.join(&tunnel.config_file);
+    if let Ok(content) = fs::read_to_string(&config_path) {
+        if let Ok(parsed) = parse_config(&content, ConfigFormat::from_path(&config_path)) {
+            return parsed.proxies;
+        }
     }
 
-    let content = serde_json::to_string_pretty(&tunnels)
-        .map_err(|e| format!("序列化自定义隧道列表失败: {}", e))?;
-
-    fs::write(&list_file, content).map_err(|e| format!("保存自定义隧道列表失败: {}", e))?;
-
-    Ok(())
+    let mut names = fields.tunnels;
+    if names.is_empty() {
+        names.push(tunnel.name.clone());
+    }
+    let last = names.len() - 1;
+    names
+        .into_iter()
+        .enumerate()
+        .map(|(index, name)| {
+            if index != last {
+                return ProxyDescriptor {
+                    name,
+                    ..Default::default()
+                };
+            }
+            ProxyDescriptor {
+                name,
+                proxy_type: fields.tunnel_type.clone(),
+                local_ip: fields.local_ip.clone(),
+                local_port: fields.local_port,
+                remote_port: fields.remote_port,
+                custom_domains: fields
+                    .custom_domains
+                    .as_deref()
+                    .map(split_list)
+                    .unwrap_or_default(),
+                subdomain: fields.subdomain.clone(),
+                plugin: None,
+            }
+        })
+        .collect()
 }
 
-/// 将字符串转换为i32（用于进程ID）
-fn string_to_i32(s: &str) -> i32 {
-    use std::collections::hash_map::DefaultHasher;
-    use std::hash::{Hash, Hasher};
+// 配置文件改名后删除旧文件
+fn remove_stale_config(app_dir: &Path, previous: &str, current: &str) {
+    if previous == current {
+        return;
+    }
+    if let Err(e) = fs::remove_file(app_dir.join(previous)) {
+        if e.kind() != std::io::ErrorKind::NotFound {
+            eprintln!("[自定义隧道] 删除旧配置文件 {} 失败: {}", previous, e);
+        }
+    }
+}
 
-    let mut hasher = DefaultHasher::new();
-    s.hash(&mut hasher);
-    let hash = hasher.finish();
+/// 保存自定义隧道到列表
+fn save_custom_tunnel_list(app_handle: &AppContext, tunnel: &CustomTunnel) -> Result<(), String> {
+    let app_dir = app_handle.app_data_dir()?;
 
-    // 确保返回正数
-    (hash as i32).abs()
+    update_custom_tunnel_list(&app_dir, |tunnels| {
+        // 如果已存在，则更新；否则添加
+        if let Some(existing) = tunnels.iter_mut().find(|t| t.id == tunnel.id) {
+            *existing = tunnel.clone();
+        } else {
+            tunnels.push(tunnel.clone());
+        }
+    })
 }
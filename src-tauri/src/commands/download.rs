@@ -3,6 +3,7 @@ use futures_util::StreamExt;
 use sha2::{Digest, Sha256};
 use std::fs;
 use std::io::Read;
+use std::path::Path;
 use tauri::{Emitter, Manager};
 
 // 从 API 获取下载信息
@@ -162,18 +163,30 @@ pub async fn get_download_url() -> Result<String, String> {
 
 #[tauri::command]
 pub async fn download_frpc(app_handle: tauri::AppHandle) -> Result<String, String> {
+    let app_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| e.to_string())?;
+
+    download_frpc_to(&app_dir, |progress| {
+        let _ = app_handle.emit("download-progress", progress);
+    })
+    .await
+}
+
+/// 下载 frpc 到指定目录并校验，下载过程中通过 on_progress 报告进度。
+/// 命令行的 download-frpc 子命令没有 AppHandle，同样调用这里
+pub async fn download_frpc_to(
+    app_dir: &Path,
+    on_progress: impl Fn(DownloadProgress) + Send + Sync,
+) -> Result<String, String> {
     // 从 API 获取下载信息
     let download_info = get_download_info().await?;
     let url = download_info.url;
     let expected_hash = download_info.hash;
     let expected_size = download_info.size;
 
-    let app_dir = app_handle
-        .path()
-        .app_data_dir()
-        .map_err(|e| e.to_string())?;
-
-    fs::create_dir_all(&app_dir).map_err(|e| e.to_string())?;
+    fs::create_dir_all(app_dir).map_err(|e| e.to_string())?;
 
     let frpc_path = if cfg!(target_os = "windows") {
         app_dir.join("frpc.exe")
@@ -314,14 +327,11 @@ pub async fn download_frpc(app_handle: tauri::AppHandle) -> Result<String, Strin
 
                     // 发送进度更新（每 100KB 发送一次）
                     if this_chunk_size >= 100 * 1024 {
-                        let _ = app_handle.emit(
-                            "download-progress",
-                            DownloadProgress {
-                                downloaded,
-                                total: total_size,
-                                percentage,
-                            },
-                        );
+                        on_progress(DownloadProgress {
+                            downloaded,
+                            total: total_size,
+                            percentage,
+                        });
                         this_chunk_size = 0;
                     }
                 }
@@ -356,14 +366,11 @@ pub async fn download_frpc(app_handle: tauri::AppHandle) -> Result<String, Strin
     use std::io::Write;
     file.flush().map_err(|e| format!("刷新文件失败: {}", e))?;
 
-    let _ = app_handle.emit(
-        "download-progress",
-        DownloadProgress {
-            downloaded,
-            total: total_size,
-            percentage: 100.0,
-        },
-    );
+    on_progress(DownloadProgress {
+        downloaded,
+        total: total_size,
+        percentage: 100.0,
+    });
 
     // 验证下载的文件大小（如果知道预期大小）
     if total_size > 0 && downloaded < total_size {
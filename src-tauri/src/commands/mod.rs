@@ -1,20 +1,32 @@
 // 命令模块
 pub mod autostart;
 pub mod background;
+pub mod control_api;
+pub mod credentials;
 pub mod custom_tunnel;
 pub mod download;
 pub mod http;
 pub mod ping;
 pub mod process;
 pub mod process_guard;
+pub mod settings;
+pub mod storage;
 pub mod tray;
+pub mod tunnel_logs;
+pub mod tunnel_state;
 
 // 重新导出所有命令函数，方便使用
 pub use autostart::*;
 pub use background::*;
+pub use control_api::*;
+pub use credentials::*;
 pub use custom_tunnel::*;
 pub use download::*;
 pub use http::*;
 pub use ping::*;
 pub use process::*;
+pub use settings::*;
+pub use storage::*;
 pub use tray::*;
+pub use tunnel_logs::*;
+pub use tunnel_state::*;
@@ -1,36 +1,31 @@
-use crate::models::{FrpcProcesses, LogMessage, ProcessGuardState};
-use crate::utils::sanitize_log;
-use std::io::{BufRead, BufReader};
-use std::process::{Command as StdCommand, Stdio};
-use std::thread;
-use tauri::{Emitter, Manager, State};
+use crate::app_context::AppContext;
+use crate::commands::tunnel_state::{apply_frpc_event, apply_process_exit, set_tunnel_state};
+use crate::frpc_log;
+use crate::frpc_output::{self, OutputFiles};
+use crate::models::{
+    ExitInfo, FrpcProcess, FrpcProcesses, LogMessage, ProcessGuardInfo, ProcessGuardState,
+    StopOutcome, TunnelExitEvent, TunnelKey, TunnelLogBatch, TunnelLogBuffers, TunnelState,
+    LOG_BUFFER_CAPACITY,
+};
+use crate::process_registry::ProcessRecord;
+use crate::redact::tunnel_redactor;
+use crate::run_config;
+use crate::settings::{ApiLaunchMode, SettingsState};
+use crate::tunnel_logs::{append_log_line, close_log_file, TunnelLogFiles};
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command as StdCommand};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use tauri::State;
 
 #[cfg(target_os = "windows")]
 use std::os::windows::process::CommandExt;
 
-#[tauri::command]
-pub async fn start_frpc(
-    app_handle: tauri::AppHandle,
-    tunnel_id: i32,
-    user_token: String,
-    processes: State<'_, FrpcProcesses>,
-    guard_state: State<'_, ProcessGuardState>,
-) -> Result<String, String> {
-    {
-        let procs = processes
-            .processes
-            .lock()
-            .map_err(|e| format!("获取进程锁失败: {}", e))?;
-        if procs.contains_key(&tunnel_id) {
-            return Err("该隧道已在运行中".to_string());
-        }
-    }
-
-    let app_dir = app_handle
-        .path()
-        .app_data_dir()
-        .map_err(|e| e.to_string())?;
-
+/// frpc 可执行文件的路径，文件不存在时返回错误，Unix 系统上确保有执行权限
+pub fn prepare_frpc(app_dir: &Path) -> Result<PathBuf, String> {
     let frpc_path = if cfg!(target_os = "windows") {
         app_dir.join("frpc.exe")
     } else {
@@ -52,14 +47,125 @@ pub async fn start_frpc(
             fs::set_permissions(&frpc_path, perms).map_err(|e| e.to_string())?;
         }
     }
+
+    Ok(frpc_path)
+}
+
+/// 停止隧道时等待 frpc 自行退出的默认时间（秒）
+pub const DEFAULT_STOP_GRACE_PERIOD_SECS: u64 = 5;
+
+/// 等待时间上限，避免前端传入过大的值导致停止操作长时间挂起
+const MAX_STOP_GRACE_PERIOD_SECS: u64 = 60;
+
+/// 进程退出后等待日志读取线程读完剩余输出的时间
+const LOG_READER_DRAIN_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// 强制结束后等待进程退出的时间
+const FORCE_KILL_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 退出事件中附带的最近日志行数
+const RECENT_LOG_LINES: usize = 20;
+
+#[tauri::command]
+pub async fn start_frpc(app_handle: tauri::AppHandle, tunnel_id: i32) -> Result<String, String> {
+    start_api(&AppContext::from(app_handle), tunnel_id).await
+}
+
+/// 启动隧道，控制接口和守护进程的重启通过这里启动两种隧道
+pub async fn start_tunnel(
+    app_handle: &AppContext,
+    tunnel_key: &TunnelKey,
+) -> Result<String, String> {
+    match tunnel_key {
+        TunnelKey::Api(tunnel_id) => start_api(app_handle, *tunnel_id).await,
+        TunnelKey::Custom(tunnel_id) => {
+            crate::commands::custom_tunnel::start_custom(app_handle, tunnel_id.clone()).await
+        }
+    }
+}
+
+/// 停止隧道并取消守护
+pub async fn stop_tunnel(
+    app_handle: &AppContext,
+    tunnel_key: &TunnelKey,
+    grace_period_secs: Option<u64>,
+) -> Result<String, String> {
+    match tunnel_key {
+        TunnelKey::Api(tunnel_id) => stop_api(app_handle, *tunnel_id, grace_period_secs).await,
+        TunnelKey::Custom(tunnel_id) => {
+            crate::commands::custom_tunnel::stop_custom(
+                app_handle,
+                tunnel_id.clone(),
+                grace_period_secs,
+            )
+            .await
+        }
+    }
+}
+
+/// 启动 API 隧道
+pub async fn start_api(app_handle: &AppContext, tunnel_id: i32) -> Result<String, String> {
+    let tunnel_key = TunnelKey::Api(tunnel_id);
+    let processes = app_handle.state::<FrpcProcesses>();
+    {
+        let procs = processes
+            .processes
+            .lock()
+            .map_err(|e| format!("获取进程锁失败: {}", e))?;
+        if procs.get(&tunnel_key).is_some_and(|p| p.is_running()) {
+            return Err("该隧道已在运行中".to_string());
+        }
+    }
+
+    set_tunnel_state(app_handle, &tunnel_key, TunnelState::Starting);
+    let result = spawn_frpc(app_handle, tunnel_id).await;
+    if let Err(e) = &result {
+        set_tunnel_state(app_handle, &tunnel_key, TunnelState::Failed(e.clone()));
+    }
+    result
+}
+
+async fn spawn_frpc(app_handle: &AppContext, tunnel_id: i32) -> Result<String, String> {
+    let tunnel_key = TunnelKey::Api(tunnel_id);
+    let user_token = crate::credentials::require_token(app_handle)?;
+    let app_dir = app_handle.app_data_dir()?;
+
+    let frpc_path = prepare_frpc(&app_dir)?;
     let mut cmd = StdCommand::new(&frpc_path);
-    cmd.current_dir(&app_dir) // 没有这个会在src-tauri目录生成frpc.ini文件
-        .arg("-u")
-        .arg(&user_token)
-        .arg("-p")
-        .arg(tunnel_id.to_string())
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped());
+    cmd.current_dir(&app_dir); // 没有这个会在src-tauri目录生成frpc.ini文件
+
+    let launch_mode = app_handle.state::<SettingsState>().get().api_launch_mode;
+    let config_file = match launch_mode {
+        ApiLaunchMode::ConfigFile => {
+            let mut content = fetch_run_config(app_handle, &user_token, tunnel_id).await?;
+            // 配置每次启动都会重新获取，守护进程修复 TLS 后的重启需要再次修改
+            if let Some(fixed) =
+                crate::commands::process_guard::pending_tls_fix(app_handle, &tunnel_key, &content)
+            {
+                content = fixed;
+            }
+            let path = run_config::write(&app_dir, &tunnel_key, &content)?;
+            cmd.arg("-c").arg(&path);
+            Some(path)
+        }
+        ApiLaunchMode::CommandLine => {
+            cmd.arg("-u")
+                .arg(&user_token)
+                .arg("-p")
+                .arg(tunnel_id.to_string());
+            None
+        }
+    };
+    let (output, stdout, stderr) = match OutputFiles::create(&app_dir, &tunnel_key) {
+        Ok(created) => created,
+        Err(e) => {
+            if let Some(path) = &config_file {
+                run_config::remove(path);
+            }
+            return Err(e);
+        }
+    };
+    cmd.stdout(stdout).stderr(stderr);
 
     // Windows上隐藏控制台窗口
     #[cfg(target_os = "windows")]
@@ -67,203 +173,606 @@ pub async fn start_frpc(
         cmd.creation_flags(0x08000000);
     }
 
-    let mut child = cmd.spawn().map_err(|e| format!("启动 frpc 失败: {}", e))?;
+    let child = match cmd.spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            if let Some(path) = &config_file {
+                run_config::remove(path);
+            }
+            output.remove();
+            return Err(format!("启动 frpc 失败: {}", e));
+        }
+    };
 
     let pid = child.id();
+    // 在日志读取线程启动前切换，避免覆盖日志触发的状态
+    set_tunnel_state(app_handle, &tunnel_key, TunnelState::LoggingIn);
+
+    let _ = emit_tunnel_log(
+        app_handle,
+        &tunnel_key,
+        format!("frpc 进程已启动 (PID: {}), 开始连接服务器...", pid),
+    );
+
+    let record = ProcessRecord::new(
+        &tunnel_key,
+        pid,
+        &frpc_path,
+        Some(config_file.as_deref().unwrap_or(&app_dir.join("frpc.ini"))),
+        &output,
+    );
+    if let Err(e) = crate::process_registry::register(app_handle, record) {
+        eprintln!("[进程登记] 登记隧道 {} 失败: {}", tunnel_id, e);
+    }
+
+    let process = FrpcProcess::new(pid);
+    // 在登记进程之后启动，脱敏时可以读到本次运行的配置文件
+    let log_readers = spawn_log_readers(app_handle, &tunnel_key, &process, output, false);
+
+    let exit = process.exit.clone();
+    let recent_logs = process.recent_logs.clone();
+    {
+        let mut procs = app_handle
+            .state::<FrpcProcesses>()
+            .processes
+            .lock()
+            .map_err(|e| format!("获取进程锁失败: {}", e))?;
+        procs.insert(tunnel_key.clone(), process);
+    }
+
+    spawn_exit_waiter(
+        app_handle.clone(),
+        tunnel_key,
+        child,
+        log_readers,
+        exit,
+        recent_logs,
+        config_file,
+    );
+
+    let _ = crate::commands::process_guard::add_guarded(
+        app_handle.state::<ProcessGuardState>(),
+        ProcessGuardInfo::Api { tunnel_id },
+    );
+
+    Ok(format!("frpc 已启动 (PID: {})", pid))
+}
+
+// 获取 API 隧道的配置。守护进程重启时 API 暂时不可用，则使用上次获取的配置，
+// 避免断网期间重启失败
+async fn fetch_run_config(
+    app_handle: &AppContext,
+    user_token: &str,
+    tunnel_id: i32,
+) -> Result<String, String> {
+    let tunnel_key = TunnelKey::Api(tunnel_id);
+    let error = match run_config::fetch_api_tunnel_config(user_token, tunnel_id).await {
+        Ok(content) => {
+            crate::commands::process_guard::remember_run_config(app_handle, &tunnel_key, &content);
+            return Ok(content);
+        }
+        Err(e) => e,
+    };
+    match crate::commands::process_guard::restart_run_config(app_handle, &tunnel_key) {
+        Some(content) => {
+            let _ = emit_tunnel_log(
+                app_handle,
+                &tunnel_key,
+                format!("{}，使用上次获取的配置重启", error),
+            );
+            Ok(content)
+        }
+        None => Err(error),
+    }
+}
+
+#[tauri::command]
+pub async fn stop_frpc(
+    app_handle: tauri::AppHandle,
+    tunnel_id: i32,
+    grace_period_secs: Option<u64>,
+) -> Result<String, String> {
+    stop_api(&AppContext::from(app_handle), tunnel_id, grace_period_secs).await
+}
+
+/// 停止 API 隧道并取消守护
+pub async fn stop_api(
+    app_handle: &AppContext,
+    tunnel_id: i32,
+    grace_period_secs: Option<u64>,
+) -> Result<String, String> {
+    let tunnel_key = TunnelKey::Api(tunnel_id);
+    let _ = crate::commands::process_guard::remove_guarded(
+        app_handle.state::<ProcessGuardState>(),
+        tunnel_key.clone(),
+        true,
+    );
+
+    let process = {
+        let mut procs = app_handle
+            .state::<FrpcProcesses>()
+            .processes
+            .lock()
+            .map_err(|e| format!("获取进程锁失败: {}", e))?;
+        procs.remove(&tunnel_key)
+    };
+
+    match process {
+        Some(process) => {
+            set_tunnel_state(app_handle, &tunnel_key, TunnelState::Stopping);
+            let grace_period = stop_grace_period(grace_period_secs);
+            let outcome = stop_process_gracefully(process, grace_period).await;
+            set_tunnel_state(app_handle, &tunnel_key, TunnelState::Stopped);
+            let outcome = outcome?;
+            let _ = crate::process_registry::unregister(app_handle, &tunnel_key);
+            match outcome {
+                StopOutcome::Graceful => Ok("frpc 已正常退出".to_string()),
+                StopOutcome::Forced => Ok(format!(
+                    "frpc 未在 {} 秒内退出，已强制停止",
+                    grace_period.as_secs()
+                )),
+                StopOutcome::AlreadyExited => Ok("frpc 已停止".to_string()),
+            }
+        }
+        None => {
+            // 可能正在等待守护进程重启
+            set_tunnel_state(app_handle, &tunnel_key, TunnelState::Stopped);
+            Err("该隧道未在运行".to_string())
+        }
+    }
+}
+
+/// 计算本次停止的等待时间
+pub fn stop_grace_period(grace_period_secs: Option<u64>) -> Duration {
+    Duration::from_secs(
+        grace_period_secs
+            .unwrap_or(DEFAULT_STOP_GRACE_PERIOD_SECS)
+            .min(MAX_STOP_GRACE_PERIOD_SECS),
+    )
+}
+
+/// 优雅停止 frpc：先通知进程退出，让它有机会向节点发送关闭代理的消息，
+/// 超过等待时间仍未退出时再强制结束
+pub async fn stop_process_gracefully(
+    process: FrpcProcess,
+    grace_period: Duration,
+) -> Result<StopOutcome, String> {
+    tokio::task::spawn_blocking(move || shutdown_process(process, grace_period))
+        .await
+        .map_err(|e| format!("停止进程失败: {}", e))?
+}
+
+fn shutdown_process(process: FrpcProcess, grace_period: Duration) -> Result<StopOutcome, String> {
+    if !process.is_running() {
+        return Ok(StopOutcome::AlreadyExited);
+    }
+
+    let pid = process.pid;
+    let signalled = match request_graceful_exit(pid) {
+        Ok(_) => true,
+        Err(e) => {
+            eprintln!("[进程] 向 frpc (PID: {}) 发送退出信号失败: {}", pid, e);
+            false
+        }
+    };
 
-    let timestamp = chrono::Local::now().format("%H:%M:%S").to_string();
-    let _ = app_handle.emit(
+    // 等待线程会在进程退出且日志读完后才标记退出，
+    // 因此返回时关闭代理等最后的输出已经发送出去
+    if signalled && process.wait_for_exit(grace_period) {
+        return Ok(StopOutcome::Graceful);
+    }
+
+    let killed = crate::process_registry::kill_pid(pid);
+    if process.wait_for_exit(FORCE_KILL_TIMEOUT) {
+        Ok(if killed {
+            StopOutcome::Forced
+        } else {
+            // kill 失败时进程可能恰好已经退出
+            StopOutcome::Graceful
+        })
+    } else {
+        Err(format!("停止进程失败: 无法结束 PID {}", pid))
+    }
+}
+
+pub fn push_recent_log(recent_logs: &Mutex<VecDeque<String>>, line: String) {
+    if let Ok(mut logs) = recent_logs.lock() {
+        if logs.len() >= RECENT_LOG_LINES {
+            logs.pop_front();
+        }
+        logs.push_back(line);
+    }
+}
+
+/// 写入日志缓冲并发送 frpc-log 事件，返回日志序号
+pub fn emit_tunnel_log(
+    app_handle: &AppContext,
+    tunnel_key: &TunnelKey,
+    message: String,
+) -> Result<u64, String> {
+    let now = chrono::Local::now();
+    let entry = app_handle
+        .state::<TunnelLogBuffers>()
+        .push(tunnel_key, message, now);
+    app_handle.emit(
         "frpc-log",
         LogMessage {
-            tunnel_id,
-            message: format!("frpc 进程已启动 (PID: {}), 开始连接服务器...", pid),
-            timestamp: timestamp.clone(),
+            tunnel_key: entry.tunnel_key,
+            tunnel_id: entry.tunnel_id,
+            message: entry.message,
+            timestamp: now.format("%H:%M:%S").to_string(),
+            seq: entry.seq,
         },
-    );
+    )?;
+    Ok(entry.seq)
+}
+
+/// 处理 frpc 输出的一行日志：发送原始日志和解析后的 frpc-event，并执行守护规则
+///
+/// line 为去除颜色和 token 后的日志，message 为发送给前端的内容（stderr 带 [ERR] 前缀）。
+/// 发送失败只记录错误，读取线程不能因此退出，否则之后的日志都不会再发送
+pub fn handle_frpc_line(
+    app_handle: &AppContext,
+    tunnel_key: &TunnelKey,
+    line: &str,
+    message: String,
+) {
+    if let Err(e) = process_frpc_line(app_handle, tunnel_key, line, message) {
+        eprintln!("[日志] 处理 {} 的日志失败: {}", tunnel_key, e);
+    }
+}
 
-    // 捕获 stdout
-    if let Some(stdout) = child.stdout.take() {
-        let app_handle_clone = app_handle.clone();
-        let tunnel_id_clone = tunnel_id;
-        let user_token_clone = user_token.clone();
+fn process_frpc_line(
+    app_handle: &AppContext,
+    tunnel_key: &TunnelKey,
+    line: &str,
+    message: String,
+) -> Result<(), String> {
+    let seq = emit_tunnel_log(app_handle, tunnel_key, message)?;
+
+    crate::commands::process_guard::apply_guard_rules(app_handle, tunnel_key, line);
+
+    if let Some(event) = frpc_log::to_event(tunnel_key, seq, line) {
+        apply_frpc_event(app_handle, &event);
+        crate::commands::process_guard::check_tls_remediation(app_handle, &event);
+        app_handle.emit("frpc-event", event)?;
+    }
+    Ok(())
+}
+
+/// 跟踪 frpc 输出文件的读取线程
+pub struct LogReaders {
+    handles: Vec<JoinHandle<()>>,
+    stopped: Arc<AtomicBool>,
+    output: OutputFiles,
+}
+
+impl LogReaders {
+    // 进程退出后调用：等读取线程把剩余的日志发送出去，再删除输出文件
+    fn finish(self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        drain_log_readers(self.handles);
+        self.output.remove();
+    }
+}
+
+/// 为 frpc 的 stdout 和 stderr 启动读取线程。日志去除颜色和密钥后写入日志文件和最近日志，
+/// 并交给 handle_frpc_line 处理。from_end 用于重新接管的进程，跳过接管前的输出
+pub fn spawn_log_readers(
+    app_handle: &AppContext,
+    tunnel_key: &TunnelKey,
+    process: &FrpcProcess,
+    output: OutputFiles,
+    from_end: bool,
+) -> LogReaders {
+    let redactor = Arc::new(tunnel_redactor(app_handle, tunnel_key));
+    let log_file = match app_handle
+        .state::<TunnelLogFiles>()
+        .writer(app_handle, tunnel_key)
+    {
+        Ok(writer) => Some(writer),
+        Err(e) => {
+            eprintln!("[日志文件] 打开隧道 {} 的日志文件失败: {}", tunnel_key, e);
+            None
+        }
+    };
+    let stopped = Arc::new(AtomicBool::new(false));
+    let mut handles = Vec::new();
+
+    for (stream, path) in [("stdout", &output.stdout), ("stderr", &output.stderr)] {
+        let app_handle = app_handle.clone();
+        let tunnel_key = tunnel_key.clone();
+        let redactor = redactor.clone();
+        let recent_logs = process.recent_logs.clone();
+        let log_file = log_file.clone();
+        let stopped = stopped.clone();
+        let path = path.clone();
         match thread::Builder::new()
-            .name(format!("frpc-stdout-{}", tunnel_id))
+            .name(format!("frpc-{}-{}", stream, tunnel_key))
             .spawn(move || {
-                let reader = BufReader::new(stdout);
-                for line in reader.lines().flatten() {
-                    // 去除 ANSI 颜色代码
-                    let clean_line = strip_ansi_escapes::strip_str(&line);
-
-                    // 隐藏用户 token
-                    let sanitized_line = sanitize_log(&clean_line, &user_token_clone);
-
-                    let timestamp = chrono::Local::now().format("%H:%M:%S").to_string();
-
-                    // 检查日志是否需要停止守护
-                    let guard_state_for_check =
-                        app_handle_clone.state::<crate::models::ProcessGuardState>();
-                    let _ = tauri::async_runtime::block_on(async {
-                        crate::commands::process_guard::check_log_and_stop_guard(
-                            app_handle_clone.clone(),
-                            tunnel_id_clone,
-                            sanitized_line.clone(),
-                            guard_state_for_check,
-                        )
-                        .await
-                    });
-
-                    if let Err(_) = app_handle_clone.emit(
-                        "frpc-log",
-                        LogMessage {
-                            tunnel_id: tunnel_id_clone,
-                            message: sanitized_line,
-                            timestamp,
-                        },
-                    ) {
-                        break;
+                let result = frpc_output::follow(&path, from_end, &stopped, |line| {
+                    // 去除 ANSI 颜色代码，隐藏令牌和配置中的密钥
+                    let clean_line = redactor.redact(&strip_ansi_escapes::strip_str(&line));
+                    let message = if stream == "stderr" {
+                        format!("[ERR] {}", clean_line)
+                    } else {
+                        clean_line.clone()
+                    };
+                    push_recent_log(&recent_logs, message.clone());
+                    if let Some(log_file) = &log_file {
+                        append_log_line(log_file, &message);
                     }
+
+                    handle_frpc_line(&app_handle, &tunnel_key, &clean_line, message);
+                });
+                if let Err(e) = result {
+                    eprintln!("[日志] 读取 {} 的 {} 失败: {}", tunnel_key, stream, e);
+                }
+                if let Some(log_file) = &log_file {
+                    close_log_file(log_file);
                 }
             }) {
-            Ok(_) => {}
-            Err(e) => eprintln!("[错误] 创建 stdout 监听线程失败: {}", e),
+            Ok(handle) => handles.push(handle),
+            Err(e) => eprintln!("[错误] 创建 {} 监听线程失败: {}", stream, e),
         }
     }
 
-    // 捕获 stderr
-    if let Some(stderr) = child.stderr.take() {
-        let app_handle_clone = app_handle.clone();
-        let tunnel_id_clone = tunnel_id;
-        let user_token_clone = user_token.clone();
-        match thread::Builder::new()
-            .name(format!("frpc-stderr-{}", tunnel_id))
-            .spawn(move || {
-                let reader = BufReader::new(stderr);
-                for line in reader.lines().flatten() {
-                    // 去除 ANSI 颜色代码
-                    let clean_line = strip_ansi_escapes::strip_str(&line);
-
-                    // 隐藏用户 token
-                    let sanitized_line = sanitize_log(&clean_line, &user_token_clone);
-
-                    let timestamp = chrono::Local::now().format("%H:%M:%S").to_string();
-
-                    // 检查错误日志是否需要停止守护
-                    let guard_state_for_check =
-                        app_handle_clone.state::<crate::models::ProcessGuardState>();
-                    let _ = tauri::async_runtime::block_on(async {
-                        crate::commands::process_guard::check_log_and_stop_guard(
-                            app_handle_clone.clone(),
-                            tunnel_id_clone,
-                            sanitized_line.clone(),
-                            guard_state_for_check,
-                        )
-                        .await
-                    });
-
-                    if let Err(_) = app_handle_clone.emit(
-                        "frpc-log",
-                        LogMessage {
-                            tunnel_id: tunnel_id_clone,
-                            message: format!("[ERR] {}", sanitized_line),
-                            timestamp,
-                        },
-                    ) {
-                        break;
+    LogReaders {
+        handles,
+        stopped,
+        output,
+    }
+}
+
+/// 为子进程启动等待线程：进程一退出就记录退出码，读完剩余日志后发送 tunnel-exited 事件
+pub fn spawn_exit_waiter(
+    app_handle: AppContext,
+    tunnel_key: TunnelKey,
+    mut child: Child,
+    log_readers: LogReaders,
+    exit: Arc<crate::models::ProcessExit>,
+    recent_logs: Arc<Mutex<VecDeque<String>>>,
+    config_file: Option<PathBuf>,
+) {
+    let pid = child.id();
+    let started_at = Instant::now();
+    let result = thread::Builder::new()
+        .name(format!("frpc-waiter-{}", tunnel_key))
+        .spawn(move || {
+            let exit_info = match child.wait() {
+                Ok(status) => exit_info_from_status(status),
+                Err(e) => {
+                    eprintln!("[进程] 等待 frpc (PID: {}) 退出失败: {}", pid, e);
+                    ExitInfo {
+                        exit_code: None,
+                        signal: None,
                     }
                 }
-            }) {
-            Ok(_) => {}
-            Err(e) => eprintln!("[错误] 创建 stderr 监听线程失败: {}", e),
-        }
+            };
+
+            // 单次运行的配置文件中含有令牌，进程退出后立即删除
+            if let Some(path) = &config_file {
+                run_config::remove(path);
+            }
+
+            log_readers.finish();
+            exit.set(exit_info);
+
+            report_process_exit(
+                &app_handle,
+                &tunnel_key,
+                pid,
+                exit_info,
+                started_at,
+                &recent_logs,
+            );
+        });
+
+    if let Err(e) = result {
+        eprintln!("[错误] 创建进程等待线程失败: {}", e);
     }
+}
 
-    {
-        let mut procs = processes
-            .processes
-            .lock()
-            .map_err(|e| format!("获取进程锁失败: {}", e))?;
-        procs.insert(tunnel_id, child);
+/// 接管的进程没有 Child 句柄，只能定期检查 PID 是否存活。
+/// 有输出文件时从文件末尾继续读取日志
+pub fn spawn_adopted_watcher(
+    app_handle: AppContext,
+    tunnel_key: TunnelKey,
+    process: &FrpcProcess,
+    config_file: Option<PathBuf>,
+    output: Option<OutputFiles>,
+) {
+    let log_readers =
+        output.map(|output| spawn_log_readers(&app_handle, &tunnel_key, process, output, true));
+    let pid = process.pid;
+    let process_start_time = process.process_start_time;
+    let exit = process.exit.clone();
+    let recent_logs = process.recent_logs.clone();
+    let started_at = process.started_at;
+    let result = thread::Builder::new()
+        .name(format!("frpc-adopted-waiter-{}", tunnel_key))
+        .spawn(move || {
+            while crate::process_registry::is_frpc_alive(pid, process_start_time) {
+                thread::sleep(Duration::from_secs(1));
+            }
+            if let Some(path) = &config_file {
+                run_config::remove(path);
+            }
+            if let Some(log_readers) = log_readers {
+                log_readers.finish();
+            }
+            let exit_info = ExitInfo {
+                exit_code: None,
+                signal: None,
+            };
+            exit.set(exit_info);
+            report_process_exit(
+                &app_handle,
+                &tunnel_key,
+                pid,
+                exit_info,
+                started_at,
+                &recent_logs,
+            );
+        });
+
+    if let Err(e) = result {
+        eprintln!("[错误] 创建进程等待线程失败: {}", e);
     }
+}
 
-    let _ = crate::commands::process_guard::add_guarded_process(tunnel_id, user_token, guard_state)
-        .await;
+#[cfg(unix)]
+fn exit_info_from_status(status: std::process::ExitStatus) -> ExitInfo {
+    use std::os::unix::process::ExitStatusExt;
+    ExitInfo {
+        exit_code: status.code(),
+        signal: status.signal(),
+    }
+}
 
-    Ok(format!("frpc 已启动 (PID: {})", pid))
+#[cfg(not(unix))]
+fn exit_info_from_status(status: std::process::ExitStatus) -> ExitInfo {
+    ExitInfo {
+        exit_code: status.code(),
+        signal: None,
+    }
 }
 
-#[tauri::command]
-pub async fn stop_frpc(
-    tunnel_id: i32,
-    processes: State<'_, FrpcProcesses>,
-    guard_state: State<'_, ProcessGuardState>,
-) -> Result<String, String> {
-    let _ =
-        crate::commands::process_guard::remove_guarded_process(tunnel_id, guard_state, true).await;
+fn report_process_exit(
+    app_handle: &AppContext,
+    tunnel_key: &TunnelKey,
+    pid: u32,
+    exit_info: ExitInfo,
+    started_at: Instant,
+    recent_logs: &Mutex<VecDeque<String>>,
+) {
+    // 只移除属于这个进程的记录，隧道可能已经被重新启动
+    let processes = app_handle.state::<FrpcProcesses>();
+    let (removed, replaced) = match processes.processes.lock() {
+        Ok(mut procs) => match procs.get(tunnel_key) {
+            Some(p) if p.pid == pid => {
+                procs.remove(tunnel_key);
+                (true, false)
+            }
+            Some(_) => (false, true),
+            None => (false, false),
+        },
+        Err(_) => (false, false),
+    };
+    if removed {
+        let _ = crate::process_registry::unregister(app_handle, tunnel_key);
+    }
+    if !replaced {
+        apply_process_exit(app_handle, tunnel_key, exit_info);
+    }
 
-    let mut procs = processes
-        .processes
+    let last_logs = recent_logs
         .lock()
-        .map_err(|e| format!("获取进程锁失败: {}", e))?;
+        .map(|logs| logs.iter().cloned().collect())
+        .unwrap_or_default();
+
+    let exit = TunnelExitEvent {
+        tunnel_key: tunnel_key.clone(),
+        tunnel_id: tunnel_key.legacy_id(),
+        pid,
+        exit_code: exit_info.exit_code,
+        signal: exit_info.signal,
+        uptime_secs: started_at.elapsed().as_secs(),
+        last_logs,
+    };
+    let _ = app_handle.emit("tunnel-exited", &exit);
+    // 守护模式没有事件循环，进程守护直接在这里处理退出
+    crate::commands::process_guard::on_tunnel_exited(app_handle, exit);
+}
 
-    if let Some(mut child) = procs.remove(&tunnel_id) {
-        match child.kill() {
-            Ok(_) => {
-                let _ = child.wait();
-                Ok("frpc 已停止".to_string())
-            }
-            Err(e) => {
-                let _ = child.wait();
-                Err(format!("停止进程失败: {}", e))
-            }
+fn drain_log_readers(log_readers: Vec<JoinHandle<()>>) {
+    let deadline = Instant::now() + LOG_READER_DRAIN_TIMEOUT;
+    for handle in log_readers {
+        while !handle.is_finished() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(20));
+        }
+        if handle.is_finished() {
+            let _ = handle.join();
         }
+    }
+}
+
+/// Unix 上发送 SIGTERM，frpc 收到后会关闭代理再退出
+#[cfg(unix)]
+pub fn request_graceful_exit(pid: u32) -> Result<(), String> {
+    let ret = unsafe { libc::kill(pid as libc::pid_t, libc::SIGTERM) };
+    if ret == 0 {
+        Ok(())
     } else {
-        Err("该隧道未在运行".to_string())
+        Err(std::io::Error::last_os_error().to_string())
     }
 }
 
+/// Windows 上没有 SIGTERM，通过附加到 frpc 的（隐藏）控制台发送 CTRL_BREAK 事件
+#[cfg(target_os = "windows")]
+pub fn request_graceful_exit(pid: u32) -> Result<(), String> {
+    use std::sync::Mutex;
+    use windows_sys::Win32::System::Console::{
+        AttachConsole, FreeConsole, GenerateConsoleCtrlEvent, SetConsoleCtrlHandler,
+        CTRL_BREAK_EVENT,
+    };
+
+    // 同一时间只能附加到一个控制台
+    static CONSOLE_LOCK: Mutex<()> = Mutex::new(());
+    let _lock = CONSOLE_LOCK
+        .lock()
+        .map_err(|e| format!("获取控制台锁失败: {}", e))?;
+
+    unsafe {
+        FreeConsole();
+        if AttachConsole(pid) == 0 {
+            return Err(std::io::Error::last_os_error().to_string());
+        }
+
+        // 忽略自身收到的控制台事件，避免启动器被一起结束
+        SetConsoleCtrlHandler(None, 1);
+        let sent = GenerateConsoleCtrlEvent(CTRL_BREAK_EVENT, 0);
+        let error = std::io::Error::last_os_error();
+        FreeConsole();
+        SetConsoleCtrlHandler(None, 0);
+
+        if sent == 0 {
+            return Err(error.to_string());
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(not(any(unix, target_os = "windows")))]
+pub fn request_graceful_exit(_pid: u32) -> Result<(), String> {
+    Err("当前平台不支持优雅退出".to_string())
+}
+
 #[tauri::command]
 pub async fn is_frpc_running(
     tunnel_id: i32,
     processes: State<'_, FrpcProcesses>,
 ) -> Result<bool, String> {
-    let mut procs = processes
+    let procs = processes
         .processes
         .lock()
         .map_err(|e| format!("获取进程锁失败: {}", e))?;
 
-    if let Some(child) = procs.get_mut(&tunnel_id) {
-        match child.try_wait() {
-            Ok(Some(_)) => {
-                procs.remove(&tunnel_id);
-                Ok(false)
-            }
-            Ok(None) => Ok(true),
-            Err(_) => {
-                procs.remove(&tunnel_id);
-                Ok(false)
-            }
-        }
-    } else {
-        Ok(false)
-    }
+    Ok(procs
+        .get(&TunnelKey::Api(tunnel_id))
+        .is_some_and(|p| p.is_running()))
 }
 
 #[tauri::command]
 pub async fn test_log_event(
     app_handle: tauri::AppHandle,
-    tunnel_id: i32,
+    tunnel_key: TunnelKey,
 ) -> Result<String, String> {
+    let app_handle = AppContext::from(app_handle);
     eprintln!("[测试] 发送测试日志事件");
-    let timestamp = chrono::Local::now().format("%H:%M:%S").to_string();
 
-    match app_handle.emit(
-        "frpc-log",
-        LogMessage {
-            tunnel_id,
-            message: "这是一条测试日志".to_string(),
-            timestamp,
-        },
-    ) {
+    match emit_tunnel_log(&app_handle, &tunnel_key, "这是一条测试日志".to_string()) {
         Ok(_) => {
             eprintln!("[测试] 测试日志事件发送成功");
             Ok("测试日志已发送".to_string())
@@ -275,43 +784,75 @@ pub async fn test_log_event(
     }
 }
 
+/// 获取日志缓冲中序号大于 since_seq 的日志，用于窗口重新显示后补齐日志
+#[tauri::command]
+pub async fn get_tunnel_logs(
+    tunnel_key: TunnelKey,
+    since_seq: Option<u64>,
+    limit: Option<usize>,
+    log_buffers: State<'_, TunnelLogBuffers>,
+) -> Result<TunnelLogBatch, String> {
+    Ok(log_batch(&log_buffers, &tunnel_key, since_seq, limit))
+}
+
+/// 取出日志缓冲中序号大于 since_seq 的日志，limit 限制在缓冲容量以内
+pub fn log_batch(
+    log_buffers: &TunnelLogBuffers,
+    tunnel_key: &TunnelKey,
+    since_seq: Option<u64>,
+    limit: Option<usize>,
+) -> TunnelLogBatch {
+    let limit = limit
+        .unwrap_or(LOG_BUFFER_CAPACITY)
+        .clamp(1, LOG_BUFFER_CAPACITY);
+    log_buffers.since(tunnel_key, since_seq.unwrap_or(0), limit)
+}
+
+/// 获取运行中的 API 隧道 ID
 #[tauri::command]
 pub async fn get_running_tunnels(processes: State<'_, FrpcProcesses>) -> Result<Vec<i32>, String> {
-    let mut procs = processes
+    let procs = processes
         .processes
         .lock()
         .map_err(|e| format!("获取进程锁失败: {}", e))?;
 
-    let mut running_tunnels = Vec::new();
-    let mut stopped_tunnels = Vec::new();
+    Ok(procs
+        .iter()
+        .filter(|(_, process)| process.is_running())
+        .filter_map(|(tunnel_key, _)| match tunnel_key {
+            TunnelKey::Api(id) => Some(*id),
+            TunnelKey::Custom(_) => None,
+        })
+        .collect())
+}
 
-    for (tunnel_id, child) in procs.iter_mut() {
-        match child.try_wait() {
-            Ok(Some(_)) => {
-                stopped_tunnels.push(*tunnel_id);
-            }
-            Ok(None) => {
-                running_tunnels.push(*tunnel_id);
-            }
-            Err(_) => {
-                stopped_tunnels.push(*tunnel_id);
-            }
-        }
-    }
+/// 获取所有运行中隧道的标识，包括自定义隧道
+#[tauri::command]
+pub async fn get_running_tunnel_keys(
+    processes: State<'_, FrpcProcesses>,
+) -> Result<Vec<TunnelKey>, String> {
+    running_tunnel_keys(&processes)
+}
 
-    for tunnel_id in stopped_tunnels {
-        procs.remove(&tunnel_id);
-    }
+pub fn running_tunnel_keys(processes: &FrpcProcesses) -> Result<Vec<TunnelKey>, String> {
+    let procs = processes
+        .processes
+        .lock()
+        .map_err(|e| format!("获取进程锁失败: {}", e))?;
 
-    Ok(running_tunnels)
+    let mut keys: Vec<TunnelKey> = procs
+        .iter()
+        .filter(|(_, process)| process.is_running())
+        .map(|(tunnel_key, _)| tunnel_key.clone())
+        .collect();
+    keys.sort();
+    Ok(keys)
 }
 
 #[tauri::command]
 pub async fn fix_frpc_ini_tls(app_handle: tauri::AppHandle) -> Result<String, String> {
-    let app_dir = app_handle
-        .path()
-        .app_data_dir()
-        .map_err(|e| e.to_string())?;
+    let app_handle = AppContext::from(app_handle);
+    let app_dir = app_handle.app_data_dir()?;
 
     let config_path = app_dir.join("frpc.ini");
 
@@ -319,21 +860,10 @@ pub async fn fix_frpc_ini_tls(app_handle: tauri::AppHandle) -> Result<String, St
         return Err("frpc.ini 文件不存在".to_string());
     }
 
-    // 读取配置文件内容
-    let content = std::fs::read_to_string(&config_path)
-        .map_err(|e| format!("读取配置文件失败: {}", e))?;
-
-    // 将 tls_enable = false 改为 tls_enable = true
-    let modified_content = content.replace("tls_enable = false", "tls_enable = true");
-
-    // 如果没有变化，说明文件中没有 tls_enable = false
-    if modified_content == content {
-        return Err("配置文件中未找到 tls_enable = false".to_string());
+    if crate::tls_config::enable_tls_in_config(&config_path)?.is_none() {
+        // 如果没有变化，说明配置文件中已经启用了 TLS
+        return Err("配置文件中已启用 TLS".to_string());
     }
 
-    // 写回配置文件
-    std::fs::write(&config_path, modified_content)
-        .map_err(|e| format!("写入配置文件失败: {}", e))?;
-
     Ok("已成功将 tls_enable 设置为 true".to_string())
 }
@@ -1,39 +1,97 @@
-use crate::models::{FrpcProcesses, LogMessage, ProcessGuardInfo, ProcessGuardState, TunnelType};
+use crate::app_context::AppContext;
+use crate::commands::custom_tunnel::custom_tunnel_config_path;
+use crate::commands::process::{emit_tunnel_log, request_graceful_exit};
+use crate::commands::tunnel_state::{set_tunnel_state, update_tunnel_state};
+use crate::frpc_config::ConfigFormat;
+use crate::frpc_log::{FrpcEvent, FrpcEventKind};
+use crate::guard_rules::{GuardAction, GuardRule, GuardRuleInfo, GuardRules};
+use crate::models::{
+    FrpcProcesses, GuardConfig, ProcessGuardInfo, ProcessGuardState, RestartState, TlsRemediation,
+    TunnelExitEvent, TunnelKey, TunnelState, TunnelStates,
+};
+use crate::process_registry::ProcessRecord;
+use crate::settings::SettingsState;
+use crate::tls_config::{enable_tls, enable_tls_in_config, restore_config};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::path::Path;
 use std::sync::atomic::Ordering;
 use std::thread;
-use std::time::Duration;
-use tauri::{Emitter, Manager, State};
-
-/// 停止守护的错误模式配置
-const STOP_GUARD_PATTERNS: &[&str] = &[
-    "token in login doesn't match token from configuration",
-    "authorization failed",
-    "invalid token",
-    "read: connection reset by peer",
-    "错误的用户token，此用户不存在",
-    "允许的隧道数量超出上限，请删除隧道或续费vip",
-    "不属于你",
-    "缺少用户token或隧道id参数",
-    "您目前为免费会员",
-    "客户端代理参数错误，配置文件与记录不匹配。请不要随意修改配置文件！",
-    "ChmlFrp API Error"
-];
+use std::time::{Duration, Instant};
+use tauri::State;
+
+/// 把设置中的守护开关、重启策略和从不守护的隧道应用到守护状态。
+/// 启动时需要在自动启动隧道之前调用
+pub fn apply_guard_settings(app_handle: &AppContext) {
+    let settings = app_handle.state::<SettingsState>().get();
+    let guard_state = app_handle.state::<ProcessGuardState>();
+
+    set_guard_enabled(guard_state, settings.process_guard_enabled);
+    if let Ok(mut config) = guard_state.config.lock() {
+        *config = settings.guard_config;
+    }
+    if let Ok(mut never_guard) = guard_state.never_guard.lock() {
+        *never_guard = settings.never_guard.into_iter().collect();
+    }
+
+    eprintln!(
+        "[守护进程] 守护进程已{}",
+        if settings.process_guard_enabled {
+            "启用"
+        } else {
+            "禁用"
+        }
+    );
+}
+
+/// 开启或关闭守护。关闭时清空守护列表、手动停止标记和重启状态，
+/// 之后再开启只守护新启动的隧道。set_process_guard_enabled 和 set_settings 都经过这里
+fn set_guard_enabled(guard_state: &ProcessGuardState, enabled: bool) {
+    let was_enabled = guard_state.enabled.swap(enabled, Ordering::SeqCst);
+    if enabled || !was_enabled {
+        return;
+    }
+    if let Ok(mut guarded) = guard_state.guarded_processes.lock() {
+        guarded.clear();
+    }
+    if let Ok(mut stopped) = guard_state.manually_stopped.lock() {
+        stopped.clear();
+    }
+    if let Ok(mut states) = guard_state.restart_states.lock() {
+        states.clear();
+    }
+}
+
+/// 守护重新接管的进程。守护开启时启动的隧道都在守护列表中，
+/// 接管后按同样的规则恢复守护
+pub fn guard_adopted_processes(app_handle: &AppContext, records: &[ProcessRecord]) {
+    let guard_state = app_handle.state::<ProcessGuardState>();
+    for record in records {
+        let info = match record.key() {
+            // 重启时从凭据存储读取用户令牌
+            Some(TunnelKey::Api(tunnel_id)) => ProcessGuardInfo::Api { tunnel_id },
+            Some(TunnelKey::Custom(tunnel_id)) => ProcessGuardInfo::Custom { tunnel_id },
+            None => continue,
+        };
+        let tunnel_key = info.tunnel_key();
+        if let Err(e) = add_guarded(guard_state, info) {
+            eprintln!("[守护进程] 守护接管的隧道 {} 失败: {}", tunnel_key, e);
+        }
+    }
+}
 
 #[tauri::command]
 pub async fn set_process_guard_enabled(
+    app_handle: tauri::AppHandle,
     enabled: bool,
     guard_state: State<'_, ProcessGuardState>,
+    settings: State<'_, SettingsState>,
 ) -> Result<String, String> {
-    guard_state.enabled.store(enabled, Ordering::SeqCst);
-
-    if !enabled {
-        if let Ok(mut guarded) = guard_state.guarded_processes.lock() {
-            guarded.clear();
-        }
-        if let Ok(mut stopped) = guard_state.manually_stopped.lock() {
-            stopped.clear();
-        }
-    }
+    let app_handle = AppContext::from(app_handle);
+    settings.update(&app_handle, |settings| {
+        settings.process_guard_enabled = enabled;
+    })?;
+    set_guard_enabled(&guard_state, enabled);
 
     Ok(format!(
         "守护进程已{}",
@@ -49,278 +107,997 @@ pub async fn get_process_guard_enabled(
 }
 
 #[tauri::command]
-pub async fn add_guarded_process(
-    tunnel_id: i32,
-    user_token: String,
+pub async fn get_process_guard_config(
+    guard_state: State<'_, ProcessGuardState>,
+) -> Result<GuardConfig, String> {
+    let config = guard_state
+        .config
+        .lock()
+        .map_err(|e| format!("获取守护配置锁失败: {}", e))?;
+    Ok(config.clone())
+}
+
+#[tauri::command]
+pub async fn set_process_guard_config(
+    app_handle: tauri::AppHandle,
+    config: GuardConfig,
     guard_state: State<'_, ProcessGuardState>,
+    settings: State<'_, SettingsState>,
 ) -> Result<(), String> {
-    if !guard_state.enabled.load(Ordering::SeqCst) {
-        return Ok(());
+    let app_handle = AppContext::from(app_handle);
+    if config.initial_backoff_ms == 0 || config.max_backoff_ms < config.initial_backoff_ms {
+        return Err("重启等待时间设置无效".to_string());
+    }
+    if config.max_restarts == 0 || config.restart_window_secs == 0 {
+        return Err("重启次数限制设置无效".to_string());
     }
 
-    let mut guarded = guard_state
-        .guarded_processes
+    settings.update(&app_handle, |settings| {
+        settings.guard_config = config.clone();
+    })?;
+
+    let mut current = guard_state
+        .config
         .lock()
-        .map_err(|e| format!("获取守护进程锁失败: {}", e))?;
+        .map_err(|e| format!("获取守护配置锁失败: {}", e))?;
+    *current = config;
+    Ok(())
+}
 
-    guarded.insert(
-        tunnel_id,
-        ProcessGuardInfo {
-            tunnel_id,
-            tunnel_type: TunnelType::Api { user_token },
-        },
-    );
+#[tauri::command]
+pub async fn get_never_guard_tunnels(
+    settings: State<'_, SettingsState>,
+) -> Result<Vec<TunnelKey>, String> {
+    Ok(settings.get().never_guard.into_iter().collect())
+}
 
-    if let Ok(mut stopped) = guard_state.manually_stopped.lock() {
-        stopped.remove(&tunnel_id);
-    }
+/// 设置隧道是否从不守护，设置后立即停止对该隧道的守护
+#[tauri::command]
+pub async fn set_tunnel_never_guard(
+    app_handle: tauri::AppHandle,
+    tunnel_key: TunnelKey,
+    never_guard: bool,
+    guard_state: State<'_, ProcessGuardState>,
+    settings: State<'_, SettingsState>,
+) -> Result<(), String> {
+    let app_handle = AppContext::from(app_handle);
+    settings.update(&app_handle, |settings| {
+        if never_guard {
+            settings.never_guard.insert(tunnel_key.clone());
+        } else {
+            settings.never_guard.remove(&tunnel_key);
+        }
+    })?;
 
+    let mut excluded = guard_state
+        .never_guard
+        .lock()
+        .map_err(|e| format!("获取守护进程锁失败: {}", e))?;
+    if never_guard {
+        excluded.insert(tunnel_key.clone());
+        if let Ok(mut guarded) = guard_state.guarded_processes.lock() {
+            guarded.remove(&tunnel_key);
+        }
+        if let Ok(mut states) = guard_state.restart_states.lock() {
+            states.remove(&tunnel_key);
+        }
+    } else {
+        excluded.remove(&tunnel_key);
+    }
     Ok(())
 }
 
+#[tauri::command]
+pub async fn add_guarded_process(
+    tunnel_id: i32,
+    guard_state: State<'_, ProcessGuardState>,
+) -> Result<(), String> {
+    add_guarded(&guard_state, ProcessGuardInfo::Api { tunnel_id })
+}
+
 #[tauri::command]
 pub async fn add_guarded_custom_tunnel(
-    tunnel_id_hash: i32,
-    original_id: String,
+    tunnel_id: String,
     guard_state: State<'_, ProcessGuardState>,
 ) -> Result<(), String> {
+    add_guarded(&guard_state, ProcessGuardInfo::Custom { tunnel_id })
+}
+
+/// 守护新启动的隧道，守护关闭或隧道设置为从不守护时不做处理
+pub fn add_guarded(guard_state: &ProcessGuardState, info: ProcessGuardInfo) -> Result<(), String> {
     if !guard_state.enabled.load(Ordering::SeqCst) {
         return Ok(());
     }
 
+    let tunnel_key = info.tunnel_key();
+    let excluded = guard_state
+        .never_guard
+        .lock()
+        .map(|never_guard| never_guard.contains(&tunnel_key))
+        .unwrap_or(false);
+    if excluded {
+        return Ok(());
+    }
+
     let mut guarded = guard_state
         .guarded_processes
         .lock()
         .map_err(|e| format!("获取守护进程锁失败: {}", e))?;
 
-    guarded.insert(
-        tunnel_id_hash,
-        ProcessGuardInfo {
-            tunnel_id: tunnel_id_hash,
-            tunnel_type: TunnelType::Custom { original_id },
-        },
-    );
+    guarded.insert(tunnel_key.clone(), info);
 
     if let Ok(mut stopped) = guard_state.manually_stopped.lock() {
-        stopped.remove(&tunnel_id_hash);
+        stopped.remove(&tunnel_key);
     }
 
+    mark_process_started(guard_state, &tunnel_key);
+
     Ok(())
 }
 
 #[tauri::command]
 pub async fn remove_guarded_process(
-    tunnel_id: i32,
+    tunnel_key: TunnelKey,
     guard_state: State<'_, ProcessGuardState>,
     is_manual_stop: bool,
+) -> Result<(), String> {
+    remove_guarded(&guard_state, tunnel_key, is_manual_stop)
+}
+
+/// 取消守护，手动停止时同时清除重启状态，之后的退出不再重启
+pub fn remove_guarded(
+    guard_state: &ProcessGuardState,
+    tunnel_key: TunnelKey,
+    is_manual_stop: bool,
 ) -> Result<(), String> {
     let mut guarded = guard_state
         .guarded_processes
         .lock()
         .map_err(|e| format!("获取守护进程锁失败: {}", e))?;
 
-    guarded.remove(&tunnel_id);
+    guarded.remove(&tunnel_key);
 
     if is_manual_stop {
+        if let Ok(mut states) = guard_state.restart_states.lock() {
+            states.remove(&tunnel_key);
+        }
         if let Ok(mut stopped) = guard_state.manually_stopped.lock() {
-            stopped.insert(tunnel_id);
+            stopped.insert(tunnel_key);
         }
     }
 
     Ok(())
 }
 
-/// 检查日志消息是否包含需要停止守护的错误模式
-pub fn should_stop_guard_by_log(message: &str) -> Option<&'static str> {
-    for pattern in STOP_GUARD_PATTERNS {
-        if message.to_lowercase().contains(&pattern.to_lowercase()) {
-            return Some(pattern);
+/// 记录隧道开始运行。用户手动启动时会清除之前的退避和崩溃循环状态，
+/// 守护进程自身发起的重启则保留重启历史
+fn mark_process_started(guard_state: &ProcessGuardState, tunnel_key: &TunnelKey) {
+    if let Ok(mut states) = guard_state.restart_states.lock() {
+        let state = states.entry(tunnel_key.clone()).or_default();
+        if !state.restart_pending {
+            *state = RestartState {
+                last_config: state.last_config.take(),
+                ..Default::default()
+            };
+        }
+    }
+}
+
+/// 守护开启时保存本次获取的 API 隧道配置，供之后的重启在获取失败时使用
+pub fn remember_run_config(app_handle: &AppContext, tunnel_key: &TunnelKey, content: &str) {
+    let guard_state = app_handle.state::<ProcessGuardState>();
+    if !guard_state.enabled.load(Ordering::SeqCst) {
+        return;
+    }
+    if let Ok(mut states) = guard_state.restart_states.lock() {
+        states.entry(tunnel_key.clone()).or_default().last_config = Some(content.to_string());
+    };
+}
+
+/// 守护进程发起的重启中，返回上次成功获取的配置；用户手动启动时返回 None
+pub fn restart_run_config(app_handle: &AppContext, tunnel_key: &TunnelKey) -> Option<String> {
+    let guard_state = app_handle.state::<ProcessGuardState>();
+    let config = guard_state
+        .restart_states
+        .lock()
+        .ok()?
+        .get(tunnel_key)
+        .filter(|state| state.restart_pending)
+        .and_then(|state| state.last_config.clone());
+    config
+}
+
+/// 守护进程对离线隧道的处理结果
+enum RestartDecision {
+    // 已有重启在等待中，或隧道处于崩溃循环状态
+    Skip,
+    // 超过重启次数上限，进入崩溃循环状态
+    CrashLooping { restarts: usize },
+    Restart { delay: Duration, attempt: u32 },
+}
+
+/// 根据重启历史决定是否以及何时重启退出的隧道
+fn plan_restart(
+    guard_state: &ProcessGuardState,
+    tunnel_key: &TunnelKey,
+    uptime: Duration,
+) -> RestartDecision {
+    let config = match guard_state.config.lock() {
+        Ok(config) => config.clone(),
+        Err(_) => return RestartDecision::Skip,
+    };
+    let mut states = match guard_state.restart_states.lock() {
+        Ok(states) => states,
+        Err(_) => return RestartDecision::Skip,
+    };
+    let state = states.entry(tunnel_key.clone()).or_default();
+
+    if state.restart_pending || state.crash_looping {
+        return RestartDecision::Skip;
+    }
+
+    // 上次启动后稳定运行了足够久，说明之前的故障已经恢复，重新从最短等待时间开始
+    if uptime >= Duration::from_secs(config.stable_uptime_secs) {
+        state.consecutive_restarts = 0;
+        state.recent_restarts.clear();
+    }
+
+    let window = Duration::from_secs(config.restart_window_secs);
+    let now = Instant::now();
+    while let Some(oldest) = state.recent_restarts.front() {
+        if now.duration_since(*oldest) > window {
+            state.recent_restarts.pop_front();
+        } else {
+            break;
+        }
+    }
+
+    if state.recent_restarts.len() >= config.max_restarts as usize {
+        state.crash_looping = true;
+        return RestartDecision::CrashLooping {
+            restarts: state.recent_restarts.len(),
+        };
+    }
+
+    let mut delay = backoff_delay(&config, state.consecutive_restarts);
+    // 守护规则要求的等待时间
+    if let Some(until) = state.backoff_until.take() {
+        delay = delay.max(until.saturating_duration_since(now));
+    }
+    state.consecutive_restarts += 1;
+    state.recent_restarts.push_back(now);
+    state.restart_pending = true;
+
+    RestartDecision::Restart {
+        delay,
+        attempt: state.consecutive_restarts,
+    }
+}
+
+/// 指数退避：initial * 2^attempt，不超过上限，并加入 ±20% 的随机抖动，
+/// 避免多个隧道在节点恢复时同时重连
+fn backoff_delay(config: &GuardConfig, attempt: u32) -> Duration {
+    let base = config
+        .initial_backoff_ms
+        .saturating_mul(1u64 << attempt.min(20))
+        .min(config.max_backoff_ms);
+    let jitter = 0.8 + 0.4 * random_unit();
+    Duration::from_millis((base as f64 * jitter) as u64)
+}
+
+/// 返回 [0, 1) 之间的随机数，RandomState 每次创建都会使用新的随机种子
+fn random_unit() -> f64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u128(
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or_default(),
+    );
+    (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+fn finish_restart(guard_state: &ProcessGuardState, tunnel_key: &TunnelKey) {
+    if let Ok(mut states) = guard_state.restart_states.lock() {
+        if let Some(state) = states.get_mut(tunnel_key) {
+            state.restart_pending = false;
         }
     }
-    None
 }
 
-/// 处理日志并检查是否需要停止守护
+/// 处理日志并按守护规则执行动作
 #[tauri::command]
 pub async fn check_log_and_stop_guard(
     app_handle: tauri::AppHandle,
-    tunnel_id: i32,
+    tunnel_key: TunnelKey,
     log_message: String,
-    guard_state: State<'_, ProcessGuardState>,
 ) -> Result<(), String> {
-    if let Some(pattern) = should_stop_guard_by_log(&log_message) {
-        eprintln!("[守护进程] 检测到隧道 {} 出现错误: {}", tunnel_id, pattern);
-        eprintln!("[守护进程] 停止对隧道 {} 的守护", tunnel_id);
+    let app_handle = AppContext::from(app_handle);
+    apply_guard_rules(&app_handle, &tunnel_key, &log_message);
+    Ok(())
+}
 
-        // 从守护列表中移除（不标记为手动停止，因为这是自动停止）
-        let mut guarded = guard_state
-            .guarded_processes
-            .lock()
-            .map_err(|e| format!("获取守护进程锁失败: {}", e))?;
+/// 将守护中隧道的一行日志与守护规则比较，命中后执行规则的动作
+pub fn apply_guard_rules(app_handle: &AppContext, tunnel_key: &TunnelKey, line: &str) {
+    let guard_state = app_handle.state::<ProcessGuardState>();
+    if !guard_state.enabled.load(Ordering::SeqCst) {
+        return;
+    }
+    let info = match guard_state.guarded_processes.lock() {
+        Ok(guarded) => guarded.get(tunnel_key).cloned(),
+        Err(_) => None,
+    };
+    let info = match info {
+        Some(info) => info,
+        None => return,
+    };
+    let rule = match app_handle.state::<GuardRules>().find_match(line) {
+        Some(rule) => rule,
+        None => return,
+    };
 
-        guarded.remove(&tunnel_id);
+    match rule.action {
+        GuardAction::StopGuard => {
+            eprintln!(
+                "[守护进程] 检测到隧道 {} 出现错误: {}",
+                tunnel_key, rule.pattern
+            );
+            eprintln!("[守护进程] 停止对隧道 {} 的守护", tunnel_key);
 
-        // 发送日志消息通知用户
-        let timestamp = chrono::Local::now().format("%H:%M:%S").to_string();
-        let _ = app_handle.emit(
-            "frpc-log",
-            LogMessage {
-                tunnel_id,
-                message: format!("检测到错误 \"{}\"，已停止守护进程", pattern),
-                timestamp,
-            },
-        );
-    }
+            // 从守护列表中移除（不标记为手动停止，因为这是自动停止）
+            if let Ok(mut guarded) = guard_state.guarded_processes.lock() {
+                guarded.remove(tunnel_key);
+            }
 
-    Ok(())
+            // 发送日志消息通知用户
+            let _ = emit_tunnel_log(
+                app_handle,
+                tunnel_key,
+                format!("检测到错误 \"{}\"，已停止守护进程", rule.pattern),
+            );
+        }
+        GuardAction::StopTunnel => {
+            eprintln!(
+                "[守护进程] 检测到隧道 {} 出现错误: {}",
+                tunnel_key, rule.pattern
+            );
+            eprintln!("[守护进程] 停止隧道 {}", tunnel_key);
+
+            let _ = emit_tunnel_log(
+                app_handle,
+                tunnel_key,
+                format!("检测到错误 \"{}\"，已停止隧道", rule.pattern),
+            );
+            stop_tunnel_in_background(app_handle.clone(), info);
+        }
+        GuardAction::FixTls => fix_tls_and_restart(app_handle, &info),
+        GuardAction::Backoff { seconds } => {
+            if let Ok(mut states) = guard_state.restart_states.lock() {
+                states.entry(tunnel_key.clone()).or_default().backoff_until =
+                    Some(Instant::now() + Duration::from_secs(seconds));
+            }
+            let _ = emit_tunnel_log(
+                app_handle,
+                tunnel_key,
+                format!(
+                    "检测到 \"{}\"，守护进程将至少等待 {} 秒后再重启隧道",
+                    rule.pattern, seconds
+                ),
+            );
+        }
+    }
 }
 
-pub fn start_guard_monitor(app_handle: tauri::AppHandle) {
+/// 在后台线程中停止隧道，日志读取线程不能等待进程退出
+fn stop_tunnel_in_background(app_handle: AppContext, info: ProcessGuardInfo) {
     thread::spawn(move || {
-        let mut last_log_time = std::time::Instant::now();
-
-        loop {
-            thread::sleep(Duration::from_secs(3));
+        let result = tauri::async_runtime::block_on(crate::commands::process::stop_tunnel(
+            &app_handle,
+            &info.tunnel_key(),
+            None,
+        ));
+        if let Err(e) = result {
+            eprintln!("[守护进程] 停止隧道 {} 失败: {}", info.tunnel_key(), e);
+        }
+    });
+}
 
-            let guard_state = app_handle.state::<ProcessGuardState>();
-            let processes = app_handle.state::<FrpcProcesses>();
+/// 修复隧道配置文件中的 TLS 设置，然后结束进程，由守护进程重启一次验证修复结果
+fn fix_tls_and_restart(app_handle: &AppContext, info: &ProcessGuardInfo) {
+    let tunnel_key = &info.tunnel_key();
 
-            let is_enabled = guard_state.enabled.load(Ordering::SeqCst);
+    // 连接成功过说明 TLS 设置没有问题，超时来自网络波动
+    let connected = app_handle
+        .state::<TunnelStates>()
+        .states
+        .lock()
+        .map(|states| {
+            matches!(
+                states.get(tunnel_key).map(|info| &info.state),
+                Some(TunnelState::Connected) | Some(TunnelState::Reconnecting)
+            )
+        })
+        .unwrap_or(true);
+    if connected {
+        return;
+    }
 
-            if last_log_time.elapsed().as_secs() >= 30 {
-                last_log_time = std::time::Instant::now();
+    let config_path = match info {
+        // 以 -u/-p 启动时没有登记配置文件，frpc 每次启动都自行获取配置，
+        // 修改本地文件不起作用
+        ProcessGuardInfo::Api { .. } => {
+            match crate::process_registry::config_path(app_handle, tunnel_key) {
+                Some(path) => path,
+                None => {
+                    report_tls_fix_not_applicable(app_handle, tunnel_key);
+                    return;
+                }
             }
+        }
+        ProcessGuardInfo::Custom { tunnel_id } => {
+            match custom_tunnel_config_path(app_handle, tunnel_id) {
+                Ok(path) => path,
+                Err(e) => {
+                    eprintln!("[守护进程] 获取配置文件路径失败: {}", e);
+                    return;
+                }
+            }
+        }
+    };
+    // TOML、YAML 和 JSON 配置由新版 frpc 读取，默认已启用 TLS
+    if ConfigFormat::from_path(&config_path) != ConfigFormat::Ini {
+        return;
+    }
+    let pid = current_pid(app_handle, tunnel_key);
+
+    // 持有锁完成检查和修改，stdout 和 stderr 的读取线程可能同时命中规则
+    let result = {
+        let guard_state = app_handle.state::<ProcessGuardState>();
+        let mut states = match guard_state.restart_states.lock() {
+            Ok(states) => states,
+            Err(_) => return,
+        };
+        let state = states.entry(tunnel_key.clone()).or_default();
+        // 每次手动启动只自动修复一次
+        if state.tls_remediation.is_some() {
+            return;
+        }
+        let result = enable_tls_in_config(&config_path);
+        if let Ok(Some(original)) = &result {
+            state.tls_remediation = Some(TlsRemediation {
+                config_path: config_path.clone(),
+                original: original.clone(),
+                fixed_pid: pid,
+                finished: false,
+            });
+        }
+        result
+    };
+
+    match result {
+        Ok(Some(_)) => {}
+        // 已经启用了 TLS，修改配置也无法解决
+        Ok(None) => return,
+        Err(e) => {
+            let message = format!("自动修复 TLS 配置失败: {}", e);
+            let _ = emit_tunnel_log(app_handle, tunnel_key, message.clone());
+            emit_tls_remediation(app_handle, tunnel_key, &config_path, "failed", &message);
+            return;
+        }
+    }
+
+    let message = "已自动将 tls_enable 设置为 true，正在重启隧道".to_string();
+    let _ = emit_tunnel_log(app_handle, tunnel_key, message.clone());
+    emit_tls_remediation(app_handle, tunnel_key, &config_path, "applied", &message);
+
+    if let Some(pid) = pid {
+        if request_graceful_exit(pid).is_err() {
+            crate::process_registry::kill_pid(pid);
+        }
+    }
+}
+
+// 命令行模式下无法自动修复 TLS，每次手动启动只提示一次
+fn report_tls_fix_not_applicable(app_handle: &AppContext, tunnel_key: &TunnelKey) {
+    let first = app_handle
+        .state::<ProcessGuardState>()
+        .restart_states
+        .lock()
+        .map(|mut states| {
+            let state = states.entry(tunnel_key.clone()).or_default();
+            !std::mem::replace(&mut state.tls_fix_skipped, true)
+        })
+        .unwrap_or(false);
+    if !first {
+        return;
+    }
+
+    let message =
+        "隧道以命令行参数启动，frpc 每次启动时自行获取配置，无法自动启用 TLS，可在设置中改为配置文件启动"
+            .to_string();
+    let _ = emit_tunnel_log(app_handle, tunnel_key, message.clone());
+    emit_tls_remediation(
+        app_handle,
+        tunnel_key,
+        Path::new(""),
+        "not_applicable",
+        &message,
+    );
+}
+
+/// TLS 修复等待验证期间，对重新获取的隧道配置同样启用 TLS，返回修改后的配置
+pub fn pending_tls_fix(
+    app_handle: &AppContext,
+    tunnel_key: &TunnelKey,
+    content: &str,
+) -> Option<String> {
+    let guard_state = app_handle.state::<ProcessGuardState>();
+    let pending = guard_state
+        .restart_states
+        .lock()
+        .ok()?
+        .get(tunnel_key)
+        .and_then(|state| state.tls_remediation.as_ref())
+        .is_some_and(|remediation| !remediation.finished);
+    if pending {
+        enable_tls(content)
+    } else {
+        None
+    }
+}
 
-            if !is_enabled {
-                continue;
+/// 根据重启后进程的日志判断 TLS 修复是否生效
+pub fn check_tls_remediation(app_handle: &AppContext, event: &FrpcEvent) {
+    let succeeded = match event.kind {
+        Some(FrpcEventKind::LoginSuccess) | Some(FrpcEventKind::ProxyStarted) => true,
+        Some(FrpcEventKind::TlsError) | Some(FrpcEventKind::IoTimeout) => false,
+        _ => return,
+    };
+    let pid = current_pid(app_handle, &event.tunnel_key);
+    finish_tls_remediation(app_handle, &event.tunnel_key, pid, succeeded);
+}
+
+/// 结束 TLS 修复：成功时保留修改，失败时写回原配置。pid 为修复时的进程时不做处理
+fn finish_tls_remediation(
+    app_handle: &AppContext,
+    tunnel_key: &TunnelKey,
+    pid: Option<u32>,
+    succeeded: bool,
+) {
+    let guard_state = app_handle.state::<ProcessGuardState>();
+    let remediation = {
+        let mut states = match guard_state.restart_states.lock() {
+            Ok(states) => states,
+            Err(_) => return,
+        };
+        match states
+            .get_mut(tunnel_key)
+            .and_then(|state| state.tls_remediation.as_mut())
+        {
+            Some(remediation) if !remediation.finished && remediation.fixed_pid != pid => {
+                remediation.finished = true;
+                remediation.clone()
             }
+            _ => return,
+        }
+    };
 
-            let guarded_list: Vec<ProcessGuardInfo> = {
-                match guard_state.guarded_processes.lock() {
-                    Ok(guarded) => guarded.values().cloned().collect(),
-                    Err(_) => continue,
-                }
-            };
+    let is_run_config = app_handle
+        .app_data_dir()
+        .is_ok_and(|dir| crate::run_config::is_run_config(&dir, &remediation.config_path));
+    let (status, message) = if succeeded {
+        ("succeeded", "启用 TLS 后隧道已成功连接".to_string())
+    } else if is_run_config {
+        // 单次运行的配置文件每次启动时重新获取，不需要写回
+        (
+            "rolled_back",
+            "启用 TLS 后仍然无法连接，下次启动时将使用原配置".to_string(),
+        )
+    } else {
+        match restore_config(&remediation.config_path, &remediation.original) {
+            Ok(()) => (
+                "rolled_back",
+                "启用 TLS 后仍然无法连接，已恢复原配置文件".to_string(),
+            ),
+            Err(e) => ("failed", format!("启用 TLS 后仍然无法连接，{}", e)),
+        }
+    };
+    eprintln!("[守护进程] 隧道 {} {}", tunnel_key, message);
+    let _ = emit_tunnel_log(app_handle, tunnel_key, message.clone());
+    emit_tls_remediation(
+        app_handle,
+        tunnel_key,
+        &remediation.config_path,
+        status,
+        &message,
+    );
+}
+
+/// 发送 tunnel-tls-remediation 事件，status 为 applied、succeeded、rolled_back、failed
+/// 或 not_applicable
+fn emit_tls_remediation(
+    app_handle: &AppContext,
+    tunnel_key: &TunnelKey,
+    config_path: &Path,
+    status: &str,
+    message: &str,
+) {
+    let _ = app_handle.emit(
+        "tunnel-tls-remediation",
+        serde_json::json!({
+            "tunnel_key": tunnel_key,
+            "tunnel_id": tunnel_key.legacy_id(),
+            "status": status,
+            "config_path": config_path.to_string_lossy(),
+            "message": message,
+            "timestamp": chrono::Local::now().format("%H:%M:%S").to_string(),
+        }),
+    );
+}
+
+fn current_pid(app_handle: &AppContext, tunnel_key: &TunnelKey) -> Option<u32> {
+    app_handle
+        .state::<FrpcProcesses>()
+        .processes
+        .lock()
+        .ok()
+        .and_then(|procs| procs.get(tunnel_key).map(|p| p.pid))
+}
+
+#[tauri::command]
+pub async fn list_guard_rules(rules: State<'_, GuardRules>) -> Result<Vec<GuardRuleInfo>, String> {
+    Ok(rules.list())
+}
 
-            if guarded_list.is_empty() {
-                continue;
+#[tauri::command]
+pub async fn add_guard_rule(
+    app_handle: tauri::AppHandle,
+    rule: GuardRule,
+    rules: State<'_, GuardRules>,
+) -> Result<GuardRule, String> {
+    let app_handle = AppContext::from(app_handle);
+    rules.add(&app_handle, rule)
+}
+
+#[tauri::command]
+pub async fn remove_guard_rule(
+    app_handle: tauri::AppHandle,
+    id: String,
+    rules: State<'_, GuardRules>,
+) -> Result<(), String> {
+    let app_handle = AppContext::from(app_handle);
+    rules.remove(&app_handle, &id)
+}
+
+/// 手动修改规则文件后重新加载
+#[tauri::command]
+pub async fn reload_guard_rules(
+    app_handle: tauri::AppHandle,
+    rules: State<'_, GuardRules>,
+) -> Result<Vec<GuardRuleInfo>, String> {
+    let app_handle = AppContext::from(app_handle);
+    rules.reload(&app_handle)?;
+    Ok(rules.list())
+}
+
+/// 进程一退出就按重启策略处理，由进程等待线程调用，无需轮询进程表
+pub fn on_tunnel_exited(app_handle: &AppContext, exit: TunnelExitEvent) {
+    let tunnel_key = &exit.tunnel_key;
+    let guard_state = app_handle.state::<ProcessGuardState>();
+
+    if !guard_state.enabled.load(Ordering::SeqCst) {
+        return;
+    }
+
+    // 修复 TLS 后重启的进程退出，说明修复没有生效
+    finish_tls_remediation(app_handle, tunnel_key, Some(exit.pid), false);
+
+    let info = match guard_state.guarded_processes.lock() {
+        Ok(guarded) => match guarded.get(tunnel_key) {
+            Some(info) => info.clone(),
+            None => return,
+        },
+        Err(_) => return,
+    };
+
+    let is_manually_stopped = guard_state
+        .manually_stopped
+        .lock()
+        .map(|stopped| stopped.contains(tunnel_key))
+        .unwrap_or(true);
+    if is_manually_stopped {
+        return;
+    }
+
+    // 旧进程的退出事件可能晚于隧道被重新启动
+    let processes = app_handle.state::<FrpcProcesses>();
+    let replaced = processes
+        .processes
+        .lock()
+        .map(|procs| {
+            procs
+                .get(tunnel_key)
+                .is_some_and(|p| p.pid != exit.pid && p.is_running())
+        })
+        .unwrap_or(false);
+    if replaced {
+        return;
+    }
+
+    plan_and_schedule_restart(app_handle, info, Duration::from_secs(exit.uptime_secs));
+}
+
+// 按重启历史安排下一次重启，超过次数上限时停止守护。
+// 进程退出和守护进程重启失败都经过这里，失败的重启同样计入退避和次数上限
+fn plan_and_schedule_restart(app_handle: &AppContext, info: ProcessGuardInfo, uptime: Duration) {
+    let tunnel_key = info.tunnel_key();
+    let guard_state = app_handle.state::<ProcessGuardState>();
+    match plan_restart(guard_state, &tunnel_key, uptime) {
+        RestartDecision::Skip => {}
+        RestartDecision::CrashLooping { restarts } => {
+            let window_secs = guard_state
+                .config
+                .lock()
+                .map(|config| config.restart_window_secs)
+                .unwrap_or_default();
+
+            if let Ok(mut guarded) = guard_state.guarded_processes.lock() {
+                guarded.remove(&tunnel_key);
             }
 
-            for info in guarded_list {
-                let tunnel_id = info.tunnel_id;
+            let message = format!(
+                "隧道在 {} 秒内已自动重启 {} 次仍然离线，已停止守护，请检查节点状态后手动启动",
+                window_secs, restarts
+            );
+            set_tunnel_state(
+                app_handle,
+                &tunnel_key,
+                TunnelState::Failed(message.clone()),
+            );
+            let _ = emit_tunnel_log(app_handle, &tunnel_key, message);
+            let _ = app_handle.emit(
+                "tunnel-crash-looping",
+                serde_json::json!({
+                    "tunnel_key": tunnel_key,
+                    "tunnel_id": tunnel_key.legacy_id(),
+                    "restarts": restarts,
+                    "window_secs": window_secs,
+                    "timestamp": chrono::Local::now().format("%H:%M:%S").to_string(),
+                }),
+            );
+        }
+        RestartDecision::Restart { delay, attempt } => {
+            schedule_restart(app_handle.clone(), info, delay, attempt);
+        }
+    }
+}
 
-                let is_manually_stopped = {
-                    match guard_state.manually_stopped.lock() {
-                        Ok(stopped) => stopped.contains(&tunnel_id),
-                        Err(_) => continue,
-                    }
-                };
+fn schedule_restart(app_handle: AppContext, info: ProcessGuardInfo, delay: Duration, attempt: u32) {
+    let tunnel_key = info.tunnel_key();
 
-                if is_manually_stopped {
-                    continue;
-                }
+    set_tunnel_state(&app_handle, &tunnel_key, TunnelState::GuardedRestarting);
+    let _ = emit_tunnel_log(
+        &app_handle,
+        &tunnel_key,
+        format!(
+            "检测到进程离线，触发守护进程，将在 {:.1} 秒后自动重启（第 {} 次）",
+            delay.as_secs_f64(),
+            attempt
+        ),
+    );
+
+    thread::spawn(move || {
+        thread::sleep(delay);
+
+        let guard_state_state = app_handle.state::<ProcessGuardState>();
+
+        // 等待期间守护可能已被关闭，或隧道已被手动停止
+        let still_guarded = guard_state_state.enabled.load(Ordering::SeqCst)
+            && guard_state_state
+                .guarded_processes
+                .lock()
+                .map(|guarded| guarded.contains_key(&tunnel_key))
+                .unwrap_or(false)
+            && !guard_state_state
+                .manually_stopped
+                .lock()
+                .map(|stopped| stopped.contains(&tunnel_key))
+                .unwrap_or(true);
+        if !still_guarded {
+            finish_restart(guard_state_state, &tunnel_key);
+            update_tunnel_state(&app_handle, &tunnel_key, |current| match current {
+                Some(TunnelState::GuardedRestarting) => Some(TunnelState::Stopped),
+                _ => None,
+            });
+            return;
+        }
+
+        let result = tauri::async_runtime::block_on(crate::commands::process::start_tunnel(
+            &app_handle,
+            &tunnel_key,
+        ));
 
-                let is_running = {
-                    match processes.processes.lock() {
-                        Ok(mut procs) => {
-                            if let Some(child) = procs.get_mut(&tunnel_id) {
-                                match child.try_wait() {
-                                    Ok(Some(_)) => {
-                                        procs.remove(&tunnel_id);
-                                        false
-                                    }
-                                    Ok(None) => true,
-                                    Err(_) => {
-                                        procs.remove(&tunnel_id);
-                                        false
-                                    }
-                                }
-                            } else {
-                                false
-                            }
-                        }
-                        Err(_) => continue,
-                    }
-                };
-
-                if !is_running {
-                    let timestamp = chrono::Local::now().format("%H:%M:%S").to_string();
-                    let _ = app_handle.emit(
-                        "frpc-log",
-                        LogMessage {
-                            tunnel_id,
-                            message: "检测到进程离线，触发守护进程，自动重启中".to_string(),
-                            timestamp,
-                        },
-                    );
-
-                    let app_clone = app_handle.clone();
-                    let tunnel_type = info.tunnel_type.clone();
-
-                    thread::spawn(move || {
-                        thread::sleep(Duration::from_secs(1));
-
-                        let processes_state = app_clone.state::<FrpcProcesses>();
-                        let guard_state_state = app_clone.state::<ProcessGuardState>();
-
-                        let result = match tunnel_type {
-                            TunnelType::Api { user_token } => {
-                                tauri::async_runtime::block_on(async {
-                                    crate::commands::process::start_frpc(
-                                        app_clone.clone(),
-                                        tunnel_id,
-                                        user_token.clone(),
-                                        processes_state,
-                                        guard_state_state,
-                                    )
-                                    .await
-                                })
-                            }
-                            TunnelType::Custom { original_id } => {
-                                tauri::async_runtime::block_on(async {
-                                    crate::commands::custom_tunnel::start_custom_tunnel(
-                                        app_clone.clone(),
-                                        original_id.clone(),
-                                        processes_state,
-                                        guard_state_state,
-                                    )
-                                    .await
-                                })
-                            }
-                        };
-
-                        match result {
-                            Ok(_) => {
-                                let timestamp = chrono::Local::now().format("%H:%M:%S").to_string();
-                                let _ = app_clone.emit(
-                                    "tunnel-auto-restarted",
-                                    serde_json::json!({
-                                        "tunnel_id": tunnel_id,
-                                        "timestamp": timestamp,
-                                    }),
-                                );
-                            }
-                            Err(e) => {
-                                let timestamp = chrono::Local::now().format("%H:%M:%S").to_string();
-                                let _ = app_clone.emit(
-                                    "frpc-log",
-                                    LogMessage {
-                                        tunnel_id,
-                                        message: format!("守护进程重启失败: {}", e),
-                                        timestamp,
-                                    },
-                                );
-
-                                let guard_state_final = app_clone.state::<ProcessGuardState>();
-                                if let Ok(mut guarded) = guard_state_final.guarded_processes.lock()
-                                {
-                                    guarded.remove(&tunnel_id);
-                                };
-                            }
-                        }
-                    });
+        finish_restart(guard_state_state, &tunnel_key);
+
+        match result {
+            Ok(_) => {
+                let timestamp = chrono::Local::now().format("%H:%M:%S").to_string();
+                let _ = app_handle.emit(
+                    "tunnel-auto-restarted",
+                    serde_json::json!({
+                        "tunnel_key": tunnel_key,
+                        "tunnel_id": tunnel_key.legacy_id(),
+                        "attempt": attempt,
+                        "timestamp": timestamp,
+                    }),
+                );
+            }
+            Err(e) => {
+                let _ =
+                    emit_tunnel_log(&app_handle, &tunnel_key, format!("守护进程重启失败: {}", e));
+                finish_tls_remediation(&app_handle, &tunnel_key, None, false);
+
+                // 等待期间隧道可能已被手动启动
+                let running = app_handle
+                    .state::<FrpcProcesses>()
+                    .processes
+                    .lock()
+                    .map(|procs| procs.get(&tunnel_key).is_some_and(|p| p.is_running()))
+                    .unwrap_or(false);
+                if !running {
+                    plan_and_schedule_restart(&app_handle, info, Duration::ZERO);
                 }
             }
         }
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> GuardConfig {
+        GuardConfig {
+            initial_backoff_ms: 1000,
+            max_backoff_ms: 10_000,
+            max_restarts: 3,
+            restart_window_secs: 300,
+            stable_uptime_secs: 60,
+        }
+    }
+
+    fn guard_state() -> ProcessGuardState {
+        let guard_state = ProcessGuardState::new();
+        *guard_state.config.lock().unwrap() = config();
+        guard_state
+    }
+
+    // 模拟一次重启完成，返回本次的序号和等待时间
+    fn restart(guard_state: &ProcessGuardState, uptime: Duration) -> (u32, Duration) {
+        let key = TunnelKey::Api(1);
+        match plan_restart(guard_state, &key, uptime) {
+            RestartDecision::Restart { delay, attempt } => {
+                finish_restart(guard_state, &key);
+                (attempt, delay)
+            }
+            _ => panic!("应当重启"),
+        }
+    }
+
+    #[test]
+    fn random_unit_is_in_range() {
+        for _ in 0..1000 {
+            let value = random_unit();
+            assert!((0.0..1.0).contains(&value), "{}", value);
+        }
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_cap_with_jitter() {
+        let config = config();
+        for (attempt, base) in [(0, 1000), (1, 2000), (2, 4000), (3, 8000), (4, 10_000)] {
+            for _ in 0..100 {
+                let delay = backoff_delay(&config, attempt).as_millis() as u64;
+                assert!(
+                    (base * 8 / 10..=base * 12 / 10).contains(&delay),
+                    "第 {} 次等待 {}ms",
+                    attempt,
+                    delay
+                );
+            }
+        }
+        // 次数很大时不溢出，仍按上限计算
+        let delay = backoff_delay(&config, u32::MAX).as_millis() as u64;
+        assert!(delay <= 12_000);
+    }
+
+    #[test]
+    fn attempts_grow_until_crash_looping() {
+        let guard_state = guard_state();
+        let key = TunnelKey::Api(1);
+        let uptime = Duration::from_secs(1);
+
+        for expected in 1..=3 {
+            let (attempt, delay) = restart(&guard_state, uptime);
+            assert_eq!(attempt, expected);
+            let base = 1000u64 << (expected - 1);
+            assert!(delay.as_millis() as u64 >= base * 8 / 10);
+        }
+
+        match plan_restart(&guard_state, &key, uptime) {
+            RestartDecision::CrashLooping { restarts } => assert_eq!(restarts, 3),
+            _ => panic!("应当进入崩溃循环"),
+        }
+        // 崩溃循环后不再重启，直到手动启动清除状态
+        assert!(matches!(
+            plan_restart(&guard_state, &key, uptime),
+            RestartDecision::Skip
+        ));
+
+        mark_process_started(&guard_state, &key);
+        assert_eq!(restart(&guard_state, uptime).0, 1);
+    }
+
+    #[test]
+    fn pending_restart_is_not_planned_twice() {
+        let guard_state = guard_state();
+        let key = TunnelKey::Api(1);
+        assert!(matches!(
+            plan_restart(&guard_state, &key, Duration::ZERO),
+            RestartDecision::Restart { .. }
+        ));
+        assert!(matches!(
+            plan_restart(&guard_state, &key, Duration::ZERO),
+            RestartDecision::Skip
+        ));
+    }
+
+    #[test]
+    fn stable_run_resets_backoff() {
+        let guard_state = guard_state();
+        let short = Duration::from_secs(1);
+        restart(&guard_state, short);
+        restart(&guard_state, short);
+        let (attempt, _) = restart(&guard_state, short);
+        assert_eq!(attempt, 3);
+
+        let (attempt, delay) = restart(&guard_state, Duration::from_secs(60));
+        assert_eq!(attempt, 1);
+        assert!(delay.as_millis() <= 1200);
+    }
+
+    #[test]
+    fn manual_start_keeps_last_config() {
+        let guard_state = guard_state();
+        let key = TunnelKey::Api(1);
+        {
+            let mut states = guard_state.restart_states.lock().unwrap();
+            let state = states.entry(key.clone()).or_default();
+            state.crash_looping = true;
+            state.last_config = Some("[common]".to_string());
+        }
+        mark_process_started(&guard_state, &key);
+        let states = guard_state.restart_states.lock().unwrap();
+        assert!(!states[&key].crash_looping);
+        assert_eq!(states[&key].last_config.as_deref(), Some("[common]"));
+    }
+
+    #[test]
+    fn rule_backoff_extends_delay() {
+        let guard_state = guard_state();
+        let key = TunnelKey::Api(1);
+        guard_state
+            .restart_states
+            .lock()
+            .unwrap()
+            .entry(key.clone())
+            .or_default()
+            .backoff_until = Some(Instant::now() + Duration::from_secs(30));
+
+        let (_, delay) = restart(&guard_state, Duration::ZERO);
+        assert!(delay > Duration::from_secs(29));
+        assert!(guard_state.restart_states.lock().unwrap()[&key]
+            .backoff_until
+            .is_none());
+    }
+}
@@ -0,0 +1,44 @@
+use crate::app_context::AppContext;
+use crate::models::ProcessGuardState;
+use crate::settings::{Settings, SettingsState};
+use tauri::State;
+
+/// 获取全部设置
+#[tauri::command]
+pub async fn get_settings(settings: State<'_, SettingsState>) -> Result<Settings, String> {
+    Ok(settings.get())
+}
+
+/// 修改设置，只需传入要修改的项，返回修改后的完整设置
+#[tauri::command]
+pub async fn set_settings(
+    app_handle: tauri::AppHandle,
+    settings: State<'_, SettingsState>,
+    patch: serde_json::Map<String, serde_json::Value>,
+) -> Result<Settings, String> {
+    let app_handle = AppContext::from(app_handle);
+    let mut merged = match serde_json::to_value(settings.get()) {
+        Ok(serde_json::Value::Object(merged)) => merged,
+        _ => return Err("序列化设置失败".to_string()),
+    };
+    merged.extend(patch);
+    let next: Settings = serde_json::from_value(serde_json::Value::Object(merged))
+        .map_err(|e| format!("设置格式不正确: {}", e))?;
+
+    let previous_never_guard = settings.get().never_guard;
+    let updated = settings.update(&app_handle, |current| {
+        *current = next;
+        current.clone()
+    })?;
+    crate::commands::process_guard::apply_guard_settings(&app_handle);
+    // 与 set_tunnel_never_guard 一样，新设为从不守护的隧道立即取消守护
+    for tunnel_key in updated.never_guard.difference(&previous_never_guard) {
+        let _ = crate::commands::process_guard::remove_guarded(
+            app_handle.state::<ProcessGuardState>(),
+            tunnel_key.clone(),
+            true,
+        );
+    }
+    crate::control_api::apply_settings(&app_handle);
+    Ok(updated)
+}
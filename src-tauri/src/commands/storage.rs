@@ -0,0 +1,7 @@
+use crate::json_store::{self, StorageRecovery};
+
+/// 取出启动以来从备份恢复的状态文件，每条记录只返回一次
+#[tauri::command]
+pub async fn take_storage_recoveries() -> Result<Vec<StorageRecovery>, String> {
+    Ok(json_store::take_recoveries())
+}
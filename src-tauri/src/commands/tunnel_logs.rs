@@ -0,0 +1,152 @@
+use crate::app_context::AppContext;
+use crate::models::TunnelKey;
+use crate::redact::tunnel_redactor;
+use crate::tunnel_logs::{
+    list_log_files, log_file_path, log_files_oldest_first, logged_tunnels, logs_root,
+    read_log_page, tunnel_log_dir, TunnelLogFileInfo, TunnelLogFiles,
+};
+use serde::Serialize;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::PathBuf;
+use tauri::State;
+
+/// 每页默认行数
+const DEFAULT_PAGE_LINES: usize = 500;
+
+/// 每页最多行数
+const MAX_PAGE_LINES: usize = 5000;
+
+// 日志分页读取结果
+#[derive(Serialize)]
+pub struct TunnelLogPage {
+    pub file_name: String,
+    // 第一行在文件中的行号（从 0 开始）
+    pub start_line: usize,
+    pub lines: Vec<String>,
+    pub total_lines: usize,
+}
+
+#[tauri::command]
+pub async fn list_tunnel_logs(
+    app_handle: tauri::AppHandle,
+    tunnel_key: Option<TunnelKey>,
+) -> Result<Vec<TunnelLogFileInfo>, String> {
+    let app_handle = AppContext::from(app_handle);
+    if let Some(tunnel_key) = tunnel_key {
+        let dir = tunnel_log_dir(&app_handle, &tunnel_key)?;
+        return Ok(list_log_files(&dir, &tunnel_key));
+    }
+
+    let root = logs_root(&app_handle)?;
+    let tunnel_keys = match logged_tunnels(&root) {
+        Ok(tunnel_keys) => tunnel_keys,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("读取日志目录失败: {}", e)),
+    };
+
+    let mut files = Vec::new();
+    for tunnel_key in &tunnel_keys {
+        files.extend(list_log_files(
+            &tunnel_log_dir(&app_handle, tunnel_key)?,
+            tunnel_key,
+        ));
+    }
+    Ok(files)
+}
+
+/// 分页读取日志，tail 为 true 时 offset 从文件末尾倒数
+#[tauri::command]
+pub async fn read_tunnel_log(
+    app_handle: tauri::AppHandle,
+    tunnel_key: TunnelKey,
+    file_name: Option<String>,
+    offset: Option<usize>,
+    limit: Option<usize>,
+    tail: Option<bool>,
+) -> Result<TunnelLogPage, String> {
+    let app_handle = AppContext::from(app_handle);
+    let dir = tunnel_log_dir(&app_handle, &tunnel_key)?;
+    let path = log_file_path(&dir, file_name.as_deref())?;
+
+    let limit = limit.unwrap_or(DEFAULT_PAGE_LINES).clamp(1, MAX_PAGE_LINES);
+    let page = read_log_page(&path, offset.unwrap_or(0), limit, tail.unwrap_or(false))
+        .map_err(|e| format!("读取日志文件失败: {}", e))?;
+    // 日志文件可能写于升级前，读取和导出时按隧道当前的配置再脱敏一次
+    let redactor = tunnel_redactor(&app_handle, &tunnel_key);
+
+    Ok(TunnelLogPage {
+        file_name: path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        start_line: page.start_line,
+        lines: page.lines.iter().map(|l| redactor.redact(l)).collect(),
+        total_lines: page.total_lines,
+    })
+}
+
+/// 导出日志，未指定文件时按时间顺序合并该隧道的全部日志
+#[tauri::command]
+pub async fn export_tunnel_logs(
+    app_handle: tauri::AppHandle,
+    tunnel_key: TunnelKey,
+    file_name: Option<String>,
+    target_path: String,
+) -> Result<String, String> {
+    let app_handle = AppContext::from(app_handle);
+    let dir = tunnel_log_dir(&app_handle, &tunnel_key)?;
+    let sources = match file_name {
+        Some(file_name) => vec![log_file_path(&dir, Some(&file_name))?],
+        None => log_files_oldest_first(&dir, &tunnel_key),
+    };
+    if sources.is_empty() || !sources.iter().all(|p| p.exists()) {
+        return Err("日志文件不存在".to_string());
+    }
+
+    let redactor = tunnel_redactor(&app_handle, &tunnel_key);
+    let target = PathBuf::from(&target_path);
+    let mut output =
+        BufWriter::new(File::create(&target).map_err(|e| format!("创建导出文件失败: {}", e))?);
+    for source in &sources {
+        let input = File::open(source).map_err(|e| format!("打开日志文件失败: {}", e))?;
+        for line in BufReader::new(input).split(b'\n') {
+            let line = line.map_err(|e| format!("导出日志失败: {}", e))?;
+            let line = redactor.redact(&String::from_utf8_lossy(&line));
+            writeln!(output, "{}", line).map_err(|e| format!("导出日志失败: {}", e))?;
+        }
+    }
+    output.flush().map_err(|e| format!("导出日志失败: {}", e))?;
+
+    Ok(format!("已导出 {} 个日志文件", sources.len()))
+}
+
+/// 删除日志，未指定文件时删除该隧道的全部日志
+#[tauri::command]
+pub async fn delete_tunnel_logs(
+    app_handle: tauri::AppHandle,
+    tunnel_key: TunnelKey,
+    file_name: Option<String>,
+    log_files: State<'_, TunnelLogFiles>,
+) -> Result<(), String> {
+    let app_handle = AppContext::from(app_handle);
+    let dir = tunnel_log_dir(&app_handle, &tunnel_key)?;
+    let targets = match file_name {
+        Some(file_name) => vec![log_file_path(&dir, Some(&file_name))?],
+        None => log_files_oldest_first(&dir, &tunnel_key),
+    };
+
+    // 先关闭正在写入的文件，Windows 上无法删除已打开的文件
+    log_files.with_writer_closed(&tunnel_key, || {
+        for target in &targets {
+            match fs::remove_file(target) {
+                Ok(()) => {}
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(format!("删除日志文件失败: {}", e)),
+            }
+        }
+        // 目录为空时一并删除
+        let _ = fs::remove_dir(&dir);
+        Ok(())
+    })
+}
@@ -0,0 +1,116 @@
+use crate::app_context::AppContext;
+use crate::frpc_log::{FrpcEvent, FrpcEventKind};
+use crate::models::{ExitInfo, TunnelKey, TunnelState, TunnelStateInfo, TunnelStates};
+use tauri::State;
+
+/// 设置隧道状态
+pub fn set_tunnel_state(app_handle: &AppContext, tunnel_key: &TunnelKey, state: TunnelState) {
+    update_tunnel_state(app_handle, tunnel_key, |_| Some(state));
+}
+
+/// 根据当前状态计算新状态，返回 None 表示保持不变；状态变化时发送 tunnel-state-changed
+pub fn update_tunnel_state(
+    app_handle: &AppContext,
+    tunnel_key: &TunnelKey,
+    transition: impl FnOnce(Option<&TunnelState>) -> Option<TunnelState>,
+) {
+    let states = app_handle.state::<TunnelStates>();
+    let changed = {
+        let mut states = match states.states.lock() {
+            Ok(states) => states,
+            Err(e) => {
+                eprintln!("[隧道状态] 获取状态锁失败: {}", e);
+                return;
+            }
+        };
+        let current = states.get(tunnel_key).map(|info| &info.state);
+        match transition(current) {
+            Some(next) if current != Some(&next) => {
+                let info = TunnelStateInfo {
+                    tunnel_key: tunnel_key.clone(),
+                    tunnel_id: tunnel_key.legacy_id(),
+                    state: next,
+                    since: chrono::Local::now().to_rfc3339(),
+                };
+                states.insert(tunnel_key.clone(), info.clone());
+                Some(info)
+            }
+            _ => None,
+        }
+    };
+
+    if let Some(info) = changed {
+        let _ = app_handle.emit("tunnel-state-changed", info);
+    }
+}
+
+/// 根据解析出的 frpc 日志事件更新状态
+pub fn apply_frpc_event(app_handle: &AppContext, event: &FrpcEvent) {
+    let kind = match event.kind {
+        Some(kind) => kind,
+        None => return,
+    };
+    let reason = event.line.body.clone();
+
+    update_tunnel_state(app_handle, &event.tunnel_key, |current| {
+        // 停止过程中的输出不再影响状态
+        if matches!(
+            current,
+            Some(TunnelState::Stopping) | Some(TunnelState::Stopped)
+        ) {
+            return None;
+        }
+        match kind {
+            FrpcEventKind::ProxyStarted => Some(TunnelState::Connected),
+            FrpcEventKind::Reconnecting
+            | FrpcEventKind::IoTimeout
+            | FrpcEventKind::ConnectionReset => match current {
+                Some(TunnelState::Failed(_)) => None,
+                _ => Some(TunnelState::Reconnecting),
+            },
+            FrpcEventKind::ProxyRejected
+            | FrpcEventKind::TlsError
+            | FrpcEventKind::AuthFailed
+            | FrpcEventKind::ApiRejected
+            | FrpcEventKind::RateLimited => Some(TunnelState::Failed(reason)),
+            // 登录成功后还要等映射启动
+            FrpcEventKind::LoginSuccess => None,
+        }
+    });
+}
+
+/// 进程退出后更新状态，守护进程随后可能改为 GuardedRestarting
+pub fn apply_process_exit(app_handle: &AppContext, tunnel_key: &TunnelKey, exit_info: ExitInfo) {
+    update_tunnel_state(app_handle, tunnel_key, |current| match current {
+        Some(TunnelState::Stopping) | Some(TunnelState::Stopped) => Some(TunnelState::Stopped),
+        // 保留日志中更具体的失败原因
+        Some(TunnelState::Failed(_)) => None,
+        _ => Some(TunnelState::Failed(describe_exit(exit_info))),
+    });
+}
+
+fn describe_exit(exit_info: ExitInfo) -> String {
+    match (exit_info.exit_code, exit_info.signal) {
+        (Some(code), _) => format!("frpc 进程已退出，退出码 {}", code),
+        (None, Some(signal)) => format!("frpc 进程被信号 {} 终止", signal),
+        (None, None) => "frpc 进程已退出".to_string(),
+    }
+}
+
+#[tauri::command]
+pub async fn get_tunnel_states(
+    tunnel_states: State<'_, TunnelStates>,
+) -> Result<Vec<TunnelStateInfo>, String> {
+    list_tunnel_states(&tunnel_states)
+}
+
+/// 按隧道标识排序的全部隧道状态
+pub fn list_tunnel_states(tunnel_states: &TunnelStates) -> Result<Vec<TunnelStateInfo>, String> {
+    let states = tunnel_states
+        .states
+        .lock()
+        .map_err(|e| format!("获取状态锁失败: {}", e))?;
+    let mut states: Vec<TunnelStateInfo> = states.values().cloned().collect();
+    states.sort_by(|a, b| a.tunnel_key.cmp(&b.tunnel_key));
+    Ok(states)
+}
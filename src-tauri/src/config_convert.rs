@@ -0,0 +1,526 @@
+// INI 配置转换
+//
+// 新版 frpc 读取 INI 配置时会提示该格式已弃用。这里把 [common] 和各代理段落
+// 按 frpc 的新版配置结构转换为 TOML、YAML 或 JSON，键名改为驼峰形式，
+// 例如 server_addr 转为 serverAddr、local_port 转为 localPort，
+// tls_enable 转为 transport.tls.enable。无法对应的键保留在转换结果之外，
+// 由调用方展示给用户。
+
+use crate::frpc_config::{
+    is_proxy_section, parse_document, serialize_document, ConfigFormat, COMMON_SECTION,
+};
+use crate::ini::{IniDocument, IniEntry};
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+/// 值的类型，决定转换后写成字符串、数字、布尔值还是数组
+#[derive(Clone, Copy)]
+enum Kind {
+    Str,
+    Int,
+    Bool,
+    List,
+}
+
+/// [common] 段的键
+const COMMON_KEYS: &[(&str, &str, Kind)] = &[
+    ("server_addr", "serverAddr", Kind::Str),
+    ("server_port", "serverPort", Kind::Int),
+    ("user", "user", Kind::Str),
+    ("authentication_method", "auth.method", Kind::Str),
+    ("token", "auth.token", Kind::Str),
+    ("oidc_client_id", "auth.oidc.clientID", Kind::Str),
+    ("oidc_client_secret", "auth.oidc.clientSecret", Kind::Str),
+    ("oidc_audience", "auth.oidc.audience", Kind::Str),
+    ("oidc_scope", "auth.oidc.scope", Kind::Str),
+    (
+        "oidc_token_endpoint_url",
+        "auth.oidc.tokenEndpointURL",
+        Kind::Str,
+    ),
+    ("log_file", "log.to", Kind::Str),
+    ("log_level", "log.level", Kind::Str),
+    ("log_max_days", "log.maxDays", Kind::Int),
+    ("disable_log_color", "log.disablePrintColor", Kind::Bool),
+    ("admin_addr", "webServer.addr", Kind::Str),
+    ("admin_port", "webServer.port", Kind::Int),
+    ("admin_user", "webServer.user", Kind::Str),
+    ("admin_pwd", "webServer.password", Kind::Str),
+    ("assets_dir", "webServer.assetsDir", Kind::Str),
+    ("protocol", "transport.protocol", Kind::Str),
+    ("pool_count", "transport.poolCount", Kind::Int),
+    ("tcp_mux", "transport.tcpMux", Kind::Bool),
+    (
+        "tcp_mux_keepalive_interval",
+        "transport.tcpMuxKeepaliveInterval",
+        Kind::Int,
+    ),
+    (
+        "connect_server_local_ip",
+        "transport.connectServerLocalIP",
+        Kind::Str,
+    ),
+    (
+        "dial_server_timeout",
+        "transport.dialServerTimeout",
+        Kind::Int,
+    ),
+    (
+        "dial_server_keepalive",
+        "transport.dialServerKeepalive",
+        Kind::Int,
+    ),
+    ("http_proxy", "transport.proxyURL", Kind::Str),
+    (
+        "heartbeat_interval",
+        "transport.heartbeatInterval",
+        Kind::Int,
+    ),
+    ("heartbeat_timeout", "transport.heartbeatTimeout", Kind::Int),
+    ("tls_enable", "transport.tls.enable", Kind::Bool),
+    ("tls_cert_file", "transport.tls.certFile", Kind::Str),
+    ("tls_key_file", "transport.tls.keyFile", Kind::Str),
+    (
+        "tls_trusted_ca_file",
+        "transport.tls.trustedCaFile",
+        Kind::Str,
+    ),
+    ("tls_server_name", "transport.tls.serverName", Kind::Str),
+    (
+        "disable_custom_tls_first_byte",
+        "transport.tls.disableCustomTLSFirstByte",
+        Kind::Bool,
+    ),
+    ("login_fail_exit", "loginFailExit", Kind::Bool),
+    ("dns_server", "dnsServer", Kind::Str),
+    ("start", "start", Kind::List),
+    ("udp_packet_size", "udpPacketSize", Kind::Int),
+];
+
+/// 代理段和访问者段的键
+const PROXY_KEYS: &[(&str, &str, Kind)] = &[
+    ("type", "type", Kind::Str),
+    ("local_ip", "localIP", Kind::Str),
+    ("local_port", "localPort", Kind::Int),
+    ("remote_port", "remotePort", Kind::Int),
+    ("custom_domains", "customDomains", Kind::List),
+    ("subdomain", "subdomain", Kind::Str),
+    ("locations", "locations", Kind::List),
+    ("http_user", "httpUser", Kind::Str),
+    ("http_pwd", "httpPassword", Kind::Str),
+    ("host_header_rewrite", "hostHeaderRewrite", Kind::Str),
+    ("route_by_http_user", "routeByHTTPUser", Kind::Str),
+    ("multiplexer", "multiplexer", Kind::Str),
+    ("use_encryption", "transport.useEncryption", Kind::Bool),
+    ("use_compression", "transport.useCompression", Kind::Bool),
+    ("bandwidth_limit", "transport.bandwidthLimit", Kind::Str),
+    (
+        "bandwidth_limit_mode",
+        "transport.bandwidthLimitMode",
+        Kind::Str,
+    ),
+    (
+        "proxy_protocol_version",
+        "transport.proxyProtocolVersion",
+        Kind::Str,
+    ),
+    ("group", "loadBalancer.group", Kind::Str),
+    ("group_key", "loadBalancer.groupKey", Kind::Str),
+    ("health_check_type", "healthCheck.type", Kind::Str),
+    (
+        "health_check_timeout_s",
+        "healthCheck.timeoutSeconds",
+        Kind::Int,
+    ),
+    (
+        "health_check_max_failed",
+        "healthCheck.maxFailed",
+        Kind::Int,
+    ),
+    (
+        "health_check_interval_s",
+        "healthCheck.intervalSeconds",
+        Kind::Int,
+    ),
+    ("health_check_url", "healthCheck.path", Kind::Str),
+    ("sk", "secretKey", Kind::Str),
+    ("allow_users", "allowUsers", Kind::List),
+    ("server_user", "serverUser", Kind::Str),
+    ("server_name", "serverName", Kind::Str),
+    ("bind_addr", "bindAddr", Kind::Str),
+    ("bind_port", "bindPort", Kind::Int),
+    ("plugin", "plugin.type", Kind::Str),
+    ("plugin_local_path", "plugin.localPath", Kind::Str),
+    ("plugin_strip_prefix", "plugin.stripPrefix", Kind::Str),
+    ("plugin_http_user", "plugin.httpUser", Kind::Str),
+    ("plugin_http_passwd", "plugin.httpPassword", Kind::Str),
+    ("plugin_unix_path", "plugin.unixPath", Kind::Str),
+    ("plugin_local_addr", "plugin.localAddr", Kind::Str),
+    ("plugin_crt_path", "plugin.crtPath", Kind::Str),
+    ("plugin_key_path", "plugin.keyPath", Kind::Str),
+    (
+        "plugin_host_header_rewrite",
+        "plugin.hostHeaderRewrite",
+        Kind::Str,
+    ),
+    ("plugin_user", "plugin.username", Kind::Str),
+    ("plugin_passwd", "plugin.password", Kind::Str),
+];
+
+/// 带前缀的键，前缀之后的部分作为子键
+const PREFIXED_KEYS: &[(&str, &str)] = &[("meta_", "metadatas"), ("header_", "requestHeaders.set")];
+
+/// 转换结果
+#[derive(Serialize, Clone, Debug)]
+pub struct ConfigConversion {
+    pub format: ConfigFormat,
+    pub content: String,
+    pub unmapped_keys: Vec<UnmappedKey>,
+    /// 是否已写回配置文件
+    pub applied: bool,
+}
+
+/// 无法转换的键
+#[derive(Serialize, Clone, Debug)]
+pub struct UnmappedKey {
+    pub section: String,
+    pub key: String,
+    pub value: String,
+    /// 从 1 开始的行号
+    pub line: usize,
+}
+
+/// 转换配置格式，目前只能转换为 TOML、YAML 或 JSON
+pub fn convert_config(
+    content: &str,
+    source: ConfigFormat,
+    target: ConfigFormat,
+) -> Result<ConfigConversion, String> {
+    if target == ConfigFormat::Ini {
+        return Err("暂不支持转换为 INI 格式".to_string());
+    }
+    if source == target {
+        return Err(format!(
+            "配置已经是 {} 格式",
+            target.extension().to_uppercase()
+        ));
+    }
+
+    let (document, unmapped_keys) = match source {
+        ConfigFormat::Ini => ini_to_document(content),
+        _ => (parse_document(content, source)?, Vec::new()),
+    };
+
+    Ok(ConfigConversion {
+        format: target,
+        content: serialize_document(&document, target)?,
+        unmapped_keys,
+        applied: false,
+    })
+}
+
+/// INI 中的键是否能转换为新版配置项
+pub fn is_mapped_key(key: &str, proxy: bool) -> bool {
+    let keys = if proxy { PROXY_KEYS } else { COMMON_KEYS };
+    keys.iter().any(|(name, _, _)| *name == key)
+        || PREFIXED_KEYS
+            .iter()
+            .any(|(prefix, _)| key.len() > prefix.len() && key.starts_with(prefix))
+}
+
+/// 新版配置中的键是否是转换结果会生成的顶层键
+pub fn is_mapped_field(field: &str, proxy: bool) -> bool {
+    let keys = if proxy { PROXY_KEYS } else { COMMON_KEYS };
+    keys.iter()
+        .map(|(_, path, _)| *path)
+        .chain(PREFIXED_KEYS.iter().map(|(_, path)| *path))
+        .any(|path| path.split('.').next() == Some(field))
+}
+
+fn ini_to_document(content: &str) -> (Map<String, Value>, Vec<UnmappedKey>) {
+    let ini = IniDocument::parse(content);
+    let mut document = Map::new();
+    let mut proxies = Vec::new();
+    let mut visitors = Vec::new();
+
+    // 第一个段落之前的键不属于任何段落
+    let mut unmapped: Vec<UnmappedKey> = ini
+        .orphans()
+        .iter()
+        .map(|entry| unmapped_key("", entry))
+        .collect();
+
+    for section in ini.sections() {
+        if section.name == COMMON_SECTION {
+            for entry in &section.entries {
+                if !convert_key(&mut document, COMMON_KEYS, &entry.key, &entry.value) {
+                    unmapped.push(unmapped_key(&section.name, entry));
+                }
+            }
+            continue;
+        }
+        if !is_proxy_section(section) {
+            continue;
+        }
+
+        let mut item = Map::new();
+        item.insert("name".to_string(), Value::String(section.name.clone()));
+        let mut is_visitor = false;
+        for entry in &section.entries {
+            // 访问者写在 visitors 中，role 本身不再需要
+            if entry.key == "role" {
+                is_visitor = entry.value == "visitor";
+                continue;
+            }
+            if !convert_key(&mut item, PROXY_KEYS, &entry.key, &entry.value) {
+                unmapped.push(unmapped_key(&section.name, entry));
+            }
+        }
+
+        if is_visitor {
+            visitors.push(Value::Object(item));
+        } else {
+            proxies.push(Value::Object(item));
+        }
+    }
+
+    if !proxies.is_empty() {
+        document.insert("proxies".to_string(), Value::Array(proxies));
+    }
+    if !visitors.is_empty() {
+        document.insert("visitors".to_string(), Value::Array(visitors));
+    }
+
+    (document, unmapped)
+}
+
+// 转换一个键，没有对应的键或值类型不符时返回 false
+fn convert_key(
+    target: &mut Map<String, Value>,
+    keys: &[(&str, &str, Kind)],
+    key: &str,
+    value: &str,
+) -> bool {
+    if let Some((_, path, kind)) = keys.iter().find(|(name, _, _)| *name == key) {
+        return match convert_value(value, *kind) {
+            Some(value) => {
+                insert_path(target, path, value);
+                true
+            }
+            None => false,
+        };
+    }
+
+    for (prefix, path) in PREFIXED_KEYS {
+        if let Some(sub_key) = key.strip_prefix(prefix).filter(|k| !k.is_empty()) {
+            insert_path(
+                target,
+                &format!("{}.{}", path, sub_key),
+                Value::String(value.to_string()),
+            );
+            return true;
+        }
+    }
+
+    false
+}
+
+fn convert_value(value: &str, kind: Kind) -> Option<Value> {
+    match kind {
+        Kind::Str => Some(Value::String(value.to_string())),
+        Kind::Int => value.parse::<i64>().ok().map(Value::from),
+        Kind::Bool => match value.to_ascii_lowercase().as_str() {
+            "true" | "1" | "yes" | "on" => Some(Value::Bool(true)),
+            "false" | "0" | "no" | "off" => Some(Value::Bool(false)),
+            _ => None,
+        },
+        Kind::List => Some(Value::Array(
+            value
+                .split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(|item| Value::String(item.to_string()))
+                .collect(),
+        )),
+    }
+}
+
+// 按 "a.b.c" 写入嵌套的表
+fn insert_path(target: &mut Map<String, Value>, path: &str, value: Value) {
+    let mut current = target;
+    let mut parts = path.split('.').peekable();
+    while let Some(part) = parts.next() {
+        if parts.peek().is_none() {
+            current.insert(part.to_string(), value);
+            return;
+        }
+        let next = current
+            .entry(part.to_string())
+            .or_insert_with(|| Value::Object(Map::new()));
+        if !next.is_object() {
+            *next = Value::Object(Map::new());
+        }
+        current = match next.as_object_mut() {
+            Some(map) => map,
+            None => return,
+        };
+    }
+}
+
+fn unmapped_key(section: &str, entry: &IniEntry) -> UnmappedKey {
+    UnmappedKey {
+        section: section.to_string(),
+        key: entry.key.clone(),
+        value: entry.value.clone(),
+        line: entry.line,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    const SAMPLE: &str = "\
+[common]
+server_addr = frp.example.com
+server_port = 7000
+token = secret
+tls_enable = true
+log_level = info
+unknown_option = 1
+
+[web]
+type = http
+local_port = 8080
+custom_domains = a.example.com, b.example.com
+use_encryption = true
+meta_owner = alice
+header_X-From-Where = frp
+
+[ssh-visitor]
+role = visitor
+type = stcp
+server_name = ssh
+sk = abc
+bind_port = abc
+";
+
+    fn convert_to(target: ConfigFormat) -> ConfigConversion {
+        convert_config(SAMPLE, ConfigFormat::Ini, target).unwrap()
+    }
+
+    #[test]
+    fn key_tables_have_unique_keys_and_paths() {
+        for keys in [COMMON_KEYS, PROXY_KEYS] {
+            let mut names = HashSet::new();
+            let mut paths = HashSet::new();
+            for (name, path, _) in keys {
+                assert!(names.insert(*name), "重复的键 {}", name);
+                assert!(paths.insert(*path), "重复的路径 {}", path);
+            }
+        }
+    }
+
+    #[test]
+    fn maps_keys_to_nested_fields() {
+        let conversion = convert_to(ConfigFormat::Toml);
+        let document: Map<String, Value> = toml::from_str(&conversion.content).unwrap();
+
+        assert_eq!(document["serverAddr"], "frp.example.com");
+        assert_eq!(document["serverPort"], 7000);
+        assert_eq!(document["auth"]["token"], "secret");
+        assert_eq!(document["transport"]["tls"]["enable"], true);
+        assert_eq!(document["log"]["level"], "info");
+
+        let web = &document["proxies"][0];
+        assert_eq!(web["name"], "web");
+        assert_eq!(web["type"], "http");
+        assert_eq!(web["localPort"], 8080);
+        assert_eq!(
+            web["customDomains"],
+            serde_json::json!(["a.example.com", "b.example.com"])
+        );
+        assert_eq!(web["transport"]["useEncryption"], true);
+        assert_eq!(web["metadatas"]["owner"], "alice");
+        assert_eq!(web["requestHeaders"]["set"]["X-From-Where"], "frp");
+
+        let visitor = &document["visitors"][0];
+        assert_eq!(visitor["name"], "ssh-visitor");
+        assert_eq!(visitor["serverName"], "ssh");
+        assert!(visitor.get("role").is_none());
+    }
+
+    #[test]
+    fn reports_unmapped_keys_with_lines() {
+        let conversion = convert_to(ConfigFormat::Toml);
+        let unmapped: Vec<(&str, &str, usize)> = conversion
+            .unmapped_keys
+            .iter()
+            .map(|key| (key.section.as_str(), key.key.as_str(), key.line))
+            .collect();
+        // 未知的键和类型不符的值都不转换
+        assert_eq!(
+            unmapped,
+            vec![
+                ("common", "unknown_option", 7),
+                ("ssh-visitor", "bind_port", 22)
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_keys_outside_sections() {
+        let conversion = convert_config(
+            "server_addr = a\n[common]\nserver_port = 7000\n",
+            ConfigFormat::Ini,
+            ConfigFormat::Toml,
+        )
+        .unwrap();
+        assert_eq!(conversion.unmapped_keys.len(), 1);
+        assert_eq!(conversion.unmapped_keys[0].section, "");
+        assert_eq!(conversion.unmapped_keys[0].line, 1);
+    }
+
+    #[test]
+    fn yaml_and_json_outputs_match_toml() {
+        let toml_document: Map<String, Value> =
+            toml::from_str(&convert_to(ConfigFormat::Toml).content).unwrap();
+        let yaml_document: Map<String, Value> =
+            serde_yaml::from_str(&convert_to(ConfigFormat::Yaml).content).unwrap();
+        let json_document: Map<String, Value> =
+            serde_json::from_str(&convert_to(ConfigFormat::Json).content).unwrap();
+        assert_eq!(toml_document, yaml_document);
+        assert_eq!(toml_document, json_document);
+    }
+
+    #[test]
+    fn converts_between_structured_formats() {
+        let conversion = convert_config(
+            "serverAddr = \"a\"\nserverPort = 7000\n",
+            ConfigFormat::Toml,
+            ConfigFormat::Json,
+        )
+        .unwrap();
+        let document: Value = serde_json::from_str(&conversion.content).unwrap();
+        assert_eq!(document["serverPort"], 7000);
+        assert!(conversion.unmapped_keys.is_empty());
+    }
+
+    #[test]
+    fn rejects_ini_target_and_same_format() {
+        assert!(convert_config(SAMPLE, ConfigFormat::Toml, ConfigFormat::Ini).is_err());
+        assert!(convert_config("", ConfigFormat::Toml, ConfigFormat::Toml).is_err());
+    }
+
+    #[test]
+    fn mapped_key_checks() {
+        assert!(is_mapped_key("server_addr", false));
+        assert!(!is_mapped_key("server_addr", true));
+        assert!(is_mapped_key("meta_owner", true));
+        assert!(!is_mapped_key("meta_", true));
+        assert!(is_mapped_field("transport", false));
+        assert!(is_mapped_field("requestHeaders", true));
+        assert!(!is_mapped_field("proxies", false));
+    }
+}
@@ -0,0 +1,754 @@
+// 自定义隧道配置校验
+//
+// 在启动 frpc 之前按 frpc 认识的键和各代理类型的规则检查配置，提前发现缺少
+// server_addr、端口超出范围、http 代理设置了 remote_port、未知的代理类型、
+// 代理名重复等问题。错误会导致 frpc 启动失败或代理无法注册，警告只作提示。
+// INI 配置的行号取自 IniDocument；TOML、YAML 和 JSON 按键名查找所在的行，
+// 只是近似位置，找不到时为 0。
+
+use crate::config_convert::{is_mapped_field, is_mapped_key};
+use crate::frpc_config::{
+    entries, is_proxy_section, parse_document, strip_bom, ConfigFormat, COMMON_SECTION,
+};
+use crate::ini::{IniDocument, IniLineKind, IniSection};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::collections::HashSet;
+
+/// frpc 支持的代理类型
+const PROXY_TYPES: &[&str] = &[
+    "tcp", "udp", "http", "https", "tcpmux", "stcp", "sudp", "xtcp",
+];
+
+/// 通过 sk 访问的类型，只有这些类型可以作为访问者
+const SECRET_TYPES: &[&str] = &["stcp", "sudp", "xtcp"];
+
+/// 按域名路由的类型
+const DOMAIN_TYPES: &[&str] = &["http", "https", "tcpmux"];
+
+/// frpc 认识但转换表中没有的 [common] 键
+const EXTRA_COMMON_KEYS: &[&str] = &[
+    "includes",
+    "authenticate_heartbeats",
+    "authenticate_new_work_conns",
+    "quic_keepalive_period",
+    "quic_max_idle_timeout",
+    "quic_max_incoming_streams",
+];
+
+/// frpc 认识但转换表中没有的代理键
+const EXTRA_PROXY_KEYS: &[&str] = &[
+    "role",
+    "protocol",
+    "keep_tunnel_open",
+    "max_retries_an_hour",
+    "min_retry_interval",
+    "fallback_to",
+    "fallback_timeout_ms",
+];
+
+/// 新版配置中转换表没有生成的顶层键
+const EXTRA_DOCUMENT_FIELDS: &[&str] = &[
+    "proxies",
+    "visitors",
+    "includes",
+    "metadatas",
+    "featureGates",
+    "natHoleStunServer",
+    "virtualNet",
+];
+
+/// 新版配置中转换表没有生成的代理键
+const EXTRA_ENTRY_FIELDS: &[&str] = &[
+    "name",
+    "annotations",
+    "responseHeaders",
+    "protocol",
+    "keepTunnelOpen",
+    "maxRetriesAnHour",
+    "minRetryInterval",
+    "fallbackTo",
+    "fallbackTimeoutMs",
+];
+
+/// 一条校验结果
+#[derive(Serialize, Clone, Debug)]
+pub struct ConfigDiagnostic {
+    /// 所在段落或代理名，整个文件的问题为空
+    pub section: String,
+    /// 从 1 开始的行号，无法定位时为 0
+    pub line: usize,
+    pub message: String,
+}
+
+/// 配置校验结果
+#[derive(Serialize, Clone, Debug)]
+pub struct ConfigValidation {
+    pub format: ConfigFormat,
+    pub valid: bool,
+    pub errors: Vec<ConfigDiagnostic>,
+    pub warnings: Vec<ConfigDiagnostic>,
+}
+
+impl ConfigValidation {
+    /// 所有错误合并为一行，用于拒绝保存时的提示
+    pub fn error_summary(&self) -> String {
+        self.errors
+            .iter()
+            .map(|diagnostic| {
+                let location = match (diagnostic.line, diagnostic.section.is_empty()) {
+                    (0, true) => String::new(),
+                    (0, false) => format!("[{}] ", diagnostic.section),
+                    (line, true) => format!("第 {} 行: ", line),
+                    (line, false) => format!("第 {} 行 [{}] ", line, diagnostic.section),
+                };
+                format!("{}{}", location, diagnostic.message)
+            })
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+
+    fn error(&mut self, section: &str, line: usize, message: impl Into<String>) {
+        self.errors.push(diagnostic(section, line, message));
+    }
+
+    fn warning(&mut self, section: &str, line: usize, message: impl Into<String>) {
+        self.warnings.push(diagnostic(section, line, message));
+    }
+}
+
+fn diagnostic(section: &str, line: usize, message: impl Into<String>) -> ConfigDiagnostic {
+    ConfigDiagnostic {
+        section: section.to_string(),
+        line,
+        message: message.into(),
+    }
+}
+
+/// 校验配置
+pub fn validate_config(content: &str, format: ConfigFormat) -> ConfigValidation {
+    let mut validation = ConfigValidation {
+        format,
+        valid: false,
+        errors: Vec::new(),
+        warnings: Vec::new(),
+    };
+
+    match format {
+        ConfigFormat::Ini => validate_ini(content, &mut validation),
+        _ => validate_document(content, format, &mut validation),
+    }
+
+    validation.valid = validation.errors.is_empty();
+    validation
+}
+
+// 参与规则检查的一个值
+struct Field {
+    key: String,
+    value: String,
+    line: usize,
+}
+
+// 一个代理或访问者中参与规则检查的字段
+struct ProxyFields {
+    name: String,
+    line: usize,
+    /// INI 和新版配置的键名不同，提示中使用配置本身的写法
+    ini: bool,
+    visitor: bool,
+    proxy_type: Option<Field>,
+    local_port: Option<Field>,
+    remote_port: Option<Field>,
+    bind_port: Option<Field>,
+    has_domains: bool,
+    has_plugin: bool,
+    has_secret: bool,
+    has_server_name: bool,
+}
+
+impl ProxyFields {
+    fn key(&self, ini: &'static str, other: &'static str) -> &'static str {
+        if self.ini {
+            ini
+        } else {
+            other
+        }
+    }
+}
+
+fn validate_ini(content: &str, validation: &mut ConfigValidation) {
+    let document = IniDocument::parse(content);
+
+    let mut current = String::new();
+    for line in document.lines() {
+        match &line.kind {
+            IniLineKind::Section { name, .. } => current = name.clone(),
+            IniLineKind::Invalid => {
+                validation.error(&current, line.number, "无法解析的行，缺少 = 或 :")
+            }
+            _ => {}
+        }
+    }
+
+    for entry in document.orphans() {
+        validation.warning(
+            "",
+            entry.line,
+            format!("{} 不属于任何段落，frpc 会忽略它", entry.key),
+        );
+    }
+
+    let mut has_common = false;
+    for section in document.sections() {
+        if section.name == COMMON_SECTION {
+            has_common = true;
+            check_ini_keys(section, false, validation);
+        }
+    }
+
+    if !has_common {
+        validation.error(COMMON_SECTION, 0, "缺少 [common] 段");
+    } else {
+        match document.get(COMMON_SECTION, "server_addr") {
+            Some(entry) if !entry.value.trim().is_empty() => {}
+            Some(entry) => validation.error(COMMON_SECTION, entry.line, "server_addr 不能为空"),
+            None => validation.error(COMMON_SECTION, 0, "缺少 server_addr"),
+        }
+        match document.get(COMMON_SECTION, "server_port") {
+            Some(entry) => check_port(
+                COMMON_SECTION,
+                &Field {
+                    key: entry.key.clone(),
+                    value: entry.value.clone(),
+                    line: entry.line,
+                },
+                validation,
+            ),
+            None => validation.warning(
+                COMMON_SECTION,
+                0,
+                "未设置 server_port，frpc 将使用默认端口 7000",
+            ),
+        }
+    }
+
+    let mut names = HashSet::new();
+    let mut proxy_count = 0;
+    for section in document.sections() {
+        if !is_proxy_section(section) {
+            continue;
+        }
+        proxy_count += 1;
+
+        if !names.insert(section.name.as_str()) {
+            validation.error(
+                &section.name,
+                section.line,
+                format!("代理名 {} 重复", section.name),
+            );
+        }
+        check_ini_keys(section, true, validation);
+
+        let field = |key: &str| {
+            section.get(key).map(|entry| Field {
+                key: entry.key.clone(),
+                value: entry.value.clone(),
+                line: entry.line,
+            })
+        };
+        let has = |key: &str| {
+            section
+                .get(key)
+                .is_some_and(|entry| !entry.value.trim().is_empty())
+        };
+
+        check_proxy(
+            &ProxyFields {
+                name: section.name.clone(),
+                line: section.line,
+                ini: true,
+                visitor: section.get("role").is_some_and(|e| e.value == "visitor"),
+                proxy_type: field("type"),
+                local_port: field("local_port"),
+                remote_port: field("remote_port"),
+                bind_port: field("bind_port"),
+                has_domains: has("custom_domains") || has("subdomain"),
+                has_plugin: has("plugin"),
+                has_secret: has("sk"),
+                has_server_name: has("server_name"),
+            },
+            validation,
+        );
+    }
+
+    if proxy_count == 0 {
+        validation.error("", 0, "配置文件必须包含至少一个隧道段");
+    }
+}
+
+// 检查段落中的未知键和重复键
+fn check_ini_keys(section: &IniSection, proxy: bool, validation: &mut ConfigValidation) {
+    let extra = if proxy {
+        EXTRA_PROXY_KEYS
+    } else {
+        EXTRA_COMMON_KEYS
+    };
+    let mut seen = HashSet::new();
+    for entry in &section.entries {
+        if !is_mapped_key(&entry.key, proxy) && !extra.contains(&entry.key.as_str()) {
+            validation.warning(
+                &section.name,
+                entry.line,
+                format!("frpc 不认识的键 {}", entry.key),
+            );
+        }
+        if !seen.insert(entry.key.as_str()) {
+            validation.warning(
+                &section.name,
+                entry.line,
+                format!("键 {} 重复，以最后一个为准", entry.key),
+            );
+        }
+    }
+}
+
+fn validate_document(content: &str, format: ConfigFormat, validation: &mut ConfigValidation) {
+    let document = match parse_document(strip_bom(content), format) {
+        Ok(document) => document,
+        Err(e) => {
+            validation.error("", 0, e);
+            return;
+        }
+    };
+
+    for key in document.keys() {
+        if !is_mapped_field(key, false) && !EXTRA_DOCUMENT_FIELDS.contains(&key.as_str()) {
+            validation.warning(
+                "",
+                find_line(content, 1, &[key]),
+                format!("frpc 不认识的键 {}", key),
+            );
+        }
+    }
+
+    match document.get("serverAddr").and_then(Value::as_str) {
+        Some(addr) if !addr.trim().is_empty() => {}
+        Some(_) => validation.error(
+            "",
+            find_line(content, 1, &["serverAddr"]),
+            "serverAddr 不能为空",
+        ),
+        None => validation.error("", 0, "缺少 serverAddr"),
+    }
+    match document_field(content, 1, &document, "serverPort") {
+        Some(field) => check_port("", &field, validation),
+        None => validation.warning("", 0, "未设置 serverPort，frpc 将使用默认端口 7000"),
+    }
+
+    let entries = entries(&document);
+    if entries.is_empty() {
+        validation.error("", 0, "配置文件必须包含至少一个带 name 的代理");
+    }
+
+    let mut names = HashSet::new();
+    // 同名的代理从上一个代理之后开始查找
+    let mut from = 1;
+    for (kind, entry) in entries {
+        let name = match entry.get("name").and_then(Value::as_str) {
+            Some(name) if !name.trim().is_empty() => name,
+            _ => {
+                validation.error("", 0, format!("{} 中有一项缺少 name", kind));
+                continue;
+            }
+        };
+        let line = find_line(content, from, &["name", name]);
+        from = line.max(from - 1) + 1;
+
+        if !names.insert(name) {
+            validation.error(name, line, format!("代理名 {} 重复", name));
+        }
+        for key in entry.keys() {
+            if !is_mapped_field(key, true) && !EXTRA_ENTRY_FIELDS.contains(&key.as_str()) {
+                validation.warning(
+                    name,
+                    find_line(content, line, &[key]),
+                    format!("frpc 不认识的键 {}", key),
+                );
+            }
+        }
+
+        let field = |key: &str| document_field(content, line, entry, key);
+        let has = |key: &str| match entry.get(key) {
+            Some(Value::String(text)) => !text.trim().is_empty(),
+            Some(Value::Array(items)) => !items.is_empty(),
+            Some(Value::Null) | None => false,
+            Some(_) => true,
+        };
+
+        check_proxy(
+            &ProxyFields {
+                name: name.to_string(),
+                line,
+                ini: false,
+                visitor: kind == "visitors",
+                proxy_type: field("type"),
+                local_port: field("localPort"),
+                remote_port: field("remotePort"),
+                bind_port: field("bindPort"),
+                has_domains: has("customDomains") || has("subdomain"),
+                has_plugin: has("plugin"),
+                has_secret: has("secretKey"),
+                has_server_name: has("serverName"),
+            },
+            validation,
+        );
+    }
+}
+
+fn document_field(
+    content: &str,
+    from: usize,
+    object: &Map<String, Value>,
+    key: &str,
+) -> Option<Field> {
+    let value = match object.get(key)? {
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    };
+    Some(Field {
+        key: key.to_string(),
+        value,
+        line: find_line(content, from, &[key]),
+    })
+}
+
+// 从 from 行开始查找同时包含所有片段的第一行，找不到时为 0
+fn find_line(content: &str, from: usize, needles: &[&str]) -> usize {
+    content
+        .lines()
+        .enumerate()
+        .skip(from.saturating_sub(1))
+        .find(|(_, line)| needles.iter().all(|needle| line.contains(needle)))
+        .map(|(index, _)| index + 1)
+        .unwrap_or(0)
+}
+
+// 按代理类型检查字段
+fn check_proxy(proxy: &ProxyFields, validation: &mut ConfigValidation) {
+    let name = proxy.name.as_str();
+
+    let proxy_type = match &proxy.proxy_type {
+        Some(field) => {
+            let proxy_type = field.value.trim().to_ascii_lowercase();
+            if !PROXY_TYPES.contains(&proxy_type.as_str()) {
+                validation.error(
+                    name,
+                    field.line,
+                    format!(
+                        "未知的代理类型 {}，可选 {}",
+                        field.value,
+                        PROXY_TYPES.join("、")
+                    ),
+                );
+                return;
+            }
+            proxy_type
+        }
+        // 旧版 frpc 读取 INI 时未设置 type 按 tcp 处理
+        None if proxy.ini => {
+            validation.warning(name, proxy.line, "未设置 type，frpc 将按 tcp 处理");
+            "tcp".to_string()
+        }
+        None => {
+            validation.error(name, proxy.line, "缺少 type");
+            return;
+        }
+    };
+    let is_secret = SECRET_TYPES.contains(&proxy_type.as_str());
+
+    for field in [&proxy.local_port, &proxy.bind_port].into_iter().flatten() {
+        check_port(name, field, validation);
+    }
+    if let Some(field) = &proxy.remote_port {
+        check_remote_port(name, field, validation);
+    }
+
+    if proxy.visitor {
+        if !is_secret {
+            validation.error(name, proxy.line, format!("{} 类型不支持访问者", proxy_type));
+        }
+        if !proxy.has_server_name {
+            validation.error(
+                name,
+                proxy.line,
+                format!("访问者缺少 {}", proxy.key("server_name", "serverName")),
+            );
+        }
+        if proxy.bind_port.is_none() {
+            validation.error(
+                name,
+                proxy.line,
+                format!("访问者缺少 {}", proxy.key("bind_port", "bindPort")),
+            );
+        }
+        if !proxy.has_secret {
+            validation.warning(
+                name,
+                proxy.line,
+                format!("未设置 {}", proxy.key("sk", "secretKey")),
+            );
+        }
+        return;
+    }
+
+    if proxy.local_port.is_none() && !proxy.has_plugin {
+        validation.error(
+            name,
+            proxy.line,
+            format!(
+                "缺少 {}，未使用插件时必须设置本地端口",
+                proxy.key("local_port", "localPort")
+            ),
+        );
+    }
+
+    if DOMAIN_TYPES.contains(&proxy_type.as_str()) {
+        if let Some(field) = &proxy.remote_port {
+            validation.error(
+                name,
+                field.line,
+                format!("{} 代理不支持 {}", proxy_type, field.key),
+            );
+        }
+        if !proxy.has_domains {
+            validation.error(
+                name,
+                proxy.line,
+                format!(
+                    "{} 代理需要设置 {} 或 subdomain",
+                    proxy_type,
+                    proxy.key("custom_domains", "customDomains")
+                ),
+            );
+        }
+    } else if is_secret {
+        if let Some(field) = &proxy.remote_port {
+            validation.warning(
+                name,
+                field.line,
+                format!("{} 代理不使用 {}", proxy_type, field.key),
+            );
+        }
+        if !proxy.has_secret {
+            validation.warning(
+                name,
+                proxy.line,
+                format!(
+                    "未设置 {}，知道代理名的访问者都可以连接",
+                    proxy.key("sk", "secretKey")
+                ),
+            );
+        }
+    } else if proxy.remote_port.is_none() {
+        validation.warning(
+            name,
+            proxy.line,
+            format!(
+                "未设置 {}，服务端将随机分配端口",
+                proxy.key("remote_port", "remotePort")
+            ),
+        );
+    }
+}
+
+fn check_port(section: &str, field: &Field, validation: &mut ConfigValidation) {
+    if !port_in(field, 1) {
+        validation.error(
+            section,
+            field.line,
+            format!("{} 的值 {} 不是有效端口（1-65535）", field.key, field.value),
+        );
+    }
+}
+
+// 远程端口为 0 时由服务端随机分配
+fn check_remote_port(section: &str, field: &Field, validation: &mut ConfigValidation) {
+    if !port_in(field, 0) {
+        validation.error(
+            section,
+            field.line,
+            format!(
+                "{} 的值 {} 不是有效端口（0-65535，0 表示随机分配）",
+                field.key, field.value
+            ),
+        );
+    }
+}
+
+fn port_in(field: &Field, min: i64) -> bool {
+    field
+        .value
+        .trim()
+        .parse::<i64>()
+        .is_ok_and(|port| (min..=65535).contains(&port))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages(diagnostics: &[ConfigDiagnostic]) -> Vec<(String, usize, String)> {
+        diagnostics
+            .iter()
+            .map(|d| (d.section.clone(), d.line, d.message.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn valid_ini_has_no_diagnostics() {
+        let content = "[common]\nserver_addr = frp.example.com\nserver_port = 7000\n\n[mc]\ntype = tcp\nlocal_port = 25565\nremote_port = 25565\n";
+        let validation = validate_config(content, ConfigFormat::Ini);
+        assert!(validation.valid, "{:?}", validation.errors);
+        assert!(validation.warnings.is_empty(), "{:?}", validation.warnings);
+    }
+
+    #[test]
+    fn remote_port_zero_is_allowed() {
+        let content = "[common]\nserver_addr = frp.example.com\nserver_port = 7000\n\n[mc]\ntype = tcp\nlocal_port = 25565\nremote_port = 0\n";
+        let validation = validate_config(content, ConfigFormat::Ini);
+        assert!(validation.valid, "{:?}", validation.errors);
+    }
+
+    #[test]
+    fn local_port_zero_is_rejected() {
+        let content = "[common]\nserver_addr = frp.example.com\nserver_port = 7000\n\n[mc]\ntype = tcp\nlocal_port = 0\nremote_port = 25565\n";
+        let validation = validate_config(content, ConfigFormat::Ini);
+        assert_eq!(
+            messages(&validation.errors),
+            vec![(
+                "mc".to_string(),
+                7,
+                "local_port 的值 0 不是有效端口（1-65535）".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn ini_reports_missing_common_and_duplicate_proxy() {
+        let content = "[web]\ntype = tcp\nlocal_port = 80\nremote_port = 8080\n[web]\ntype = tcp\nlocal_port = 80\nremote_port = 8081\n";
+        let validation = validate_config(content, ConfigFormat::Ini);
+        let errors = messages(&validation.errors);
+        assert!(errors.contains(&("common".to_string(), 0, "缺少 [common] 段".to_string())));
+        assert!(errors.contains(&("web".to_string(), 5, "代理名 web 重复".to_string())));
+    }
+
+    #[test]
+    fn toml_diagnostics_point_at_lines() {
+        let content = r#"serverAddr = ""
+serverPort = 70000
+
+[[proxies]]
+name = "web"
+type = "http"
+localPort = 80
+remotePort = 8080
+"#;
+        let validation = validate_config(content, ConfigFormat::Toml);
+        assert_eq!(
+            messages(&validation.errors),
+            vec![
+                ("".to_string(), 1, "serverAddr 不能为空".to_string()),
+                (
+                    "".to_string(),
+                    2,
+                    "serverPort 的值 70000 不是有效端口（1-65535）".to_string()
+                ),
+                (
+                    "web".to_string(),
+                    8,
+                    "http 代理不支持 remotePort".to_string()
+                ),
+                (
+                    "web".to_string(),
+                    5,
+                    "http 代理需要设置 customDomains 或 subdomain".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn yaml_duplicate_names_point_at_each_proxy() {
+        let content = "serverAddr: frp.example.com\nserverPort: 7000\nproxies:\n  - name: mc\n    type: tcp\n    localPort: 25565\n    remotePort: 25565\n  - name: mc\n    type: tcp\n    localPort: 25566\n    remotePort: 0\n";
+        let validation = validate_config(content, ConfigFormat::Yaml);
+        assert_eq!(
+            messages(&validation.errors),
+            vec![("mc".to_string(), 8, "代理名 mc 重复".to_string())]
+        );
+    }
+
+    #[test]
+    fn json_reports_unknown_type_and_unknown_key() {
+        let content = r#"{
+  "serverAddr": "frp.example.com",
+  "serverPort": 7000,
+  "proxies": [
+    {
+      "name": "ssh",
+      "type": "ftp",
+      "localPort": 22,
+      "colour": "blue"
+    }
+  ]
+}
+"#;
+        let validation = validate_config(content, ConfigFormat::Json);
+        assert_eq!(
+            messages(&validation.errors),
+            vec![(
+                "ssh".to_string(),
+                7,
+                format!("未知的代理类型 ftp，可选 {}", PROXY_TYPES.join("、"))
+            )]
+        );
+        assert_eq!(
+            messages(&validation.warnings),
+            vec![("ssh".to_string(), 9, "frpc 不认识的键 colour".to_string())]
+        );
+    }
+
+    #[test]
+    fn unparsable_document_is_an_error() {
+        let validation = validate_config("proxies = [", ConfigFormat::Toml);
+        assert!(!validation.valid);
+        assert_eq!(validation.errors[0].line, 0);
+    }
+
+    #[test]
+    fn find_line_starts_from_given_line() {
+        let content = "id = x\nid = y\nid = x\n";
+        assert_eq!(find_line(content, 1, &["id", "x"]), 1);
+        assert_eq!(find_line(content, 2, &["id", "x"]), 3);
+        assert_eq!(find_line(content, 1, &["missing"]), 0);
+    }
+
+    #[test]
+    fn error_summary_includes_locations() {
+        let validation = ConfigValidation {
+            format: ConfigFormat::Ini,
+            valid: false,
+            errors: vec![
+                diagnostic("", 0, "a"),
+                diagnostic("mc", 0, "b"),
+                diagnostic("", 3, "c"),
+                diagnostic("mc", 4, "d"),
+            ],
+            warnings: Vec::new(),
+        };
+        assert_eq!(
+            validation.error_summary(),
+            "a; [mc] b; 第 3 行: c; 第 4 行 [mc] d"
+        );
+    }
+}
@@ -0,0 +1,537 @@
+// 本地控制接口
+//
+// 开启后在本机地址或 Unix 套接字上提供 HTTP 接口，供脚本和命令行列出、启动、
+// 停止隧道以及查看状态和日志。接口直接调用前端使用的命令，行为与界面操作一致。
+// 每个请求都要带上保存在应用数据目录 control_api_token 中的令牌：
+// Authorization: Bearer <令牌>。查询参数会出现在命令历史和代理日志中，
+// 只有日志推送接口为了兼容无法设置请求头的 EventSource 才接受 ?token=<令牌>。
+//
+//   GET  /v1/tunnels                    所有隧道及状态
+//   GET  /v1/tunnels/<标识>             单个隧道的状态
+//   POST /v1/tunnels/<标识>/start       启动隧道
+//   POST /v1/tunnels/<标识>/stop        停止隧道，可选 {"grace_period_secs": 秒}
+//   GET  /v1/tunnels/<标识>/logs        缓冲中的日志，可选 ?since=<序号>&limit=<条数>
+//   GET  /v1/tunnels/<标识>/logs/stream 以 Server-Sent Events 持续推送日志
+//
+// 隧道标识与前端一致，为 api_<ID> 或 custom_<名称>。
+
+use crate::app_context::AppContext;
+use crate::commands::custom_tunnel::CustomTunnel;
+use crate::control_http::{self, ListenAddr, Listener, Request, Stream};
+use crate::models::{
+    FrpcProcesses, TunnelKey, TunnelLogBuffers, TunnelStateInfo, TunnelStates, LOG_BUFFER_CAPACITY,
+};
+use crate::settings::{ControlApiSettings, SettingsState};
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::OsRng;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// 控制接口令牌文件
+pub const TOKEN_FILE: &str = "control_api_token";
+
+/// 日志推送检查新日志的间隔
+const STREAM_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// 没有新日志时发送保活注释的间隔
+const STREAM_KEEP_ALIVE: Duration = Duration::from_secs(15);
+
+/// 控制接口的运行状态
+#[derive(Serialize, Clone, Debug)]
+pub struct ControlApiStatus {
+    pub enabled: bool,
+    pub listen: String,
+    pub running: bool,
+    /// 最近一次启动失败的原因
+    pub error: Option<String>,
+}
+
+/// 列表和状态接口返回的隧道信息
+#[derive(Serialize)]
+struct TunnelStatus {
+    tunnel_key: TunnelKey,
+    /// 自定义隧道的名称，API 隧道的名称只能从 ChmlFrp API 获取，这里为空
+    name: Option<String>,
+    running: bool,
+    state: Option<TunnelStateInfo>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct StopRequest {
+    grace_period_secs: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct CommandResult {
+    message: String,
+}
+
+struct RunningServer {
+    addr: ListenAddr,
+    stopping: Arc<AtomicBool>,
+    thread: JoinHandle<()>,
+}
+
+/// 控制接口服务，作为 Tauri 状态管理
+#[derive(Default)]
+pub struct ControlApiServer {
+    server: Mutex<Option<RunningServer>>,
+    error: Mutex<Option<String>>,
+    token: Arc<RwLock<String>>,
+}
+
+impl ControlApiServer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn status(&self, settings: &ControlApiSettings) -> ControlApiStatus {
+        let server = lock(&self.server);
+        ControlApiStatus {
+            enabled: settings.enabled,
+            listen: server
+                .as_ref()
+                .map(|server| server.addr.to_string())
+                .unwrap_or_else(|| settings.listen.clone()),
+            running: server.is_some(),
+            error: lock(&self.error).clone(),
+        }
+    }
+
+    /// 按设置启动、停止或重启服务，监听地址没有变化时保持运行
+    pub fn apply(
+        &self,
+        app_handle: &AppContext,
+        settings: &ControlApiSettings,
+    ) -> Result<(), String> {
+        let result = self.apply_inner(app_handle, settings);
+        *lock(&self.error) = result.as_ref().err().cloned();
+        result
+    }
+
+    fn apply_inner(
+        &self,
+        app_handle: &AppContext,
+        settings: &ControlApiSettings,
+    ) -> Result<(), String> {
+        let mut server = lock(&self.server);
+        if !settings.enabled {
+            if let Some(running) = server.take() {
+                stop_server(running);
+            }
+            return Ok(());
+        }
+
+        let addr = ListenAddr::parse(&settings.listen)?;
+        if server.as_ref().is_some_and(|running| running.addr == addr) {
+            return Ok(());
+        }
+        if let Some(running) = server.take() {
+            stop_server(running);
+        }
+
+        *self
+            .token
+            .write()
+            .map_err(|e| format!("获取令牌锁失败: {}", e))? = load_or_create_token(app_handle)?;
+        let listener = Listener::bind(&addr)?;
+        let stopping = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let app_handle = app_handle.clone();
+            let stopping = stopping.clone();
+            let token = self.token.clone();
+            std::thread::spawn(move || accept_loop(app_handle, listener, token, stopping))
+        };
+        eprintln!("[控制接口] 已在 {} 上监听", addr);
+        *server = Some(RunningServer {
+            addr,
+            stopping,
+            thread,
+        });
+        Ok(())
+    }
+
+    /// 生成新令牌，旧令牌立即失效
+    pub fn regenerate_token(&self, app_handle: &AppContext) -> Result<String, String> {
+        let token = generate_token();
+        write_token(&app_handle.app_data_dir()?.join(TOKEN_FILE), &token)?;
+        *self
+            .token
+            .write()
+            .map_err(|e| format!("获取令牌锁失败: {}", e))? = token.clone();
+        Ok(token)
+    }
+
+    /// 停止服务，退出应用时调用
+    pub fn shutdown(&self) {
+        if let Some(running) = lock(&self.server).take() {
+            stop_server(running);
+        }
+    }
+}
+
+/// 按当前设置启动或停止控制接口，启动时和设置修改后调用
+pub fn apply_settings(app_handle: &AppContext) {
+    let settings = app_handle.state::<SettingsState>().get().control_api;
+    if let Err(e) = app_handle
+        .state::<ControlApiServer>()
+        .apply(app_handle, &settings)
+    {
+        eprintln!("[控制接口] 启动失败: {}", e);
+    }
+}
+
+/// 守护模式下命令行通过控制接口管理隧道，不论设置中是否开启都按设置的地址启动
+pub fn start_for_daemon(app_handle: &AppContext) -> Result<(), String> {
+    let settings = ControlApiSettings {
+        enabled: true,
+        ..app_handle.state::<SettingsState>().get().control_api
+    };
+    app_handle
+        .state::<ControlApiServer>()
+        .apply(app_handle, &settings)
+}
+
+/// 读取令牌，没有时生成一个
+pub fn load_or_create_token(app_handle: &AppContext) -> Result<String, String> {
+    let path = app_handle.app_data_dir()?.join(TOKEN_FILE);
+    if let Ok(token) = fs::read_to_string(&path) {
+        let token = token.trim();
+        if !token.is_empty() {
+            return Ok(token.to_string());
+        }
+    }
+    let token = generate_token();
+    write_token(&path, &token)?;
+    Ok(token)
+}
+
+fn generate_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+// 令牌文件只有当前用户可以读写
+fn write_token(path: &Path, token: &str) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("创建目录失败: {}", e))?;
+    }
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options
+        .open(path)
+        .and_then(|mut file| file.write_all(token.as_bytes()))
+        .map_err(|e| format!("保存控制接口令牌失败: {}", e))
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    match mutex.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+// 设置停止标记后连接一次监听地址，唤醒等待连接的线程
+fn stop_server(running: RunningServer) {
+    running.stopping.store(true, Ordering::SeqCst);
+    let _ = control_http::connect(&running.addr);
+    let _ = running.thread.join();
+    eprintln!("[控制接口] 已停止监听 {}", running.addr);
+}
+
+fn accept_loop(
+    app_handle: AppContext,
+    listener: Listener,
+    token: Arc<RwLock<String>>,
+    stopping: Arc<AtomicBool>,
+) {
+    loop {
+        let accepted = listener.accept();
+        if stopping.load(Ordering::SeqCst) {
+            break;
+        }
+        match accepted {
+            Ok(stream) => {
+                let app_handle = app_handle.clone();
+                let token = token.clone();
+                let stopping = stopping.clone();
+                std::thread::spawn(move || {
+                    handle_connection(&app_handle, stream, &token, &stopping)
+                });
+            }
+            Err(e) => {
+                eprintln!("[控制接口] 接受连接失败: {}", e);
+                std::thread::sleep(Duration::from_millis(100));
+            }
+        }
+    }
+}
+
+fn handle_connection(
+    app_handle: &AppContext,
+    mut stream: Box<dyn Stream>,
+    token: &RwLock<String>,
+    stopping: &AtomicBool,
+) {
+    let request = match control_http::read_request(stream.as_mut()) {
+        Ok(request) => request,
+        Err(e) => {
+            let _ = control_http::write_error(stream.as_mut(), 400, &e);
+            return;
+        }
+    };
+
+    let authorized = {
+        let expected = match token.read() {
+            Ok(expected) => expected,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        let query_token = if request.method == "GET" && request.path.ends_with("/logs/stream") {
+            request.query.get("token").map(String::as_str)
+        } else {
+            None
+        };
+        let provided = request
+            .header("authorization")
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .or(query_token)
+            .unwrap_or_default();
+        control_http::token_matches(&expected, provided.trim())
+    };
+    if !authorized {
+        let _ = control_http::write_error(stream.as_mut(), 401, "令牌无效");
+        return;
+    }
+
+    if let Err(e) = route(app_handle, &request, stream.as_mut(), stopping) {
+        eprintln!("[控制接口] 写入响应失败: {}", e);
+    }
+}
+
+fn route(
+    app_handle: &AppContext,
+    request: &Request,
+    stream: &mut dyn Stream,
+    stopping: &AtomicBool,
+) -> std::io::Result<()> {
+    let segments = request.path_segments();
+    let segments: Vec<&str> = segments.iter().map(String::as_str).collect();
+    let (tunnel_key, action) = match segments.as_slice() {
+        ["v1", "tunnels"] => {
+            return match request.method.as_str() {
+                "GET" => respond(stream, list_tunnels(app_handle)),
+                _ => control_http::write_error(stream, 405, "不支持的请求方法"),
+            };
+        }
+        ["v1", "tunnels", key, action @ ..] => match TunnelKey::try_from(key.to_string()) {
+            Ok(tunnel_key) => (tunnel_key, action.join("/")),
+            Err(e) => return control_http::write_error(stream, 400, &e),
+        },
+        _ => return control_http::write_error(stream, 404, "接口不存在"),
+    };
+
+    match (request.method.as_str(), action.as_str()) {
+        ("GET", "") => respond(stream, tunnel_status(app_handle, &tunnel_key)),
+        ("POST", "start") => respond_command(stream, start_tunnel(app_handle, &tunnel_key)),
+        ("POST", "stop") => {
+            let grace_period_secs = if request.body.is_empty() {
+                request
+                    .query
+                    .get("grace_period_secs")
+                    .and_then(|secs| secs.parse().ok())
+            } else {
+                match serde_json::from_slice::<StopRequest>(&request.body) {
+                    Ok(body) => body.grace_period_secs,
+                    Err(e) => {
+                        return control_http::write_error(
+                            stream,
+                            400,
+                            &format!("请求体格式不正确: {}", e),
+                        )
+                    }
+                }
+            };
+            respond_command(
+                stream,
+                stop_tunnel(app_handle, &tunnel_key, grace_period_secs),
+            )
+        }
+        ("GET", "logs") => {
+            let since = query_number(request, "since").unwrap_or(0);
+            let limit = query_number(request, "limit").map(|limit| limit as usize);
+            let batch = crate::commands::process::log_batch(
+                app_handle.state::<TunnelLogBuffers>(),
+                &tunnel_key,
+                Some(since),
+                limit,
+            );
+            respond(stream, Ok(batch))
+        }
+        ("GET", "logs/stream") => stream_logs(app_handle, request, &tunnel_key, stream, stopping),
+        (_, "" | "start" | "stop" | "logs" | "logs/stream") => {
+            control_http::write_error(stream, 405, "不支持的请求方法")
+        }
+        _ => control_http::write_error(stream, 404, "接口不存在"),
+    }
+}
+
+fn query_number(request: &Request, name: &str) -> Option<u64> {
+    request.query.get(name).and_then(|value| value.parse().ok())
+}
+
+fn respond<T: Serialize>(
+    stream: &mut dyn Stream,
+    result: Result<T, String>,
+) -> std::io::Result<()> {
+    match result {
+        Ok(value) => control_http::write_json(stream, 200, &value),
+        Err(e) => control_http::write_error(stream, 500, &e),
+    }
+}
+
+// 启动和停止失败多为隧道已在运行或未在运行，返回 409
+fn respond_command(stream: &mut dyn Stream, result: Result<String, String>) -> std::io::Result<()> {
+    match result {
+        Ok(message) => control_http::write_json(stream, 200, &CommandResult { message }),
+        Err(e) => control_http::write_error(stream, 409, &e),
+    }
+}
+
+fn list_tunnels(app_handle: &AppContext) -> Result<Vec<TunnelStatus>, String> {
+    let custom_tunnels = custom_tunnels(app_handle)?;
+    let running = running_tunnel_keys(app_handle)?;
+    let states = tunnel_states(app_handle)?;
+
+    // 自定义隧道来自本地列表，API 隧道只包含运行过的
+    let mut keys: Vec<TunnelKey> = custom_tunnels
+        .iter()
+        .map(|tunnel| TunnelKey::Custom(tunnel.id.clone()))
+        .chain(running.iter().cloned())
+        .chain(states.iter().map(|state| state.tunnel_key.clone()))
+        .collect();
+    keys.sort();
+    keys.dedup();
+
+    Ok(keys
+        .into_iter()
+        .map(|tunnel_key| TunnelStatus {
+            name: custom_tunnel_name(&custom_tunnels, &tunnel_key),
+            running: running.contains(&tunnel_key),
+            state: states
+                .iter()
+                .find(|state| state.tunnel_key == tunnel_key)
+                .cloned(),
+            tunnel_key,
+        })
+        .collect())
+}
+
+fn tunnel_status(app_handle: &AppContext, tunnel_key: &TunnelKey) -> Result<TunnelStatus, String> {
+    let name = match tunnel_key {
+        TunnelKey::Custom(_) => {
+            let name = custom_tunnel_name(&custom_tunnels(app_handle)?, tunnel_key);
+            if name.is_none() {
+                return Err(format!("自定义隧道不存在: {}", tunnel_key));
+            }
+            name
+        }
+        TunnelKey::Api(_) => None,
+    };
+    Ok(TunnelStatus {
+        name,
+        running: running_tunnel_keys(app_handle)?.contains(tunnel_key),
+        state: tunnel_states(app_handle)?
+            .into_iter()
+            .find(|state| &state.tunnel_key == tunnel_key),
+        tunnel_key: tunnel_key.clone(),
+    })
+}
+
+/// 启动隧道，与界面上的启动按钮调用相同的命令
+pub fn start_tunnel(app_handle: &AppContext, tunnel_key: &TunnelKey) -> Result<String, String> {
+    tauri::async_runtime::block_on(crate::commands::process::start_tunnel(
+        app_handle, tunnel_key,
+    ))
+}
+
+fn stop_tunnel(
+    app_handle: &AppContext,
+    tunnel_key: &TunnelKey,
+    grace_period_secs: Option<u64>,
+) -> Result<String, String> {
+    tauri::async_runtime::block_on(crate::commands::process::stop_tunnel(
+        app_handle,
+        tunnel_key,
+        grace_period_secs,
+    ))
+}
+
+fn custom_tunnels(app_handle: &AppContext) -> Result<Vec<CustomTunnel>, String> {
+    crate::commands::custom_tunnel::load_custom_tunnels(app_handle)
+}
+
+fn custom_tunnel_name(custom_tunnels: &[CustomTunnel], tunnel_key: &TunnelKey) -> Option<String> {
+    match tunnel_key {
+        TunnelKey::Custom(tunnel_id) => custom_tunnels
+            .iter()
+            .find(|tunnel| &tunnel.id == tunnel_id)
+            .map(|tunnel| tunnel.name.clone()),
+        TunnelKey::Api(_) => None,
+    }
+}
+
+fn running_tunnel_keys(app_handle: &AppContext) -> Result<Vec<TunnelKey>, String> {
+    crate::commands::process::running_tunnel_keys(app_handle.state::<FrpcProcesses>())
+}
+
+fn tunnel_states(app_handle: &AppContext) -> Result<Vec<TunnelStateInfo>, String> {
+    crate::commands::tunnel_state::list_tunnel_states(app_handle.state::<TunnelStates>())
+}
+
+// 先补发缓冲中 since 或 Last-Event-ID 之后的日志，再定时检查新日志，
+// 直到客户端断开或服务停止
+fn stream_logs(
+    app_handle: &AppContext,
+    request: &Request,
+    tunnel_key: &TunnelKey,
+    stream: &mut dyn Stream,
+    stopping: &AtomicBool,
+) -> std::io::Result<()> {
+    let mut last_seq = request
+        .header("last-event-id")
+        .and_then(|id| id.parse().ok())
+        .or_else(|| query_number(request, "since"))
+        .unwrap_or(0);
+    let log_buffers = app_handle.state::<TunnelLogBuffers>();
+
+    control_http::start_event_stream(stream)?;
+    let mut last_write = Instant::now();
+    while !stopping.load(Ordering::SeqCst) {
+        // 序号比缓冲中最新的还大时 since 从头返回，启动器重启后同样可以续传
+        let batch = log_buffers.since(tunnel_key, last_seq, LOG_BUFFER_CAPACITY);
+        for entry in &batch.entries {
+            control_http::write_event(stream, entry.seq, entry)?;
+            last_seq = entry.seq;
+            last_write = Instant::now();
+        }
+        if last_write.elapsed() >= STREAM_KEEP_ALIVE {
+            control_http::write_keep_alive(stream)?;
+            last_write = Instant::now();
+        }
+        std::thread::sleep(STREAM_POLL_INTERVAL);
+    }
+    Ok(())
+}
@@ -0,0 +1,486 @@
+// 本地控制接口使用的 HTTP/1.1 实现
+//
+// 控制接口只监听本机地址或 Unix 套接字，请求量很小，这里不引入 HTTP 框架，
+// 每个连接只处理一个请求，响应后关闭连接。TCP 和 Unix 套接字统一为 Stream，
+// 日志推送使用 Server-Sent Events，在同一个连接上持续写入。
+
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// 请求头的总长度上限
+const MAX_HEADER_BYTES: usize = 16 * 1024;
+
+/// 请求体的长度上限
+const MAX_BODY_BYTES: usize = 1024 * 1024;
+
+/// 读取请求的超时时间
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 可读写的连接
+pub trait Stream: Read + Write + Send {}
+
+impl<T: Read + Write + Send> Stream for T {}
+
+/// 监听地址，TCP 只允许本机地址
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ListenAddr {
+    Tcp(SocketAddr),
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+impl ListenAddr {
+    /// 解析 127.0.0.1:7789、[::1]:7789 或 unix:/path/to/socket
+    pub fn parse(listen: &str) -> Result<Self, String> {
+        let listen = listen.trim();
+        if let Some(path) = listen.strip_prefix("unix:") {
+            #[cfg(unix)]
+            {
+                if path.is_empty() {
+                    return Err("Unix 套接字路径不能为空".to_string());
+                }
+                return Ok(Self::Unix(PathBuf::from(path)));
+            }
+            #[cfg(not(unix))]
+            {
+                let _ = path;
+                return Err("当前系统不支持 Unix 套接字".to_string());
+            }
+        }
+
+        let addr: SocketAddr = listen
+            .parse()
+            .map_err(|_| format!("监听地址格式不正确: {}", listen))?;
+        if !addr.ip().is_loopback() {
+            return Err("控制接口只能监听本机地址 127.0.0.1 或 ::1".to_string());
+        }
+        Ok(Self::Tcp(addr))
+    }
+}
+
+impl std::fmt::Display for ListenAddr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Tcp(addr) => write!(f, "{}", addr),
+            #[cfg(unix)]
+            Self::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+pub enum Listener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(std::os::unix::net::UnixListener, PathBuf),
+}
+
+impl Listener {
+    pub fn bind(addr: &ListenAddr) -> Result<Self, String> {
+        match addr {
+            ListenAddr::Tcp(addr) => TcpListener::bind(addr)
+                .map(Self::Tcp)
+                .map_err(|e| format!("监听 {} 失败: {}", addr, e)),
+            #[cfg(unix)]
+            ListenAddr::Unix(path) => {
+                remove_stale_socket(path)?;
+                let listener = bind_private_socket(path)?;
+                Ok(Self::Unix(listener, path.clone()))
+            }
+        }
+    }
+
+    /// 等待下一个连接
+    pub fn accept(&self) -> io::Result<Box<dyn Stream>> {
+        match self {
+            Self::Tcp(listener) => {
+                let (stream, _) = listener.accept()?;
+                stream.set_read_timeout(Some(READ_TIMEOUT))?;
+                Ok(Box::new(stream))
+            }
+            #[cfg(unix)]
+            Self::Unix(listener, _) => {
+                let (stream, _) = listener.accept()?;
+                stream.set_read_timeout(Some(READ_TIMEOUT))?;
+                Ok(Box::new(stream))
+            }
+        }
+    }
+}
+
+// 上次运行留下的套接字文件会导致绑定失败。只删除没有程序监听的套接字，
+// 路径上是普通文件或其他类型时报错，避免地址写错时删掉用户的文件
+#[cfg(unix)]
+fn remove_stale_socket(path: &Path) -> Result<(), String> {
+    use std::os::unix::fs::FileTypeExt;
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(format!("读取 {} 失败: {}", path.display(), e)),
+    };
+    if !metadata.file_type().is_socket() {
+        return Err(format!("{} 已存在且不是套接字文件", path.display()));
+    }
+    if connect(&ListenAddr::Unix(path.to_path_buf())).is_ok() {
+        return Err(format!("{} 已有其他程序在监听", path.display()));
+    }
+    fs::remove_file(path).map_err(|e| format!("删除旧的套接字文件 {} 失败: {}", path.display(), e))
+}
+
+// 套接字先在只有当前用户可以访问的临时目录中创建并设置为 0600，再链接到监听路径。
+// 直接在监听路径上绑定时，设置权限之前其他用户可以按 umask 的权限连接
+#[cfg(unix)]
+fn bind_private_socket(path: &Path) -> Result<std::os::unix::net::UnixListener, String> {
+    use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+    let parent = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let temp_dir = parent.join(format!(".{}.tmp", std::process::id()));
+    fs::DirBuilder::new()
+        .mode(0o700)
+        .create(&temp_dir)
+        .map_err(|e| format!("创建临时目录 {} 失败: {}", temp_dir.display(), e))?;
+
+    let temp_path = temp_dir.join("socket");
+    let result = std::os::unix::net::UnixListener::bind(&temp_path)
+        .map_err(|e| format!("监听 {} 失败: {}", path.display(), e))
+        .and_then(|listener| {
+            fs::set_permissions(&temp_path, fs::Permissions::from_mode(0o600))
+                .map_err(|e| format!("设置套接字文件权限失败: {}", e))?;
+            // 硬链接不会覆盖已存在的文件，其他程序抢先占用地址时绑定失败
+            fs::hard_link(&temp_path, path)
+                .map_err(|e| format!("监听 {} 失败: {}", path.display(), e))?;
+            Ok(listener)
+        });
+    let _ = fs::remove_dir_all(&temp_dir);
+    result
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let Self::Unix(_, path) = self {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+/// 连接到监听地址，用于唤醒等待连接的线程，以及命令行客户端
+pub fn connect(addr: &ListenAddr) -> io::Result<Box<dyn Stream>> {
+    match addr {
+        ListenAddr::Tcp(addr) => {
+            let stream = TcpStream::connect_timeout(addr, Duration::from_secs(5))?;
+            Ok(Box::new(stream))
+        }
+        #[cfg(unix)]
+        ListenAddr::Unix(path) => Ok(Box::new(std::os::unix::net::UnixStream::connect(path)?)),
+    }
+}
+
+pub struct Request {
+    pub method: String,
+    /// 未解码的路径，不含查询参数
+    pub path: String,
+    pub query: HashMap<String, String>,
+    /// 键为小写
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).map(String::as_str)
+    }
+
+    /// 按 / 分段并解码，隧道名称中编码的 / 不会被当作分隔符
+    pub fn path_segments(&self) -> Vec<String> {
+        self.path
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .map(percent_decode)
+            .collect()
+    }
+}
+
+/// 读取一个请求
+pub fn read_request(stream: &mut dyn Stream) -> Result<Request, String> {
+    let mut reader = BufReader::new(stream);
+    let mut header_bytes = 0;
+    let mut read_line = |reader: &mut BufReader<&mut dyn Stream>| -> Result<String, String> {
+        let mut line = String::new();
+        let read = reader
+            .by_ref()
+            .take((MAX_HEADER_BYTES - header_bytes) as u64)
+            .read_line(&mut line)
+            .map_err(|e| format!("读取请求失败: {}", e))?;
+        header_bytes += read;
+        if !line.ends_with('\n') {
+            return Err("请求头过长或不完整".to_string());
+        }
+        Ok(line.trim_end_matches(['\r', '\n']).to_string())
+    };
+
+    let request_line = read_line(&mut reader)?;
+    let mut parts = request_line.split(' ');
+    let (method, target) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(method), Some(target), Some(version)) if version.starts_with("HTTP/1.") => {
+            (method.to_string(), target.to_string())
+        }
+        _ => return Err("请求格式不正确".to_string()),
+    };
+
+    let mut headers = HashMap::new();
+    loop {
+        let line = read_line(&mut reader)?;
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.insert(name.trim().to_ascii_lowercase(), value.trim().to_string());
+        }
+    }
+
+    let length: usize = match headers.get("content-length") {
+        Some(length) => length
+            .parse()
+            .map_err(|_| "Content-Length 格式不正确".to_string())?,
+        None => 0,
+    };
+    if length > MAX_BODY_BYTES {
+        return Err("请求体过大".to_string());
+    }
+    let mut body = vec![0; length];
+    reader
+        .read_exact(&mut body)
+        .map_err(|e| format!("读取请求失败: {}", e))?;
+
+    let (path, query) = target.split_once('?').unwrap_or((target.as_str(), ""));
+    let query = query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            // 查询参数中的 + 表示空格
+            (
+                percent_decode(&key.replace('+', " ")),
+                percent_decode(&value.replace('+', " ")),
+            )
+        })
+        .collect();
+
+    Ok(Request {
+        method,
+        path: path.to_string(),
+        query,
+        headers,
+        body,
+    })
+}
+
+/// 解码 URL 中的 %XX
+pub fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let byte = std::str::from_utf8(&bytes[i + 1..i + 3])
+                .ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+            if let Some(byte) = byte {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+pub fn write_response(
+    stream: &mut dyn Stream,
+    status: u16,
+    content_type: &str,
+    body: &[u8],
+) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        reason(status),
+        content_type,
+        body.len()
+    )?;
+    stream.write_all(body)?;
+    stream.flush()
+}
+
+pub fn write_json<T: Serialize + ?Sized>(
+    stream: &mut dyn Stream,
+    status: u16,
+    value: &T,
+) -> io::Result<()> {
+    let body = serde_json::to_vec(value).unwrap_or_default();
+    write_response(stream, status, "application/json; charset=utf-8", &body)
+}
+
+pub fn write_error(stream: &mut dyn Stream, status: u16, message: &str) -> io::Result<()> {
+    write_json(stream, status, &serde_json::json!({ "error": message }))
+}
+
+/// 开始 Server-Sent Events 响应，之后用 write_event 持续写入
+pub fn start_event_stream(stream: &mut dyn Stream) -> io::Result<()> {
+    stream.write_all(
+        b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream; charset=utf-8\r\n\
+          Cache-Control: no-cache\r\nConnection: close\r\n\r\n",
+    )?;
+    stream.flush()
+}
+
+/// 写入一个事件，id 用于断线重连时通过 Last-Event-ID 续传
+pub fn write_event<T: Serialize>(stream: &mut dyn Stream, id: u64, data: &T) -> io::Result<()> {
+    let data = serde_json::to_string(data).unwrap_or_default();
+    write!(stream, "id: {}\ndata: {}\n\n", id, data)?;
+    stream.flush()
+}
+
+/// 写入注释行，保持连接并检测客户端是否已断开
+pub fn write_keep_alive(stream: &mut dyn Stream) -> io::Result<()> {
+    stream.write_all(b": keep-alive\n\n")?;
+    stream.flush()
+}
+
+/// 发送请求，返回状态码和读到响应头之后的响应体，供命令行客户端使用
+pub fn send_request(
+    addr: &ListenAddr,
+    method: &str,
+    path: &str,
+    token: &str,
+) -> Result<(u16, BufReader<Box<dyn Stream>>), String> {
+    let mut stream = connect(addr).map_err(|e| format!("连接 {} 失败: {}", addr, e))?;
+    write!(
+        stream,
+        "{} {} HTTP/1.1\r\nHost: localhost\r\nAuthorization: Bearer {}\r\n\
+         Content-Length: 0\r\nConnection: close\r\n\r\n",
+        method, path, token
+    )
+    .and_then(|_| stream.flush())
+    .map_err(|e| format!("发送请求失败: {}", e))?;
+
+    let mut reader = BufReader::new(stream);
+    let mut status_line = String::new();
+    reader
+        .read_line(&mut status_line)
+        .map_err(|e| format!("读取响应失败: {}", e))?;
+    let status = status_line
+        .split(' ')
+        .nth(1)
+        .and_then(|code| code.parse().ok())
+        .ok_or_else(|| "响应格式不正确".to_string())?;
+    loop {
+        let mut line = String::new();
+        let read = reader
+            .read_line(&mut line)
+            .map_err(|e| format!("读取响应失败: {}", e))?;
+        if read == 0 || line.trim_end().is_empty() {
+            break;
+        }
+    }
+    Ok((status, reader))
+}
+
+/// 编码路径中的一段，与 path_segments 的解码对应
+pub fn percent_encode(text: &str) -> String {
+    let mut encoded = String::with_capacity(text.len());
+    for byte in text.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-_.~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        _ => "Internal Server Error",
+    }
+}
+
+/// 比较令牌时不因第一个不同的字符提前返回
+pub fn token_matches(expected: &str, actual: &str) -> bool {
+    let (expected, actual) = (expected.as_bytes(), actual.as_bytes());
+    if expected.is_empty() || expected.len() != actual.len() {
+        return false;
+    }
+    expected
+        .iter()
+        .zip(actual)
+        .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+        == 0
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "chmlfrp-control-http-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn unix_socket_is_private_and_replaces_stale_socket() {
+        let dir = test_dir("socket");
+        let addr = ListenAddr::Unix(dir.join("control.sock"));
+
+        // 模拟异常退出后留下的套接字文件
+        drop(std::os::unix::net::UnixListener::bind(dir.join("control.sock")).unwrap());
+
+        let listener = Listener::bind(&addr).unwrap();
+        let mode = fs::metadata(dir.join("control.sock"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert!(connect(&addr).is_ok());
+        // 正在监听的地址不能再次绑定
+        assert!(Listener::bind(&addr).is_err());
+
+        drop(listener);
+        assert!(!dir.join("control.sock").exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn unix_bind_keeps_regular_file() {
+        let dir = test_dir("regular");
+        let path = dir.join("notes.txt");
+        fs::write(&path, "keep").unwrap();
+
+        assert!(Listener::bind(&ListenAddr::Unix(path.clone())).is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "keep");
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,142 @@
+// 用户令牌的加密存储
+//
+// ChmlFrp 的用户令牌由后端保存，启动 API 隧道和守护进程重启时从这里读取，
+// 不再由前端在每次启动时传入。令牌使用 AES-256-GCM 加密后保存在应用数据目录的
+// credentials.json 中，密钥由本机的机器标识和随机盐派生，文件被复制到其他设备后
+// 无法解密。取不到机器标识时退回到应用数据目录中随机生成的密钥文件。
+
+use crate::app_context::AppContext;
+use crate::json_store;
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const CREDENTIALS_FILE: &str = "credentials.json";
+
+/// 取不到机器标识时使用的密钥文件
+const FALLBACK_SECRET_FILE: &str = "credentials.key";
+
+const CREDENTIALS_VERSION: u32 = 1;
+
+/// 派生密钥时附加的应用标识，避免与其他程序使用相同的密钥
+const KEY_CONTEXT: &[u8] = b"ChmlFrpLauncher credentials v1";
+
+#[derive(Serialize, Deserialize)]
+struct EncryptedCredentials {
+    version: u32,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+/// 加密保存用户令牌，令牌没有变化时不重写文件
+pub fn save_token(app_handle: &AppContext, user_token: &str) -> Result<(), String> {
+    if load_token(app_handle).ok().flatten().as_deref() == Some(user_token) {
+        return Ok(());
+    }
+
+    let app_dir = app_handle.app_data_dir()?;
+    let mut salt = [0u8; 16];
+    OsRng.fill_bytes(&mut salt);
+    let cipher = cipher(&app_dir, &salt)?;
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, user_token.as_bytes())
+        .map_err(|e| format!("加密令牌失败: {}", e))?;
+
+    json_store::write_json(
+        &app_dir.join(CREDENTIALS_FILE),
+        &EncryptedCredentials {
+            version: CREDENTIALS_VERSION,
+            salt: hex::encode(salt),
+            nonce: hex::encode(nonce),
+            ciphertext: hex::encode(ciphertext),
+        },
+    )
+}
+
+/// 读取并解密用户令牌，没有保存过令牌时返回 None
+pub fn load_token(app_handle: &AppContext) -> Result<Option<String>, String> {
+    let app_dir = app_handle.app_data_dir()?;
+    let path = app_dir.join(CREDENTIALS_FILE);
+    let stored: EncryptedCredentials = match json_store::read_json(&path)? {
+        Some(stored) => stored,
+        None => return Ok(None),
+    };
+    if stored.version != CREDENTIALS_VERSION {
+        return Err(format!("不支持的凭据版本: {}", stored.version));
+    }
+
+    let decode = |value: &str| hex::decode(value).map_err(|e| format!("凭据格式不正确: {}", e));
+    let salt = decode(&stored.salt)?;
+    let nonce = decode(&stored.nonce)?;
+    if nonce.len() != 12 {
+        return Err("凭据格式不正确".to_string());
+    }
+    let ciphertext = decode(&stored.ciphertext)?;
+
+    let plaintext = cipher(&app_dir, &salt)?
+        .decrypt(Nonce::from_slice(&nonce), ciphertext.as_slice())
+        .map_err(|_| "解密令牌失败，凭据可能来自其他设备，请重新登录".to_string())?;
+    String::from_utf8(plaintext)
+        .map(Some)
+        .map_err(|e| format!("凭据格式不正确: {}", e))
+}
+
+/// 读取用户令牌，未登录时返回错误
+pub fn require_token(app_handle: &AppContext) -> Result<String, String> {
+    load_token(app_handle)?.ok_or_else(|| "未登录，请先登录".to_string())
+}
+
+/// 删除保存的令牌
+pub fn clear_token(app_handle: &AppContext) -> Result<(), String> {
+    let path = app_handle.app_data_dir()?.join(CREDENTIALS_FILE);
+    let _file = json_store::lock(&path)?;
+    // 备份中同样含有令牌
+    for extension in ["", ".bak", ".corrupt"] {
+        let mut name = path.as_os_str().to_os_string();
+        name.push(extension);
+        match fs::remove_file(PathBuf::from(name)) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(format!("删除凭据失败: {}", e)),
+        }
+    }
+    Ok(())
+}
+
+fn cipher(app_dir: &Path, salt: &[u8]) -> Result<Aes256Gcm, String> {
+    let mut hasher = Sha256::new();
+    hasher.update(KEY_CONTEXT);
+    hasher.update(machine_secret(app_dir)?);
+    hasher.update(salt);
+    let key = hasher.finalize();
+    Ok(Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key)))
+}
+
+// 本机的机器标识，取不到时读取或生成应用数据目录中的随机密钥
+fn machine_secret(app_dir: &Path) -> Result<Vec<u8>, String> {
+    match machine_uid::get() {
+        Ok(id) if !id.trim().is_empty() => return Ok(id.trim().as_bytes().to_vec()),
+        Ok(_) => eprintln!("[凭据] 机器标识为空，使用本地密钥文件"),
+        Err(e) => eprintln!("[凭据] 获取机器标识失败，使用本地密钥文件: {}", e),
+    }
+
+    let path = app_dir.join(FALLBACK_SECRET_FILE);
+    if let Ok(secret) = fs::read(&path) {
+        if secret.len() == 32 {
+            return Ok(secret);
+        }
+    }
+
+    let mut secret = vec![0u8; 32];
+    OsRng.fill_bytes(&mut secret);
+    fs::create_dir_all(app_dir).map_err(|e| format!("创建目录失败: {}", e))?;
+    // 密钥文件从创建起就只有当前用户可以读取
+    json_store::write_file(&path, &secret)?;
+    Ok(secret)
+}
@@ -0,0 +1,101 @@
+// 无界面的守护模式
+//
+// 以 daemon 子命令运行时不创建 Tauri 应用，也就不会启动窗口、托盘和依赖图形环境
+// 的事件循环，没有图形环境的服务器上可以直接运行。守护模式通过 AppContext 使用与
+// 图形界面相同的状态，读取设置后重新接管 frpc 进程，启动本地控制接口，再启动标记为
+// 自动启动的隧道。日志照常写入缓冲和日志文件，命令行的其他子命令通过控制接口管理
+// 隧道。控制接口的监听地址同时起到单实例锁的作用，地址被占用时守护模式不会启动。
+//
+// 收到 Ctrl+C 或 SIGTERM 后停止控制接口并退出，frpc 进程继续运行，下次启动时重新接管。
+
+use crate::app_context::AppContext;
+use crate::control_api::ControlApiServer;
+use crate::models::TunnelKey;
+use crate::settings::SettingsState;
+use std::time::Duration;
+
+/// 启动自动启动隧道之间的间隔，与前端一致
+const AUTO_START_INTERVAL: Duration = Duration::from_millis(500);
+
+/// 运行守护模式，返回进程退出码
+pub fn run() -> i32 {
+    let app_handle = match crate::cli::app_data_dir() {
+        Ok(app_dir) => AppContext::headless(app_dir),
+        Err(e) => {
+            eprintln!("[守护模式] 启动失败: {}", e);
+            return 1;
+        }
+    };
+
+    if let Err(e) = start(&app_handle) {
+        eprintln!("[守护模式] 启动失败: {}", e);
+        app_handle.state::<ControlApiServer>().shutdown();
+        return 1;
+    }
+    eprintln!("[守护模式] 已启动");
+
+    tauri::async_runtime::block_on(wait_for_shutdown());
+    eprintln!("[守护模式] 正在退出");
+    app_handle.state::<ControlApiServer>().shutdown();
+    0
+}
+
+fn start(app_handle: &AppContext) -> Result<(), Box<dyn std::error::Error>> {
+    crate::manage_state(app_handle);
+    crate::load_settings(app_handle)?;
+    // 先占用控制接口的地址，已有实例在运行时直接退出
+    crate::control_api::start_for_daemon(app_handle)?;
+    crate::start_services(app_handle)?;
+
+    let app_handle = app_handle.clone();
+    std::thread::spawn(move || start_auto_start_tunnels(&app_handle));
+    Ok(())
+}
+
+// 等待 Ctrl+C，Unix 上同时等待服务管理器发送的 SIGTERM
+async fn wait_for_shutdown() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = terminate.recv() => {}
+                }
+                return;
+            }
+            Err(e) => eprintln!("[守护模式] 监听 SIGTERM 失败: {}", e),
+        }
+    }
+
+    if let Err(e) = tokio::signal::ctrl_c().await {
+        // 无法监听信号时一直运行，由系统结束进程
+        eprintln!("[守护模式] 监听 Ctrl+C 失败: {}", e);
+        std::future::pending::<()>().await;
+    }
+}
+
+// 启动设置中标记为自动启动且没有在运行的隧道
+fn start_auto_start_tunnels(app_handle: &AppContext) {
+    let tunnel_keys = app_handle.state::<SettingsState>().get().tunnel_auto_start;
+    for tunnel_key in tunnel_keys {
+        if is_running(app_handle, &tunnel_key) {
+            continue;
+        }
+        match crate::control_api::start_tunnel(app_handle, &tunnel_key) {
+            Ok(message) => eprintln!("[守护模式] 自动启动 {}: {}", tunnel_key, message),
+            Err(e) => eprintln!("[守护模式] 自动启动 {} 失败: {}", tunnel_key, e),
+        }
+        std::thread::sleep(AUTO_START_INTERVAL);
+    }
+}
+
+fn is_running(app_handle: &AppContext, tunnel_key: &TunnelKey) -> bool {
+    let processes = app_handle.state::<crate::models::FrpcProcesses>();
+    let procs = match processes.processes.lock() {
+        Ok(procs) => procs,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    procs.get(tunnel_key).is_some_and(|p| p.is_running())
+}
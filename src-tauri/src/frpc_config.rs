@@ -0,0 +1,552 @@
+// frpc 配置文件格式
+//
+// 旧版 frpc 使用 INI 配置：[common] 段写服务器信息，其余每个段落是一个代理。
+// frpc 0.52 起改用 TOML、YAML 或 JSON，键名为驼峰形式（serverAddr、localPort），
+// 代理写在 proxies 数组中，访问者写在 visitors 数组中。这里识别配置格式，
+// 把包含多个代理的配置拆成每个代理一份，并读出隧道列表需要展示的信息。
+
+use crate::ini::{IniDocument, IniSection};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::path::Path;
+
+pub const COMMON_SECTION: &str = "common";
+
+/// 每一项都会拆成单独的隧道
+const ENTRY_KEYS: &[&str] = &["proxies", "visitors"];
+
+/// 出现任意一个即认为是新版配置
+const DOCUMENT_KEYS: &[&str] = &[
+    "serverAddr",
+    "serverPort",
+    "auth",
+    "transport",
+    "proxies",
+    "visitors",
+];
+
+/// 配置文件格式
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ConfigFormat {
+    #[default]
+    Ini,
+    Toml,
+    Yaml,
+    Json,
+}
+
+impl ConfigFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Ini => "ini",
+            Self::Toml => "toml",
+            Self::Yaml => "yaml",
+            Self::Json => "json",
+        }
+    }
+
+    /// 根据扩展名判断格式，未知扩展名按 INI 处理
+    pub fn from_path(path: &Path) -> Self {
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("toml") => Self::Toml,
+            Some("yaml") | Some("yml") => Self::Yaml,
+            Some("json") => Self::Json,
+            _ => Self::Ini,
+        }
+    }
+
+    /// 根据内容识别格式，无法识别为新版配置时按 INI 处理
+    pub fn detect(content: &str) -> Self {
+        let content = strip_bom(content);
+        if content.trim_start().starts_with('{') && serde_json::from_str::<Value>(content).is_ok() {
+            return Self::Json;
+        }
+        for format in [Self::Toml, Self::Yaml] {
+            if parse_document(content, format).is_ok_and(|document| is_frpc_document(&document)) {
+                return format;
+            }
+        }
+        Self::Ini
+    }
+}
+
+/// 配置中读出的隧道信息
+#[derive(Default)]
+pub struct ConfigInfo {
+    pub server_addr: Option<String>,
+    pub server_port: Option<u16>,
+    pub proxies: Vec<ProxyDescriptor>,
+}
+
+/// 配置中的一个代理或访问者
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ProxyDescriptor {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub proxy_type: Option<String>,
+    pub local_ip: Option<String>,
+    pub local_port: Option<u16>,
+    pub remote_port: Option<u16>,
+    #[serde(default)]
+    pub custom_domains: Vec<String>,
+    pub subdomain: Option<String>,
+    /// 插件类型，例如 static_file、http_proxy
+    pub plugin: Option<String>,
+}
+
+/// 解析配置文件
+pub fn parse_config(content: &str, format: ConfigFormat) -> Result<ConfigInfo, String> {
+    if format == ConfigFormat::Ini {
+        return parse_ini_config(content);
+    }
+
+    let document = parse_document(strip_bom(content), format)?;
+    let text = |entry: &Map<String, Value>, key: &str| {
+        entry.get(key).and_then(Value::as_str).map(str::to_string)
+    };
+
+    let proxies: Vec<ProxyDescriptor> = entries(&document)
+        .into_iter()
+        .filter_map(|(_, entry)| {
+            Some(ProxyDescriptor {
+                name: text(entry, "name")?,
+                proxy_type: text(entry, "type"),
+                local_ip: text(entry, "localIP"),
+                local_port: entry.get("localPort").and_then(as_port),
+                remote_port: entry.get("remotePort").and_then(as_port),
+                custom_domains: entry
+                    .get("customDomains")
+                    .map(string_list)
+                    .unwrap_or_default(),
+                subdomain: text(entry, "subdomain"),
+                plugin: entry
+                    .get("plugin")
+                    .and_then(|plugin| plugin.get("type"))
+                    .and_then(Value::as_str)
+                    .map(str::to_string),
+            })
+        })
+        .collect();
+
+    if proxies.is_empty() {
+        return Err("配置文件必须包含至少一个带 name 的代理".to_string());
+    }
+
+    Ok(ConfigInfo {
+        server_addr: text(&document, "serverAddr"),
+        server_port: document.get("serverPort").and_then(as_port),
+        proxies,
+    })
+}
+
+/// 按代理拆分配置，返回代理名和只包含该代理的配置内容
+pub fn split_config(content: &str, format: ConfigFormat) -> Result<Vec<(String, String)>, String> {
+    if format == ConfigFormat::Ini {
+        return split_ini_config(content);
+    }
+
+    let document = parse_document(strip_bom(content), format)?;
+    let entries = entries(&document);
+
+    let mut names = Vec::with_capacity(entries.len());
+    for (_, entry) in &entries {
+        match entry.get("name").and_then(Value::as_str) {
+            Some(name) if !name.trim().is_empty() => names.push(name.trim().to_string()),
+            _ => return Err("配置文件中有代理缺少 name".to_string()),
+        }
+    }
+
+    // 只有一个代理时保留原文，不丢失注释和排版
+    if let [name] = names.as_slice() {
+        return Ok(vec![(name.clone(), content.to_string())]);
+    }
+
+    let common: Map<String, Value> = document
+        .iter()
+        .filter(|(key, _)| !ENTRY_KEYS.contains(&key.as_str()))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect();
+
+    let mut split = Vec::with_capacity(entries.len());
+    for ((key, entry), name) in entries.into_iter().zip(names) {
+        let mut single = common.clone();
+        single.insert(
+            key.to_string(),
+            Value::Array(vec![Value::Object(entry.clone())]),
+        );
+        split.push((name, serialize_document(&single, format)?));
+    }
+
+    Ok(split)
+}
+
+pub fn strip_bom(content: &str) -> &str {
+    content.strip_prefix('\u{feff}').unwrap_or(content)
+}
+
+/// 解析 TOML、YAML 或 JSON 配置
+pub fn parse_document(content: &str, format: ConfigFormat) -> Result<Map<String, Value>, String> {
+    match format {
+        ConfigFormat::Toml => {
+            toml::from_str(content).map_err(|e| format!("解析 TOML 配置失败: {}", e))
+        }
+        ConfigFormat::Yaml => {
+            serde_yaml::from_str(content).map_err(|e| format!("解析 YAML 配置失败: {}", e))
+        }
+        ConfigFormat::Json => {
+            serde_json::from_str(content).map_err(|e| format!("解析 JSON 配置失败: {}", e))
+        }
+        ConfigFormat::Ini => Err("INI 配置不能按结构化格式解析".to_string()),
+    }
+}
+
+/// 生成 TOML、YAML 或 JSON 配置
+pub fn serialize_document(
+    document: &Map<String, Value>,
+    format: ConfigFormat,
+) -> Result<String, String> {
+    match format {
+        ConfigFormat::Toml => {
+            toml::to_string(document).map_err(|e| format!("生成 TOML 配置失败: {}", e))
+        }
+        ConfigFormat::Yaml => {
+            serde_yaml::to_string(document).map_err(|e| format!("生成 YAML 配置失败: {}", e))
+        }
+        ConfigFormat::Json => {
+            serde_json::to_string_pretty(document).map_err(|e| format!("生成 JSON 配置失败: {}", e))
+        }
+        ConfigFormat::Ini => Err("INI 配置不能按结构化格式生成".to_string()),
+    }
+}
+
+fn is_frpc_document(document: &Map<String, Value>) -> bool {
+    DOCUMENT_KEYS.iter().any(|key| document.contains_key(*key))
+}
+
+/// proxies 和 visitors 中的每一项，以及它所在的数组名
+pub fn entries(document: &Map<String, Value>) -> Vec<(&'static str, &Map<String, Value>)> {
+    ENTRY_KEYS
+        .iter()
+        .filter_map(|key| Some((*key, document.get(*key)?.as_array()?)))
+        .flat_map(|(key, items)| {
+            items
+                .iter()
+                .filter_map(move |item| Some((key, item.as_object()?)))
+        })
+        .collect()
+}
+
+fn as_port(value: &Value) -> Option<u16> {
+    match value {
+        Value::Number(number) => number.as_u64().and_then(|port| u16::try_from(port).ok()),
+        Value::String(text) => text.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+// customDomains 是字符串数组，也兼容以逗号分隔的字符串
+fn string_list(value: &Value) -> Vec<String> {
+    match value {
+        Value::String(text) => split_list(text),
+        Value::Array(items) => items
+            .iter()
+            .filter_map(Value::as_str)
+            .map(str::to_string)
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// 以逗号分隔的列表
+pub fn split_list(text: &str) -> Vec<String> {
+    text.split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn split_ini_config(content: &str) -> Result<Vec<(String, String)>, String> {
+    let document = IniDocument::parse(content);
+    let proxies: Vec<&IniSection> = document
+        .sections()
+        .iter()
+        .filter(|section| is_proxy_section(section))
+        .collect();
+
+    // 只有一个代理时保留原文
+    if let [section] = proxies.as_slice() {
+        return Ok(vec![(section.name.clone(), content.to_string())]);
+    }
+
+    let eol = document.eol();
+    let common: String = document
+        .sections()
+        .iter()
+        .filter(|section| section.name == COMMON_SECTION)
+        .map(|section| document.section_text(section))
+        .collect();
+    let common = common.trim_end();
+
+    Ok(proxies
+        .into_iter()
+        .map(|section| {
+            let block = document.section_text(section);
+            let block = block.trim_end();
+            let single = if common.is_empty() {
+                format!("{}{}", block, eol)
+            } else {
+                format!("{}{}{}{}{}", common, eol, eol, block, eol)
+            };
+            (section.name.clone(), single)
+        })
+        .collect())
+}
+
+/// 解析INI配置文件
+fn parse_ini_config(content: &str) -> Result<ConfigInfo, String> {
+    let document = IniDocument::parse(content);
+
+    let proxies: Vec<ProxyDescriptor> = document
+        .sections()
+        .iter()
+        .filter(|section| is_proxy_section(section))
+        .map(|section| {
+            let value = |key: &str| section.get(key).map(|entry| entry.value.clone());
+            let port = |key: &str| section.get(key).and_then(|entry| entry.value.parse().ok());
+            ProxyDescriptor {
+                name: section.name.clone(),
+                proxy_type: value("type"),
+                local_ip: value("local_ip"),
+                local_port: port("local_port"),
+                remote_port: port("remote_port"),
+                custom_domains: value("custom_domains")
+                    .map(|domains| split_list(&domains))
+                    .unwrap_or_default(),
+                subdomain: value("subdomain"),
+                plugin: value("plugin"),
+            }
+        })
+        .collect();
+
+    if proxies.is_empty() {
+        return Err("配置文件必须包含至少一个隧道段".to_string());
+    }
+
+    Ok(ConfigInfo {
+        server_addr: document
+            .get(COMMON_SECTION, "server_addr")
+            .map(|entry| entry.value.clone()),
+        server_port: document
+            .get(COMMON_SECTION, "server_port")
+            .and_then(|entry| entry.value.parse().ok()),
+        proxies,
+    })
+}
+
+/// 除 [common] 外的段落都是代理或访问者
+pub fn is_proxy_section(section: &IniSection) -> bool {
+    !section.name.is_empty() && section.name != COMMON_SECTION
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INI: &str = "\
+[common]
+server_addr = frp.example.com
+server_port = 7000
+
+[web]
+type = http
+local_port = 8080
+custom_domains = a.example.com, b.example.com
+
+[ssh]
+type = tcp
+local_ip = 127.0.0.1
+local_port = 22
+remote_port = 6000
+";
+
+    const TOML: &str = r#"serverAddr = "frp.example.com"
+serverPort = 7000
+
+[[proxies]]
+name = "web"
+type = "http"
+localPort = 8080
+customDomains = ["a.example.com", "b.example.com"]
+
+[[proxies]]
+name = "ssh"
+type = "tcp"
+localIP = "127.0.0.1"
+localPort = 22
+remotePort = 6000
+"#;
+
+    const YAML: &str = "\
+serverAddr: frp.example.com
+serverPort: 7000
+proxies:
+  - name: web
+    type: http
+    localPort: 8080
+    customDomains: [a.example.com, b.example.com]
+  - name: ssh
+    type: tcp
+    localIP: 127.0.0.1
+    localPort: 22
+    remotePort: 6000
+";
+
+    const JSON: &str = r#"{
+  "serverAddr": "frp.example.com",
+  "serverPort": 7000,
+  "proxies": [
+    {"name": "web", "type": "http", "localPort": 8080, "customDomains": ["a.example.com", "b.example.com"]},
+    {"name": "ssh", "type": "tcp", "localIP": "127.0.0.1", "localPort": 22, "remotePort": 6000}
+  ]
+}
+"#;
+
+    const FIXTURES: [(ConfigFormat, &str); 4] = [
+        (ConfigFormat::Ini, INI),
+        (ConfigFormat::Toml, TOML),
+        (ConfigFormat::Yaml, YAML),
+        (ConfigFormat::Json, JSON),
+    ];
+
+    #[test]
+    fn detects_each_format() {
+        for (format, content) in FIXTURES {
+            assert_eq!(ConfigFormat::detect(content), format);
+        }
+        assert_eq!(
+            ConfigFormat::detect(&format!("\u{feff}{}", JSON)),
+            ConfigFormat::Json
+        );
+    }
+
+    #[test]
+    fn detects_format_from_path() {
+        assert_eq!(
+            ConfigFormat::from_path(Path::new("frpc.YML")),
+            ConfigFormat::Yaml
+        );
+        assert_eq!(
+            ConfigFormat::from_path(Path::new("frpc.conf")),
+            ConfigFormat::Ini
+        );
+    }
+
+    #[test]
+    fn parses_same_proxies_from_every_format() {
+        for (format, content) in FIXTURES {
+            let info = parse_config(content, format).unwrap();
+            assert_eq!(info.server_addr.as_deref(), Some("frp.example.com"));
+            assert_eq!(info.server_port, Some(7000));
+            assert_eq!(
+                info.proxies,
+                vec![
+                    ProxyDescriptor {
+                        name: "web".to_string(),
+                        proxy_type: Some("http".to_string()),
+                        local_port: Some(8080),
+                        custom_domains: vec![
+                            "a.example.com".to_string(),
+                            "b.example.com".to_string()
+                        ],
+                        ..Default::default()
+                    },
+                    ProxyDescriptor {
+                        name: "ssh".to_string(),
+                        proxy_type: Some("tcp".to_string()),
+                        local_ip: Some("127.0.0.1".to_string()),
+                        local_port: Some(22),
+                        remote_port: Some(6000),
+                        ..Default::default()
+                    },
+                ],
+                "{:?}",
+                format
+            );
+        }
+    }
+
+    #[test]
+    fn splits_ini_keeping_common_section() {
+        let split = split_config(INI, ConfigFormat::Ini).unwrap();
+        let names: Vec<&str> = split.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["web", "ssh"]);
+        assert_eq!(
+            split[1].1,
+            "[common]\nserver_addr = frp.example.com\nserver_port = 7000\n\n[ssh]\ntype = tcp\nlocal_ip = 127.0.0.1\nlocal_port = 22\nremote_port = 6000\n"
+        );
+    }
+
+    #[test]
+    fn splits_structured_formats_into_single_proxy_documents() {
+        for (format, content) in FIXTURES.into_iter().skip(1) {
+            let split = split_config(content, format).unwrap();
+            assert_eq!(split.len(), 2, "{:?}", format);
+            for (name, single) in &split {
+                let document = parse_document(single, format).unwrap();
+                assert_eq!(document["serverAddr"], "frp.example.com");
+                let proxies = document["proxies"].as_array().unwrap();
+                assert_eq!(proxies.len(), 1);
+                assert_eq!(proxies[0]["name"], name.as_str());
+                assert_eq!(ConfigFormat::detect(single), format);
+            }
+        }
+    }
+
+    #[test]
+    fn single_proxy_is_kept_verbatim() {
+        let content = "# 注释\n[common]\nserver_addr = a\n\n[web]\ntype = tcp\n";
+        assert_eq!(
+            split_config(content, ConfigFormat::Ini).unwrap(),
+            vec![("web".to_string(), content.to_string())]
+        );
+        let content = "serverAddr = \"a\" # 注释\n[[proxies]]\nname = \"web\"\n";
+        assert_eq!(
+            split_config(content, ConfigFormat::Toml).unwrap(),
+            vec![("web".to_string(), content.to_string())]
+        );
+    }
+
+    #[test]
+    fn visitors_are_split_too() {
+        let content = "serverAddr = \"a\"\n[[proxies]]\nname = \"p\"\n[[visitors]]\nname = \"v\"\n";
+        let split = split_config(content, ConfigFormat::Toml).unwrap();
+        let visitor = parse_document(&split[1].1, ConfigFormat::Toml).unwrap();
+        assert_eq!(split[1].0, "v");
+        assert!(visitor.get("proxies").is_none());
+        assert_eq!(visitor["visitors"][0]["name"], "v");
+    }
+
+    #[test]
+    fn common_only_files_have_no_proxies() {
+        let ini = "[common]\nserver_addr = a\n";
+        assert!(split_config(ini, ConfigFormat::Ini).unwrap().is_empty());
+        assert!(parse_config(ini, ConfigFormat::Ini).is_err());
+
+        let toml = "serverAddr = \"a\"\nserverPort = 7000\n";
+        assert_eq!(ConfigFormat::detect(toml), ConfigFormat::Toml);
+        assert!(split_config(toml, ConfigFormat::Toml).unwrap().is_empty());
+        assert!(parse_config(toml, ConfigFormat::Toml).is_err());
+    }
+
+    #[test]
+    fn proxy_without_name_is_rejected() {
+        let content = "[[proxies]]\nname = \"a\"\n[[proxies]]\ntype = \"tcp\"\n";
+        assert!(split_config(content, ConfigFormat::Toml).is_err());
+    }
+}
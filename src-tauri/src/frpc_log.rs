@@ -0,0 +1,419 @@
+// frpc 日志解析
+//
+// frpc 的日志格式为：
+//   2024/01/15 12:34:56 [I] [service.go:301] [bc5e5ef6bbf8f9d5] [name] start proxy success
+// 新版本的日期为 2024-01-15 12:34:56.123，源文件带目录。这里把一行日志拆成
+// 日期、级别、源文件、代理名和正文，并识别常见事件，前端和守护进程不再需要
+// 各自匹配日志文本。
+
+use crate::models::TunnelKey;
+use aho_corasick::{AhoCorasick, AhoCorasickBuilder};
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+/// 日志级别
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum FrpcLogLevel {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl FrpcLogLevel {
+    fn from_tag(tag: &str) -> Option<Self> {
+        match tag {
+            "T" => Some(Self::Trace),
+            "D" => Some(Self::Debug),
+            "I" => Some(Self::Info),
+            "W" => Some(Self::Warn),
+            "E" => Some(Self::Error),
+            _ => None,
+        }
+    }
+}
+
+/// 解析后的日志行，不是 frpc 格式的行只有 body
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct FrpcLogLine {
+    pub date: Option<String>,
+    pub level: Option<FrpcLogLevel>,
+    pub source: Option<String>,
+    pub proxy_name: Option<String>,
+    pub body: String,
+}
+
+/// 已识别的日志事件
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FrpcEventKind {
+    /// 登录服务器成功
+    LoginSuccess,
+    /// 映射启动成功
+    ProxyStarted,
+    /// 服务器拒绝启动映射
+    ProxyRejected,
+    /// 与服务器断开后尝试重连
+    Reconnecting,
+    /// TLS 配置与服务器不匹配
+    TlsError,
+    /// 网络超时
+    IoTimeout,
+    /// 连接被服务器重置
+    ConnectionReset,
+    /// token 错误或鉴权失败
+    AuthFailed,
+    /// ChmlFrp API 拒绝了请求，例如隧道不属于该用户或超出会员限制
+    ApiRejected,
+    /// 账户因频繁启动被暂时限制
+    RateLimited,
+}
+
+/// 发送给前端的 frpc-event
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FrpcEvent {
+    pub tunnel_key: TunnelKey,
+    /// 兼容旧版的数字 ID，见 TunnelKey::legacy_id
+    pub tunnel_id: i32,
+    /// 对应 frpc-log 中原始日志的序号
+    pub seq: u64,
+    pub kind: Option<FrpcEventKind>,
+    #[serde(flatten)]
+    pub line: FrpcLogLine,
+}
+
+// 靠前的规则优先；匹配时不区分 ASCII 大小写
+const EVENT_PATTERNS: &[(FrpcEventKind, &[&str])] = &[
+    (
+        FrpcEventKind::AuthFailed,
+        &[
+            "token in login doesn't match token from configuration",
+            "authorization failed",
+            "invalid token",
+            "错误的用户token，此用户不存在",
+            "缺少用户token或隧道id参数",
+        ],
+    ),
+    (
+        FrpcEventKind::ApiRejected,
+        &[
+            "允许的隧道数量超出上限，请删除隧道或续费vip",
+            "不属于你",
+            "您目前为免费会员",
+            "客户端代理参数错误，配置文件与记录不匹配。请不要随意修改配置文件！",
+            "chmlfrp api error",
+        ],
+    ),
+    (FrpcEventKind::RateLimited, &["429 unknown status"]),
+    (
+        FrpcEventKind::TlsError,
+        &[
+            "tls_enable",
+            "first record does not look like a tls handshake",
+            "remote error: tls:",
+            "tls: handshake failure",
+            "tls: failed to verify certificate",
+            "x509: certificate",
+        ],
+    ),
+    (FrpcEventKind::IoTimeout, &["i/o timeout"]),
+    (
+        FrpcEventKind::ConnectionReset,
+        &["read: connection reset by peer"],
+    ),
+    (
+        FrpcEventKind::LoginSuccess,
+        &["login to server success", "成功登录至服务器"],
+    ),
+    (
+        FrpcEventKind::ProxyStarted,
+        &["start proxy success", "映射启动成功"],
+    ),
+    (FrpcEventKind::ProxyRejected, &["start error", "启动失败"]),
+    (
+        FrpcEventKind::Reconnecting,
+        &[
+            "try to reconnect",
+            "reconnect to server",
+            "尝试重新连接",
+            "重新连接",
+        ],
+    ),
+];
+
+/// 解析一行 frpc 日志
+pub fn parse_line(line: &str) -> FrpcLogLine {
+    let mut parsed = FrpcLogLine::default();
+    let mut rest = line.trim();
+
+    if let Some((date, remaining)) = split_date(rest) {
+        parsed.date = Some(date.to_string());
+        rest = remaining;
+    }
+
+    if let Some((tag, remaining)) = take_bracket(rest) {
+        if let Some(level) = FrpcLogLevel::from_tag(tag) {
+            parsed.level = Some(level);
+            rest = remaining;
+        }
+    }
+
+    if parsed.level.is_some() {
+        if let Some((source, remaining)) = take_bracket(rest) {
+            if source.contains(".go:") {
+                parsed.source = Some(source.to_string());
+                rest = remaining;
+            }
+        }
+
+        // 源文件之后依次是运行 ID 和代理名
+        let mut prefixes = Vec::new();
+        while let Some((tag, remaining)) = take_bracket(rest) {
+            prefixes.push(tag);
+            rest = remaining;
+            if prefixes.len() == 2 {
+                break;
+            }
+        }
+        parsed.proxy_name = match prefixes.as_slice() {
+            [_, proxy] => Some(proxy_display_name(proxy)),
+            [only] if !is_run_id(only) => Some(proxy_display_name(only)),
+            _ => None,
+        };
+    }
+
+    parsed.body = rest.to_string();
+    parsed
+}
+
+/// 识别日志事件
+pub fn classify(line: &FrpcLogLine) -> Option<FrpcEventKind> {
+    let matcher = event_matcher().as_ref()?;
+    matcher
+        .patterns
+        .find_overlapping_iter(&line.body)
+        .map(|m| matcher.rules[m.pattern().as_usize()])
+        .min()
+        .map(|rule| EVENT_PATTERNS[rule].0)
+}
+
+/// 所有规则的模式编译成的匹配器，rules 为每个模式所属规则的序号
+struct EventMatcher {
+    patterns: AhoCorasick,
+    rules: Vec<usize>,
+}
+
+fn event_matcher() -> &'static Option<EventMatcher> {
+    static MATCHER: OnceLock<Option<EventMatcher>> = OnceLock::new();
+    MATCHER.get_or_init(|| {
+        let mut patterns = Vec::new();
+        let mut rules = Vec::new();
+        for (rule, (_, rule_patterns)) in EVENT_PATTERNS.iter().enumerate() {
+            for pattern in rule_patterns.iter() {
+                patterns.push(*pattern);
+                rules.push(rule);
+            }
+        }
+        match AhoCorasickBuilder::new()
+            .ascii_case_insensitive(true)
+            .build(&patterns)
+        {
+            Ok(patterns) => Some(EventMatcher { patterns, rules }),
+            Err(e) => {
+                eprintln!("[日志] 创建事件匹配器失败: {}", e);
+                None
+            }
+        }
+    })
+}
+
+/// 解析并生成事件，既不是 frpc 格式也没有识别出事件时返回 None
+pub fn to_event(tunnel_key: &TunnelKey, seq: u64, line: &str) -> Option<FrpcEvent> {
+    let parsed = parse_line(line);
+    let kind = classify(&parsed);
+    if kind.is_none() && parsed.level.is_none() {
+        return None;
+    }
+    Some(FrpcEvent {
+        tunnel_key: tunnel_key.clone(),
+        tunnel_id: tunnel_key.legacy_id(),
+        seq,
+        kind,
+        line: parsed,
+    })
+}
+
+fn split_date(line: &str) -> Option<(&str, &str)> {
+    let mut parts = line.splitn(3, ' ');
+    let day = parts.next()?;
+    let time = parts.next()?;
+    let rest = parts.next().unwrap_or("");
+
+    let day_ok = day.len() == 10
+        && day
+            .chars()
+            .all(|c| c.is_ascii_digit() || c == '/' || c == '-');
+    let time_ok = time.len() >= 8
+        && time
+            .chars()
+            .all(|c| c.is_ascii_digit() || c == ':' || c == '.');
+    if !day_ok || !time_ok {
+        return None;
+    }
+    Some((&line[..day.len() + 1 + time.len()], rest.trim_start()))
+}
+
+fn take_bracket(text: &str) -> Option<(&str, &str)> {
+    let inner = text.strip_prefix('[')?;
+    let end = inner.find(']')?;
+    Some((&inner[..end], inner[end + 1..].trim_start()))
+}
+
+fn is_run_id(tag: &str) -> bool {
+    tag.len() >= 8 && tag.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+// ChmlFrp 的代理名为 "token.隧道名"，token 已被替换为 ***TOKEN*** 或 ***
+fn proxy_display_name(name: &str) -> String {
+    for prefix in ["***TOKEN***.", "***."] {
+        if let Some(stripped) = name.strip_prefix(prefix) {
+            return stripped.to_string();
+        }
+    }
+    name.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn classify_line(line: &str) -> Option<FrpcEventKind> {
+        classify(&parse_line(line))
+    }
+
+    #[test]
+    fn parses_legacy_line_with_proxy_name() {
+        let line = parse_line(
+            "2024/01/15 12:34:56 [I] [proxy_manager.go:144] [bc5e5ef6bbf8f9d5] [***TOKEN***.mc] start proxy success",
+        );
+        assert_eq!(line.date.as_deref(), Some("2024/01/15 12:34:56"));
+        assert_eq!(line.level, Some(FrpcLogLevel::Info));
+        assert_eq!(line.source.as_deref(), Some("proxy_manager.go:144"));
+        assert_eq!(line.proxy_name.as_deref(), Some("mc"));
+        assert_eq!(line.body, "start proxy success");
+    }
+
+    #[test]
+    fn parses_new_line_without_proxy_name() {
+        let line = parse_line(
+            "2024-01-15 12:34:56.123 [I] [client/service.go:295] [bc5e5ef6bbf8f9d5] login to server success, get run id [bc5e5ef6bbf8f9d5]",
+        );
+        assert_eq!(line.date.as_deref(), Some("2024-01-15 12:34:56.123"));
+        assert_eq!(line.source.as_deref(), Some("client/service.go:295"));
+        assert_eq!(line.proxy_name, None);
+        assert_eq!(
+            line.body,
+            "login to server success, get run id [bc5e5ef6bbf8f9d5]"
+        );
+    }
+
+    #[test]
+    fn non_frpc_line_keeps_only_body() {
+        let line = parse_line("  frpc version 0.51.3  ");
+        assert_eq!(
+            line,
+            FrpcLogLine {
+                body: "frpc version 0.51.3".to_string(),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn classifies_real_frpc_lines() {
+        let cases = [
+            (
+                "2024/01/15 12:34:56 [I] [service.go:301] [bc5e5ef6bbf8f9d5] login to server success, get run id [bc5e5ef6bbf8f9d5]",
+                Some(FrpcEventKind::LoginSuccess),
+            ),
+            (
+                "2024/01/15 12:34:56 [I] [proxy_manager.go:144] [bc5e5ef6bbf8f9d5] [***.web] start proxy success",
+                Some(FrpcEventKind::ProxyStarted),
+            ),
+            (
+                "2024/01/15 12:34:56 [W] [control.go:181] [bc5e5ef6bbf8f9d5] [***.web] start error: port already used",
+                Some(FrpcEventKind::ProxyRejected),
+            ),
+            (
+                "2024/01/15 12:34:56 [E] [service.go:305] login to server failed: token in login doesn't match token from configuration",
+                Some(FrpcEventKind::AuthFailed),
+            ),
+            (
+                "2024/01/15 12:34:56 [W] [service.go:131] login to server failed: tls: first record does not look like a TLS handshake",
+                Some(FrpcEventKind::TlsError),
+            ),
+            (
+                "2024/01/15 12:34:56 [W] [service.go:131] login to server failed: x509: certificate signed by unknown authority",
+                Some(FrpcEventKind::TlsError),
+            ),
+            (
+                "2024/01/15 12:34:56 [W] [service.go:131] login to server failed: dial tcp 1.2.3.4:7000: i/o timeout",
+                Some(FrpcEventKind::IoTimeout),
+            ),
+            (
+                "2024/01/15 12:34:56 [E] [control.go:158] [bc5e5ef6bbf8f9d5] work connection closed before response StartWorkConn message: read tcp 10.0.0.2:51234->1.2.3.4:7000: read: connection reset by peer",
+                Some(FrpcEventKind::ConnectionReset),
+            ),
+            (
+                "2024/01/15 12:34:56 [I] [service.go:215] [bc5e5ef6bbf8f9d5] try to reconnect to server...",
+                Some(FrpcEventKind::Reconnecting),
+            ),
+            (
+                "2024/01/15 12:34:56 [I] [root.go:220] start frpc service for config file [frpc.ini]",
+                None,
+            ),
+        ];
+        for (line, expected) in cases {
+            assert_eq!(classify_line(line), expected, "{}", line);
+        }
+    }
+
+    #[test]
+    fn earlier_rule_wins_regardless_of_position() {
+        // 行内先出现超时，但鉴权失败的规则更靠前
+        assert_eq!(
+            classify_line("i/o timeout, then authorization failed"),
+            Some(FrpcEventKind::AuthFailed)
+        );
+    }
+
+    #[test]
+    fn matching_ignores_ascii_case() {
+        assert_eq!(
+            classify_line("Login To Server Success"),
+            Some(FrpcEventKind::LoginSuccess)
+        );
+    }
+
+    #[test]
+    fn unrelated_tls_mention_is_not_tls_error() {
+        assert_eq!(
+            classify_line(
+                "2024/01/15 12:34:56 [I] [proxy_manager.go:144] [bc5e5ef6bbf8f9d5] [***.site] plugin https2http uses tls: cert loaded"
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn to_event_skips_plain_unrecognised_lines() {
+        let key = TunnelKey::Api(7);
+        assert!(to_event(&key, 1, "hello").is_none());
+        let event = to_event(&key, 2, "2024/01/15 12:34:56 [I] [root.go:220] hello").unwrap();
+        assert_eq!(event.tunnel_id, 7);
+        assert_eq!(event.seq, 2);
+        assert_eq!(event.kind, None);
+    }
+}
@@ -0,0 +1,289 @@
+// frpc 输出文件
+//
+// 通过管道读取 frpc 的输出时，启动器退出后管道的读端随之关闭，重新接管的
+// frpc 下一次写日志就会收到 SIGPIPE 退出（Windows 上写入失败）。这里让 frpc
+// 把 stdout 和 stderr 以追加方式写入 run 目录中的文件，启动器用读取线程跟踪
+// 文件新增的内容；重新接管进程时，新的启动器从文件末尾继续读取。
+//
+// 文件在读取线程读到末尾且超过 MAX_OUTPUT_BYTES 时截断，frpc 之后从文件开头
+// 继续追加。检查长度和截断之间 frpc 恰好写入的内容会丢失，只影响个别日志行。
+
+use crate::models::TunnelKey;
+use crate::run_config;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+
+/// 读到文件末尾后等待新内容的间隔
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// 已读完的输出文件超过该大小后截断
+const MAX_OUTPUT_BYTES: u64 = 1024 * 1024;
+
+/// 一次运行的 stdout 和 stderr 文件
+#[derive(Clone, Debug)]
+pub struct OutputFiles {
+    pub stdout: PathBuf,
+    pub stderr: PathBuf,
+}
+
+impl OutputFiles {
+    /// 创建本次运行的输出文件，返回传给 frpc 的 stdout 和 stderr
+    pub fn create(app_dir: &Path, tunnel_key: &TunnelKey) -> Result<(Self, Stdio, Stdio), String> {
+        let base = format!("{}-{}", tunnel_key, run_config::timestamp_millis());
+        let (stdout, stdout_file) = run_config::create_named(app_dir, &format!("{}.out", base))?;
+        let (stderr, stderr_file) =
+            match run_config::create_named(app_dir, &format!("{}.err", base)) {
+                Ok(created) => created,
+                Err(e) => {
+                    drop(stdout_file);
+                    run_config::remove(&stdout);
+                    return Err(e);
+                }
+            };
+        Ok((
+            Self { stdout, stderr },
+            Stdio::from(stdout_file),
+            Stdio::from(stderr_file),
+        ))
+    }
+
+    pub fn remove(&self) {
+        run_config::remove(&self.stdout);
+        run_config::remove(&self.stderr);
+    }
+}
+
+/// 跟踪输出文件，每读到一行调用一次 on_line（不含换行符）
+///
+/// from_end 为 true 时跳过已有内容，用于重新接管的进程。stopped 在进程退出后
+/// 设置，读取线程再读完一遍剩余内容后返回
+pub fn follow(
+    path: &Path,
+    from_end: bool,
+    stopped: &AtomicBool,
+    on_line: impl FnMut(String),
+) -> io::Result<()> {
+    follow_file(path, from_end, stopped, MAX_OUTPUT_BYTES, on_line)
+}
+
+fn follow_file(
+    path: &Path,
+    from_end: bool,
+    stopped: &AtomicBool,
+    max_bytes: u64,
+    mut on_line: impl FnMut(String),
+) -> io::Result<()> {
+    // 截断文件需要写权限
+    let file = fs::OpenOptions::new().read(true).write(true).open(path)?;
+    let mut reader = BufReader::new(file);
+    if from_end {
+        reader.seek(SeekFrom::End(0))?;
+    }
+
+    let mut line = Vec::new();
+    let mut draining = false;
+    loop {
+        reader.read_until(b'\n', &mut line)?;
+        if line.ends_with(b"\n") {
+            on_line(decode_line(&line));
+            line.clear();
+            continue;
+        }
+
+        // 已读到文件末尾。进程退出后再读一遍，确保退出前写入的内容都已读到
+        if draining {
+            if !line.is_empty() {
+                on_line(decode_line(&line));
+            }
+            return Ok(());
+        }
+        if stopped.load(Ordering::SeqCst) {
+            draining = true;
+            continue;
+        }
+
+        if line.is_empty() {
+            let position = reader.stream_position()?;
+            let len = reader.get_ref().metadata()?.len();
+            if position >= max_bytes && len == position {
+                reader.get_ref().set_len(0)?;
+                reader.seek(SeekFrom::Start(0))?;
+            } else if len < position {
+                // 文件被其他启动器截断过
+                reader.seek(SeekFrom::Start(0))?;
+            }
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+fn decode_line(line: &[u8]) -> String {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    let line = line.strip_suffix(b"\r").unwrap_or(line);
+    String::from_utf8_lossy(line).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::sync::mpsc;
+    use std::sync::Arc;
+    use std::time::Instant;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "chmlfrp-frpc-output-{}-{}-{}",
+            name,
+            std::process::id(),
+            run_config::timestamp_millis()
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    // 与 frpc 一样以追加方式写入
+    fn open_append(path: &Path) -> fs::File {
+        fs::OpenOptions::new().append(true).open(path).unwrap()
+    }
+
+    fn spawn_follow(
+        path: &Path,
+        from_end: bool,
+        max_bytes: u64,
+    ) -> (
+        Arc<AtomicBool>,
+        mpsc::Receiver<String>,
+        thread::JoinHandle<io::Result<()>>,
+    ) {
+        let stopped = Arc::new(AtomicBool::new(false));
+        let (tx, rx) = mpsc::channel();
+        let path = path.to_path_buf();
+        let flag = stopped.clone();
+        let handle = thread::spawn(move || {
+            follow_file(&path, from_end, &flag, max_bytes, |line| {
+                let _ = tx.send(line);
+            })
+        });
+        (stopped, rx, handle)
+    }
+
+    #[test]
+    fn reads_lines_and_trailing_partial_line_after_stop() {
+        let dir = test_dir("partial");
+        let (files, _, _) = OutputFiles::create(&dir, &TunnelKey::Api(1)).unwrap();
+        let mut out = open_append(&files.stdout);
+        out.write_all(b"first\r\nsecond\npart").unwrap();
+
+        let (stopped, rx, handle) = spawn_follow(&files.stdout, false, MAX_OUTPUT_BYTES);
+        assert_eq!(rx.recv_timeout(Duration::from_secs(2)).unwrap(), "first");
+        assert_eq!(rx.recv_timeout(Duration::from_secs(2)).unwrap(), "second");
+
+        out.write_all(b"ial\nlast").unwrap();
+        stopped.store(true, Ordering::SeqCst);
+        handle.join().unwrap().unwrap();
+        let rest: Vec<String> = rx.try_iter().collect();
+        assert_eq!(rest, vec!["partial".to_string(), "last".to_string()]);
+
+        files.remove();
+        assert!(!files.stdout.exists() && !files.stderr.exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn from_end_skips_existing_output() {
+        let dir = test_dir("from-end");
+        let (files, _, _) = OutputFiles::create(&dir, &TunnelKey::Api(2)).unwrap();
+        let mut out = open_append(&files.stdout);
+        out.write_all(b"old\n").unwrap();
+
+        let (stopped, rx, handle) = spawn_follow(&files.stdout, true, MAX_OUTPUT_BYTES);
+        thread::sleep(POLL_INTERVAL * 2);
+        out.write_all(b"new\n").unwrap();
+        assert_eq!(rx.recv_timeout(Duration::from_secs(2)).unwrap(), "new");
+
+        stopped.store(true, Ordering::SeqCst);
+        handle.join().unwrap().unwrap();
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn truncates_file_once_read_past_limit() {
+        let dir = test_dir("truncate");
+        let (files, _, _) = OutputFiles::create(&dir, &TunnelKey::Api(3)).unwrap();
+        let mut out = open_append(&files.stdout);
+        out.write_all(b"0123456789\n0123456789\n").unwrap();
+
+        let (stopped, rx, handle) = spawn_follow(&files.stdout, false, 16);
+        assert_eq!(
+            rx.recv_timeout(Duration::from_secs(2)).unwrap(),
+            "0123456789"
+        );
+        assert_eq!(
+            rx.recv_timeout(Duration::from_secs(2)).unwrap(),
+            "0123456789"
+        );
+
+        let deadline = Instant::now() + Duration::from_secs(2);
+        while fs::metadata(&files.stdout).unwrap().len() > 0 && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(20));
+        }
+        assert_eq!(fs::metadata(&files.stdout).unwrap().len(), 0);
+
+        // 追加写入的内容从文件开头继续
+        out.write_all(b"after\n").unwrap();
+        assert_eq!(rx.recv_timeout(Duration::from_secs(2)).unwrap(), "after");
+        assert_eq!(fs::metadata(&files.stdout).unwrap().len(), 6);
+
+        stopped.store(true, Ordering::SeqCst);
+        handle.join().unwrap().unwrap();
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    // 模拟启动器退出后重新接管：第一个读取线程退出后进程继续运行，
+    // 新的读取线程能读到它之后的输出
+    #[cfg(unix)]
+    #[test]
+    fn readopted_process_survives_reader_exit() {
+        let dir = test_dir("readopt");
+        let (files, stdout, stderr) = OutputFiles::create(&dir, &TunnelKey::Api(4)).unwrap();
+        let mut child = std::process::Command::new("sh")
+            .arg("-c")
+            .arg("while :; do echo tick; echo err >&2; sleep 0.05; done")
+            .stdout(stdout)
+            .stderr(stderr)
+            .spawn()
+            .unwrap();
+
+        let (stopped, rx, handle) = spawn_follow(&files.stdout, false, MAX_OUTPUT_BYTES);
+        assert_eq!(rx.recv_timeout(Duration::from_secs(2)).unwrap(), "tick");
+        stopped.store(true, Ordering::SeqCst);
+        handle.join().unwrap().unwrap();
+        drop(rx);
+
+        thread::sleep(Duration::from_millis(300));
+        assert!(
+            child.try_wait().unwrap().is_none(),
+            "进程在读取线程退出后结束"
+        );
+
+        let (stopped, rx, handle) = spawn_follow(&files.stdout, true, MAX_OUTPUT_BYTES);
+        assert_eq!(rx.recv_timeout(Duration::from_secs(2)).unwrap(), "tick");
+        let (err_stopped, err_rx, err_handle) = spawn_follow(&files.stderr, true, MAX_OUTPUT_BYTES);
+        assert_eq!(err_rx.recv_timeout(Duration::from_secs(2)).unwrap(), "err");
+
+        child.kill().unwrap();
+        child.wait().unwrap();
+        stopped.store(true, Ordering::SeqCst);
+        err_stopped.store(true, Ordering::SeqCst);
+        handle.join().unwrap().unwrap();
+        err_handle.join().unwrap().unwrap();
+        files.remove();
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,340 @@
+// 守护规则
+//
+// frpc 输出的每一行日志都会与守护规则比较，命中后执行规则指定的动作。
+// 除内置规则外，还可以在应用数据目录的 guard_rules.toml 或 guard_rules.json
+// 中添加规则，ChmlFrp API 新增错误提示时无需等待新版本。文件中的规则优先于
+// 内置规则，同 ID 的规则会覆盖内置规则，disabled_builtin 可以停用内置规则。
+
+use crate::app_context::AppContext;
+use crate::json_store;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use std::sync::{Mutex, RwLock};
+
+const RULES_TOML_FILE: &str = "guard_rules.toml";
+const RULES_JSON_FILE: &str = "guard_rules.json";
+
+static RULES_FILE_LOCK: Mutex<()> = Mutex::new(());
+
+/// 内置规则：出现前一组错误时自动重启也无法恢复，停止守护；
+/// 登录超时或 TLS 握手失败时尝试启用 TLS 后重启
+const BUILTIN_RULES: &[(&str, &str, GuardAction)] = &[
+    (
+        "builtin-token-mismatch",
+        "token in login doesn't match token from configuration",
+        GuardAction::StopGuard,
+    ),
+    (
+        "builtin-authorization-failed",
+        "authorization failed",
+        GuardAction::StopGuard,
+    ),
+    (
+        "builtin-invalid-token",
+        "invalid token",
+        GuardAction::StopGuard,
+    ),
+    (
+        "builtin-connection-reset",
+        "read: connection reset by peer",
+        GuardAction::StopGuard,
+    ),
+    (
+        "builtin-user-not-found",
+        "错误的用户token，此用户不存在",
+        GuardAction::StopGuard,
+    ),
+    (
+        "builtin-tunnel-limit",
+        "允许的隧道数量超出上限，请删除隧道或续费vip",
+        GuardAction::StopGuard,
+    ),
+    ("builtin-not-owner", "不属于你", GuardAction::StopGuard),
+    (
+        "builtin-missing-params",
+        "缺少用户token或隧道id参数",
+        GuardAction::StopGuard,
+    ),
+    (
+        "builtin-free-member",
+        "您目前为免费会员",
+        GuardAction::StopGuard,
+    ),
+    (
+        "builtin-config-mismatch",
+        "客户端代理参数错误，配置文件与记录不匹配。请不要随意修改配置文件！",
+        GuardAction::StopGuard,
+    ),
+    (
+        "builtin-api-error",
+        "ChmlFrp API Error",
+        GuardAction::StopGuard,
+    ),
+    (
+        "builtin-tls-hint",
+        "请尝试将配置文件中tls_enable",
+        GuardAction::FixTls,
+    ),
+    (
+        "builtin-tls-handshake",
+        "first record does not look like a TLS handshake",
+        GuardAction::FixTls,
+    ),
+    ("builtin-io-timeout", "i/o timeout", GuardAction::FixTls),
+];
+
+/// 匹配方式
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchKind {
+    #[default]
+    Substring,
+    Regex,
+}
+
+/// 命中规则后执行的动作
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum GuardAction {
+    /// 停止守护，进程继续运行
+    StopGuard,
+    /// 停止守护并停止隧道
+    StopTunnel,
+    /// 修复配置文件中的 TLS 设置后重启隧道
+    FixTls,
+    /// 下次自动重启前至少等待指定秒数
+    Backoff { seconds: u64 },
+}
+
+/// 守护规则
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct GuardRule {
+    pub id: String,
+    pub pattern: String,
+    #[serde(default)]
+    pub match_kind: MatchKind,
+    #[serde(default)]
+    pub case_sensitive: bool,
+    pub action: GuardAction,
+}
+
+/// 规则列表中的一项
+#[derive(Serialize, Clone, Debug)]
+pub struct GuardRuleInfo {
+    #[serde(flatten)]
+    pub rule: GuardRule,
+    pub builtin: bool,
+    pub enabled: bool,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct RulesFile {
+    #[serde(default)]
+    disabled_builtin: Vec<String>,
+    #[serde(default)]
+    rules: Vec<GuardRule>,
+}
+
+struct CompiledRule {
+    rule: GuardRule,
+    regex: Regex,
+}
+
+/// 已加载的守护规则，匹配器在加载时编译
+pub struct GuardRules {
+    compiled: RwLock<Vec<CompiledRule>>,
+    entries: RwLock<Vec<GuardRuleInfo>>,
+}
+
+impl GuardRules {
+    pub fn new() -> Self {
+        let rules = Self {
+            compiled: RwLock::new(Vec::new()),
+            entries: RwLock::new(Vec::new()),
+        };
+        rules.apply(RulesFile::default());
+        rules
+    }
+
+    /// 从应用数据目录重新加载规则
+    pub fn reload(&self, app_handle: &AppContext) -> Result<(), String> {
+        let app_dir = app_handle.app_data_dir()?;
+        let _lock = RULES_FILE_LOCK
+            .lock()
+            .map_err(|e| format!("获取守护规则锁失败: {}", e))?;
+        let file = read_rules_file(&app_dir)?;
+        self.apply(file);
+        Ok(())
+    }
+
+    /// 返回第一条命中的规则
+    pub fn find_match(&self, line: &str) -> Option<GuardRule> {
+        let compiled = self.compiled.read().ok()?;
+        compiled
+            .iter()
+            .find(|c| c.regex.is_match(line))
+            .map(|c| c.rule.clone())
+    }
+
+    pub fn list(&self) -> Vec<GuardRuleInfo> {
+        self.entries
+            .read()
+            .map(|entries| entries.clone())
+            .unwrap_or_default()
+    }
+
+    /// 添加规则，ID 相同的规则会被替换
+    pub fn add(&self, app_handle: &AppContext, mut rule: GuardRule) -> Result<GuardRule, String> {
+        rule.id = rule.id.trim().to_string();
+        if rule.id.is_empty() {
+            rule.id = format!("rule-{}", chrono::Local::now().timestamp_millis());
+        }
+        if rule.pattern.is_empty() {
+            return Err("规则内容不能为空".to_string());
+        }
+        compile(&rule)?;
+
+        let app_dir = app_handle.app_data_dir()?;
+        let _lock = RULES_FILE_LOCK
+            .lock()
+            .map_err(|e| format!("获取守护规则锁失败: {}", e))?;
+        let mut file = read_rules_file(&app_dir)?;
+        file.disabled_builtin.retain(|id| id != &rule.id);
+        match file.rules.iter_mut().find(|r| r.id == rule.id) {
+            Some(existing) => *existing = rule.clone(),
+            None => file.rules.push(rule.clone()),
+        }
+        write_rules_file(&app_dir, &file)?;
+        self.apply(file);
+        Ok(rule)
+    }
+
+    /// 删除规则
+    pub fn remove(&self, app_handle: &AppContext, id: &str) -> Result<(), String> {
+        let app_dir = app_handle.app_data_dir()?;
+        let _lock = RULES_FILE_LOCK
+            .lock()
+            .map_err(|e| format!("获取守护规则锁失败: {}", e))?;
+        let mut file = read_rules_file(&app_dir)?;
+
+        let before = file.rules.len();
+        file.rules.retain(|r| r.id != id);
+        let removed = file.rules.len() != before;
+
+        // 删除覆盖内置规则的自定义规则时恢复内置规则，否则停用内置规则
+        if !removed {
+            if !builtin_rules().iter().any(|r| r.id == id) {
+                return Err(format!("规则 {} 不存在", id));
+            }
+            if !file.disabled_builtin.iter().any(|d| d == id) {
+                file.disabled_builtin.push(id.to_string());
+            }
+        }
+
+        write_rules_file(&app_dir, &file)?;
+        self.apply(file);
+        Ok(())
+    }
+
+    fn apply(&self, file: RulesFile) {
+        let builtins = builtin_rules();
+        let mut entries = Vec::new();
+        let mut compiled = Vec::new();
+
+        for rule in &file.rules {
+            match compile(rule) {
+                Ok(regex) => {
+                    compiled.push(CompiledRule {
+                        rule: rule.clone(),
+                        regex,
+                    });
+                    entries.push(GuardRuleInfo {
+                        rule: rule.clone(),
+                        builtin: false,
+                        enabled: true,
+                    });
+                }
+                Err(e) => eprintln!("[守护规则] 忽略无效规则 {}: {}", rule.id, e),
+            }
+        }
+
+        for rule in builtins {
+            if file.rules.iter().any(|r| r.id == rule.id) {
+                continue;
+            }
+            let enabled = !file.disabled_builtin.contains(&rule.id);
+            if enabled {
+                if let Ok(regex) = compile(&rule) {
+                    compiled.push(CompiledRule {
+                        rule: rule.clone(),
+                        regex,
+                    });
+                }
+            }
+            entries.push(GuardRuleInfo {
+                rule,
+                builtin: true,
+                enabled,
+            });
+        }
+
+        if let Ok(mut current) = self.compiled.write() {
+            *current = compiled;
+        }
+        if let Ok(mut current) = self.entries.write() {
+            *current = entries;
+        }
+    }
+}
+
+fn builtin_rules() -> Vec<GuardRule> {
+    BUILTIN_RULES
+        .iter()
+        .map(|(id, pattern, action)| GuardRule {
+            id: id.to_string(),
+            pattern: pattern.to_string(),
+            match_kind: MatchKind::Substring,
+            case_sensitive: false,
+            action: action.clone(),
+        })
+        .collect()
+}
+
+fn compile(rule: &GuardRule) -> Result<Regex, String> {
+    let pattern = match rule.match_kind {
+        MatchKind::Substring => regex::escape(&rule.pattern),
+        MatchKind::Regex => rule.pattern.clone(),
+    };
+    RegexBuilder::new(&pattern)
+        .case_insensitive(!rule.case_sensitive)
+        .build()
+        .map_err(|e| format!("无效的正则表达式: {}", e))
+}
+
+// 两种格式同时存在时以 TOML 为准
+fn read_rules_file(app_dir: &Path) -> Result<RulesFile, String> {
+    let toml_path = app_dir.join(RULES_TOML_FILE);
+    if toml_path.exists() {
+        let content =
+            fs::read_to_string(&toml_path).map_err(|e| format!("读取守护规则失败: {}", e))?;
+        return toml::from_str(&content).map_err(|e| format!("解析守护规则失败: {}", e));
+    }
+
+    Ok(json_store::read_json(&app_dir.join(RULES_JSON_FILE))?.unwrap_or_default())
+}
+
+// 保存为用户已有的格式，默认使用 JSON
+fn write_rules_file(app_dir: &Path, file: &RulesFile) -> Result<(), String> {
+    fs::create_dir_all(app_dir).map_err(|e| format!("创建目录失败: {}", e))?;
+
+    let toml_path = app_dir.join(RULES_TOML_FILE);
+    if toml_path.exists() {
+        let content =
+            toml::to_string_pretty(file).map_err(|e| format!("序列化守护规则失败: {}", e))?;
+        return json_store::write_file(&toml_path, content.as_bytes());
+    }
+
+    json_store::write_json(&app_dir.join(RULES_JSON_FILE), file)
+}
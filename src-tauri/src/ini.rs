@@ -0,0 +1,448 @@
+// INI 配置模型
+//
+// 按行保存原始内容，每行记录行号和类型：空行、注释、段落头或键值对。
+// 键值对记录键、去掉两侧引号后的值，以及值在原始行中的位置，修改值时
+// 只替换这一段，其余内容原样保留，未修改的部分写回时与原文件逐字节一致。
+// 同名的段落和键都按出现顺序保留，读取时以最后一个为准。
+// frpc 读取 INI 时忽略行内注释（go-ini 的 IgnoreInlineComment），值中的 ; 和 #
+// 属于值本身，这里同样只把以 ; 或 # 开头的整行视为注释。
+
+use std::fmt;
+use std::ops::Range;
+
+/// 一行内容
+#[derive(Clone, Debug)]
+pub struct IniLine {
+    /// 从 1 开始的行号
+    pub number: usize,
+    /// 包含换行符的原始内容
+    pub raw: String,
+    pub kind: IniLineKind,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IniLineKind {
+    Blank,
+    Comment,
+    Section {
+        name: String,
+        comment: Option<String>,
+    },
+    Entry(IniEntry),
+    /// 既不是段落头也没有分隔符的行
+    Invalid,
+}
+
+/// 键值对
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IniEntry {
+    pub key: String,
+    /// 去掉两侧引号后的值
+    pub value: String,
+    pub quoted: bool,
+    /// 从 1 开始的行号
+    pub line: usize,
+    // 值（引号内的部分）在原始行中的位置
+    value_range: Range<usize>,
+}
+
+/// 段落
+#[derive(Clone, Debug)]
+pub struct IniSection {
+    pub name: String,
+    /// 段落头的行号，从 1 开始
+    pub line: usize,
+    pub entries: Vec<IniEntry>,
+    // 段落占用的行，从段落头到下一个段落头之前
+    lines: Range<usize>,
+}
+
+impl IniSection {
+    /// 同一段落中同名的键以最后一个为准
+    pub fn get(&self, key: &str) -> Option<&IniEntry> {
+        self.entries.iter().rev().find(|entry| entry.key == key)
+    }
+}
+
+/// 解析后的 INI 文件
+#[derive(Clone, Debug, Default)]
+pub struct IniDocument {
+    lines: Vec<IniLine>,
+    sections: Vec<IniSection>,
+    /// 第一个段落之前的键值对
+    orphans: Vec<IniEntry>,
+}
+
+impl IniDocument {
+    pub fn parse(content: &str) -> Self {
+        let lines = content
+            .split_inclusive('\n')
+            .enumerate()
+            .map(|(index, raw)| IniLine {
+                number: index + 1,
+                raw: raw.to_string(),
+                kind: parse_line(raw, index + 1),
+            })
+            .collect();
+        Self::from_lines(lines)
+    }
+
+    fn from_lines(lines: Vec<IniLine>) -> Self {
+        let mut sections: Vec<IniSection> = Vec::new();
+        let mut orphans = Vec::new();
+
+        for (index, line) in lines.iter().enumerate() {
+            match &line.kind {
+                IniLineKind::Section { name, .. } => {
+                    if let Some(previous) = sections.last_mut() {
+                        previous.lines.end = index;
+                    }
+                    sections.push(IniSection {
+                        name: name.clone(),
+                        line: line.number,
+                        entries: Vec::new(),
+                        lines: index..lines.len(),
+                    });
+                }
+                IniLineKind::Entry(entry) => match sections.last_mut() {
+                    Some(section) => section.entries.push(entry.clone()),
+                    None => orphans.push(entry.clone()),
+                },
+                _ => {}
+            }
+        }
+
+        Self {
+            lines,
+            sections,
+            orphans,
+        }
+    }
+
+    pub fn lines(&self) -> &[IniLine] {
+        &self.lines
+    }
+
+    /// 按出现顺序排列的段落，同名段落分别列出
+    pub fn sections(&self) -> &[IniSection] {
+        &self.sections
+    }
+
+    pub fn orphans(&self) -> &[IniEntry] {
+        &self.orphans
+    }
+
+    /// 所有同名段落中最后出现的键
+    pub fn get(&self, section: &str, key: &str) -> Option<&IniEntry> {
+        self.sections
+            .iter()
+            .rev()
+            .filter(|s| s.name == section)
+            .find_map(|s| s.get(key))
+    }
+
+    /// 文件使用的换行符
+    pub fn eol(&self) -> &'static str {
+        if self.lines.iter().any(|line| line.raw.ends_with("\r\n")) {
+            "\r\n"
+        } else {
+            "\n"
+        }
+    }
+
+    /// 段落的原始内容，包括段落头和之后的注释、空行
+    pub fn section_text(&self, section: &IniSection) -> String {
+        self.lines[section.lines.clone()]
+            .iter()
+            .map(|line| line.raw.as_str())
+            .collect()
+    }
+
+    /// 修改指定行的值，保留键名、分隔符两侧的空格和引号
+    pub fn set_value(&mut self, line: usize, value: &str) -> bool {
+        let index = match self.lines.iter().position(|l| l.number == line) {
+            Some(index) => index,
+            None => return false,
+        };
+        let range = match &self.lines[index].kind {
+            IniLineKind::Entry(entry) => entry.value_range.clone(),
+            _ => return false,
+        };
+        let raw = &self.lines[index].raw;
+        let raw = format!("{}{}{}", &raw[..range.start], value, &raw[range.end..]);
+        self.replace_lines(index..index + 1, vec![raw]);
+        true
+    }
+
+    /// 在段落最后一个非空行之后添加键值对
+    pub fn insert_entry(&mut self, section_index: usize, key: &str, value: &str) {
+        let lines = match self.sections.get(section_index) {
+            Some(section) => section.lines.clone(),
+            None => return,
+        };
+        let eol = self.eol();
+        let last = self.lines[lines.clone()]
+            .iter()
+            .rposition(|line| !matches!(line.kind, IniLineKind::Blank))
+            .map(|offset| lines.start + offset)
+            .unwrap_or(lines.start);
+
+        let mut previous = self.lines[last].raw.clone();
+        if !previous.ends_with('\n') {
+            previous.push_str(eol);
+        }
+        let entry = format!("{} = {}{}", key, value, eol);
+        self.replace_lines(last..last + 1, vec![previous, entry]);
+    }
+
+    /// 在文件开头添加段落
+    pub fn prepend_section(&mut self, name: &str, entries: &[(&str, &str)]) {
+        let eol = self.eol();
+        let mut text = format!("[{}]{}", name, eol);
+        for (key, value) in entries {
+            text.push_str(&format!("{} = {}{}", key, value, eol));
+        }
+        text.push_str(eol);
+        self.replace_lines(0..0, vec![text]);
+    }
+
+    // 替换一段行后重新编号并重建段落
+    fn replace_lines(&mut self, range: Range<usize>, raw: Vec<String>) {
+        let mut content: String = self.lines[..range.start]
+            .iter()
+            .map(|line| line.raw.as_str())
+            .collect();
+        content.extend(raw);
+        content.extend(self.lines[range.end..].iter().map(|line| line.raw.clone()));
+        *self = Self::parse(&content);
+    }
+}
+
+impl fmt::Display for IniDocument {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.lines
+            .iter()
+            .try_for_each(|line| f.write_str(&line.raw))
+    }
+}
+
+fn parse_line(raw: &str, number: usize) -> IniLineKind {
+    // 行首的空白和第一行的 BOM 不属于内容
+    let start = raw.len() - raw.trim_start_matches(['\u{feff}', ' ', '\t']).len();
+    let text = raw[start..].trim_end_matches(['\r', '\n']);
+    let trimmed = text.trim_end();
+
+    if trimmed.is_empty() {
+        return IniLineKind::Blank;
+    }
+    if trimmed.starts_with(';') || trimmed.starts_with('#') {
+        return IniLineKind::Comment;
+    }
+
+    if let Some(rest) = trimmed.strip_prefix('[') {
+        if let Some(end) = rest.find(']') {
+            let comment = rest[end + 1..]
+                .trim_start()
+                .strip_prefix([';', '#'])
+                .map(|c| c.trim().to_string());
+            return IniLineKind::Section {
+                name: rest[..end].trim().to_string(),
+                comment,
+            };
+        }
+    }
+
+    let delimiter = match text.find(['=', ':']) {
+        Some(delimiter) => delimiter,
+        None => return IniLineKind::Invalid,
+    };
+    let key = text[..delimiter].trim().to_string();
+    if key.is_empty() {
+        return IniLineKind::Invalid;
+    }
+
+    // 值从分隔符后第一个非空白字符开始，到行尾的空白之前结束
+    let after = &text[delimiter + 1..];
+    let value_start = delimiter + 1 + (after.len() - after.trim_start().len());
+    let rest = text[value_start..].trim_end();
+
+    let quoted = is_quoted(rest);
+    let value_range = if quoted {
+        value_start + 1..value_start + rest.len() - 1
+    } else {
+        value_start..value_start + rest.len()
+    };
+
+    IniLineKind::Entry(IniEntry {
+        key,
+        value: text[value_range.clone()].to_string(),
+        quoted,
+        line: number,
+        value_range: start + value_range.start..start + value_range.end,
+    })
+}
+
+// 与 go-ini 相同，整个值被同一种引号包围且中间没有这种引号时才去掉引号
+fn is_quoted(value: &str) -> bool {
+    ['"', '\''].into_iter().any(|quote| {
+        value.len() >= 2
+            && value.starts_with(quote)
+            && value.ends_with(quote)
+            && value[1..].find(quote) == Some(value.len() - 2)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(document: &IniDocument, section: &str, key: &str) -> IniEntry {
+        document.get(section, key).unwrap().clone()
+    }
+
+    #[test]
+    fn classifies_lines() {
+        let document = IniDocument::parse(
+            "; 注释\n# 注释\n\n[common] ; 服务器\nserver_addr = a\ninvalid line\n",
+        );
+        let kinds: Vec<&IniLineKind> = document.lines().iter().map(|line| &line.kind).collect();
+        assert_eq!(kinds[0], &IniLineKind::Comment);
+        assert_eq!(kinds[1], &IniLineKind::Comment);
+        assert_eq!(kinds[2], &IniLineKind::Blank);
+        assert_eq!(
+            kinds[3],
+            &IniLineKind::Section {
+                name: "common".to_string(),
+                comment: Some("服务器".to_string()),
+            }
+        );
+        assert!(matches!(kinds[4], IniLineKind::Entry(_)));
+        assert_eq!(kinds[5], &IniLineKind::Invalid);
+    }
+
+    #[test]
+    fn keeps_inline_comment_markers_in_values() {
+        // frpc 不识别行内注释，; 和 # 之后的内容属于值
+        let document =
+            IniDocument::parse("[common]\ntoken = abc ; 令牌\nurl = http://a/#top\nuser = x # y\n");
+        assert_eq!(entry(&document, "common", "token").value, "abc ; 令牌");
+        assert_eq!(entry(&document, "common", "url").value, "http://a/#top");
+        assert_eq!(entry(&document, "common", "user").value, "x # y");
+    }
+
+    #[test]
+    fn strips_surrounding_quotes_only() {
+        let document =
+            IniDocument::parse("[p]\na = \"x y\"\nb = 'z'\nc = \"x\" y\nd = \"a\"b\"\ne = \"\n");
+        let value = |key: &str| {
+            let entry = entry(&document, "p", key);
+            (entry.value, entry.quoted)
+        };
+        assert_eq!(value("a"), ("x y".to_string(), true));
+        assert_eq!(value("b"), ("z".to_string(), true));
+        assert_eq!(value("c"), ("\"x\" y".to_string(), false));
+        assert_eq!(value("d"), ("\"a\"b\"".to_string(), false));
+        assert_eq!(value("e"), ("\"".to_string(), false));
+    }
+
+    #[test]
+    fn accepts_colon_and_empty_values() {
+        let document = IniDocument::parse("[p]\nlocal_port: 22\nsk =\n");
+        assert_eq!(entry(&document, "p", "local_port").value, "22");
+        assert_eq!(entry(&document, "p", "sk").value, "");
+    }
+
+    #[test]
+    fn round_trips_byte_for_byte() {
+        for content in [
+            "\u{feff}[common]\r\nserver_addr = a\r\n\r\n; x\r\n[p]\r\ntype=tcp\r\n",
+            "[common]\n  server_addr   =   a   \n\n[p]\ntype = tcp",
+            "",
+            "\n\n",
+        ] {
+            assert_eq!(IniDocument::parse(content).to_string(), content);
+        }
+    }
+
+    #[test]
+    fn bom_and_crlf_do_not_leak_into_names_or_values() {
+        let document = IniDocument::parse("\u{feff}[common]\r\nserver_addr = a \r\n");
+        assert_eq!(document.sections()[0].name, "common");
+        assert_eq!(entry(&document, "common", "server_addr").value, "a");
+        assert_eq!(document.eol(), "\r\n");
+    }
+
+    #[test]
+    fn duplicate_keys_and_sections_use_the_last_one() {
+        let document = IniDocument::parse(
+            "[common]\nserver_port = 1\nserver_port = 2\n[common]\nserver_port = 3\n",
+        );
+        assert_eq!(document.sections().len(), 2);
+        assert_eq!(document.sections()[0].entries.len(), 2);
+        assert_eq!(
+            document.sections()[0].get("server_port").unwrap().value,
+            "2"
+        );
+        let last = entry(&document, "common", "server_port");
+        assert_eq!((last.value.as_str(), last.line), ("3", 5));
+    }
+
+    #[test]
+    fn orphans_are_kept_apart() {
+        let document = IniDocument::parse("a = 1\n[common]\nb = 2\n");
+        assert_eq!(document.orphans().len(), 1);
+        assert_eq!(document.orphans()[0].key, "a");
+        assert_eq!(document.sections()[0].entries.len(), 1);
+    }
+
+    #[test]
+    fn set_value_changes_only_that_value() {
+        let content =
+            "; 头部\r\n[common]\r\nserver_addr = a\r\ntls_enable =  \"false\"  \r\ntoken = t\r\n";
+        let mut document = IniDocument::parse(content);
+        let line = entry(&document, "common", "tls_enable").line;
+        assert!(document.set_value(line, "true"));
+        assert_eq!(
+            document.to_string(),
+            "; 头部\r\n[common]\r\nserver_addr = a\r\ntls_enable =  \"true\"  \r\ntoken = t\r\n"
+        );
+        assert_eq!(entry(&document, "common", "tls_enable").value, "true");
+        assert!(!document.set_value(1, "x"));
+        assert!(!document.set_value(99, "x"));
+    }
+
+    #[test]
+    fn insert_entry_follows_last_line_of_section() {
+        let mut document = IniDocument::parse("[common]\nserver_addr = a\n\n[p]\ntype = tcp");
+        document.insert_entry(0, "tls_enable", "true");
+        document.insert_entry(1, "local_port", "22");
+        assert_eq!(
+            document.to_string(),
+            "[common]\nserver_addr = a\ntls_enable = true\n\n[p]\ntype = tcp\nlocal_port = 22\n"
+        );
+    }
+
+    #[test]
+    fn prepend_section_uses_file_line_endings() {
+        let mut document = IniDocument::parse("[p]\r\ntype = tcp\r\n");
+        document.prepend_section("common", &[("tls_enable", "true")]);
+        assert_eq!(
+            document.to_string(),
+            "[common]\r\ntls_enable = true\r\n\r\n[p]\r\ntype = tcp\r\n"
+        );
+        assert_eq!(document.sections()[1].line, 4);
+    }
+
+    #[test]
+    fn section_text_spans_until_next_header() {
+        let document = IniDocument::parse("[a]\nx = 1\n; c\n\n[b]\ny = 2\n");
+        assert_eq!(
+            document.section_text(&document.sections()[0]),
+            "[a]\nx = 1\n; c\n\n"
+        );
+        assert_eq!(
+            document.section_text(&document.sections()[1]),
+            "[b]\ny = 2\n"
+        );
+    }
+}
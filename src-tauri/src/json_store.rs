@@ -0,0 +1,222 @@
+// JSON 状态文件读写
+//
+// 隧道列表、自动启动设置等 JSON 文件会被多个异步命令读改写，直接 fs::write
+// 时并发保存会互相覆盖，写到一半崩溃会留下截断的文件。这里先写入临时文件再
+// 重命名替换，替换前把上一份完好的内容复制为 .bak；同一文件的读改写在进程内
+// 通过文件锁串行执行。主文件损坏时从 .bak 恢复，损坏的文件另存为 .corrupt，
+// 恢复记录由前端读取后提示用户。文件中可能有令牌等敏感内容，主文件和备份
+// 都只有当前用户可以读写。
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Condvar, Mutex};
+
+/// 正在被读写的文件
+static LOCKED_FILES: Mutex<BTreeSet<PathBuf>> = Mutex::new(BTreeSet::new());
+static FILE_UNLOCKED: Condvar = Condvar::new();
+
+/// 尚未被前端读取的恢复记录
+static RECOVERIES: Mutex<Vec<StorageRecovery>> = Mutex::new(Vec::new());
+
+/// 一次从备份恢复的记录
+#[derive(Serialize, Clone, Debug)]
+pub struct StorageRecovery {
+    pub file: String,
+    /// 主文件无法读取的原因
+    pub error: String,
+    pub recovered_at: String,
+}
+
+/// 持有期间其他线程无法读写同一文件，读改写需要在同一个锁内完成
+pub struct LockedFile {
+    path: PathBuf,
+}
+
+/// 获取文件锁，同一文件已被锁定时等待
+pub fn lock(path: &Path) -> Result<LockedFile, String> {
+    let mut locked = LOCKED_FILES
+        .lock()
+        .map_err(|e| format!("获取文件锁失败: {}", e))?;
+    while locked.contains(path) {
+        locked = FILE_UNLOCKED
+            .wait(locked)
+            .map_err(|e| format!("获取文件锁失败: {}", e))?;
+    }
+    locked.insert(path.to_path_buf());
+    Ok(LockedFile {
+        path: path.to_path_buf(),
+    })
+}
+
+impl Drop for LockedFile {
+    fn drop(&mut self) {
+        if let Ok(mut locked) = LOCKED_FILES.lock() {
+            locked.remove(&self.path);
+        }
+        FILE_UNLOCKED.notify_all();
+    }
+}
+
+impl LockedFile {
+    /// 读取文件，不存在时返回 None，主文件损坏时从备份恢复
+    pub fn read<T: DeserializeOwned>(&self) -> Result<Option<T>, String> {
+        let error = match read_file(&self.path) {
+            Ok(value) => return Ok(value),
+            Err(error) => error,
+        };
+
+        let backup = sibling(&self.path, "bak");
+        let content = match fs::read_to_string(&backup) {
+            Ok(content) => content,
+            Err(_) => return Err(error),
+        };
+        let value = match serde_json::from_str(&content) {
+            Ok(value) => value,
+            Err(_) => return Err(error),
+        };
+
+        if let Err(e) = fs::rename(&self.path, sibling(&self.path, "corrupt")) {
+            eprintln!("[存储] 保留损坏的文件失败: {}", e);
+        }
+        replace_file(&self.path, content.as_bytes())?;
+
+        let file = file_name(&self.path);
+        eprintln!("[存储] {} 已损坏，已从备份恢复: {}", file, error);
+        if let Ok(mut recoveries) = RECOVERIES.lock() {
+            recoveries.push(StorageRecovery {
+                file,
+                error,
+                recovered_at: chrono::Local::now().to_rfc3339(),
+            });
+        }
+
+        Ok(Some(value))
+    }
+
+    /// 写入临时文件后替换主文件，替换前备份上一份完好的内容
+    pub fn write<T: Serialize + ?Sized>(&self, value: &T) -> Result<(), String> {
+        let content = serde_json::to_string_pretty(value)
+            .map_err(|e| format!("序列化 {} 失败: {}", file_name(&self.path), e))?;
+
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("创建目录失败: {}", e))?;
+        }
+
+        // 主文件已损坏时不能覆盖备份
+        let current = fs::read(&self.path)
+            .ok()
+            .filter(|bytes| serde_json::from_slice::<serde_json::Value>(bytes).is_ok());
+        if let Some(current) = current {
+            if let Err(e) = replace_file(&sibling(&self.path, "bak"), &current) {
+                eprintln!("[存储] 备份 {} 失败: {}", file_name(&self.path), e);
+            }
+        }
+
+        replace_file(&self.path, content.as_bytes())
+    }
+}
+
+/// 加锁读取文件
+pub fn read_json<T: DeserializeOwned>(path: &Path) -> Result<Option<T>, String> {
+    lock(path)?.read()
+}
+
+/// 加锁写入文件
+pub fn write_json<T: Serialize + ?Sized>(path: &Path, value: &T) -> Result<(), String> {
+    lock(path)?.write(value)
+}
+
+/// 加锁写入非 JSON 文件，同样先写临时文件再替换，不保留备份
+pub fn write_file(path: &Path, content: &[u8]) -> Result<(), String> {
+    let _file = lock(path)?;
+    replace_file(path, content)
+}
+
+/// 取出尚未提示过的恢复记录
+pub fn take_recoveries() -> Vec<StorageRecovery> {
+    RECOVERIES
+        .lock()
+        .map(|mut recoveries| std::mem::take(&mut *recoveries))
+        .unwrap_or_default()
+}
+
+fn read_file<T: DeserializeOwned>(path: &Path) -> Result<Option<T>, String> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("读取 {} 失败: {}", file_name(path), e)),
+    };
+    serde_json::from_str(&content)
+        .map(Some)
+        .map_err(|e| format!("解析 {} 失败: {}", file_name(path), e))
+}
+
+// 先写入并刷新临时文件，再重命名替换目标文件。临时文件创建时就只有当前用户
+// 可以读写，替换后原来权限较宽的文件也随之收紧
+fn replace_file(path: &Path, content: &[u8]) -> Result<(), String> {
+    let temp = sibling(path, "tmp");
+    let write = || -> std::io::Result<()> {
+        // 上次写到一半留下的临时文件
+        let _ = fs::remove_file(&temp);
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options.open(&temp)?;
+        file.write_all(content)?;
+        file.sync_all()?;
+        fs::rename(&temp, path)
+    };
+    write().map_err(|e| {
+        let _ = fs::remove_file(&temp);
+        format!("保存 {} 失败: {}", file_name(path), e)
+    })
+}
+
+// 同目录下追加扩展名的文件，例如 custom_tunnels.json.bak
+fn sibling(path: &Path, extension: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(".");
+    name.push(extension);
+    PathBuf::from(name)
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.to_string_lossy().to_string())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn mode(path: &Path) -> u32 {
+        fs::metadata(path).unwrap().permissions().mode() & 0o777
+    }
+
+    #[test]
+    fn writes_file_and_backup_owner_only() {
+        let dir = std::env::temp_dir().join(format!("chmlfrp-json-store-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("credentials.json");
+        fs::write(&path, "{}").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+
+        write_json(&path, &serde_json::json!({ "token": "secret" })).unwrap();
+        assert_eq!(mode(&path), 0o600);
+        assert_eq!(mode(&sibling(&path, "bak")), 0o600);
+        assert_eq!(fs::read_to_string(sibling(&path, "bak")).unwrap(), "{}");
+        assert!(!sibling(&path, "tmp").exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -1,8 +1,29 @@
+mod app_context;
+pub mod cli;
 mod commands;
+mod config_convert;
+mod config_validate;
+mod control_api;
+mod control_http;
+mod credentials;
+mod daemon;
+mod frpc_config;
+mod frpc_log;
+mod frpc_output;
+mod guard_rules;
+mod ini;
+mod json_store;
 mod models;
-mod utils;
+mod process_registry;
+mod redact;
+mod run_config;
+mod settings;
+mod tls_config;
+mod tunnel_logs;
 
-pub use models::{FrpcProcesses, ProcessGuardState};
+pub use models::{FrpcProcesses, ProcessGuardState, TunnelLogBuffers, TunnelStates};
+
+use app_context::AppContext;
 
 use tauri::{
     menu::{MenuBuilder, MenuItemBuilder},
@@ -27,30 +48,14 @@ pub fn run() {
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_deep_link::init())
         .setup(|app| {
-            let app_handle = app.handle().clone();
+            let app_handle = AppContext::from(app.handle().clone());
+            manage_state(&app_handle);
+            
+            // 读取设置，在前端自动启动隧道之前恢复守护设置
+            load_settings(&app_handle)?;
             
             // 获取自动启动隧道设置
-            let auto_start_tunnels = {
-                let app_data_dir = app_handle.path().app_data_dir().ok();
-                if let Some(dir) = app_data_dir {
-                    let config_path = dir.join("auto_start_tunnels.json");
-                    if config_path.exists() {
-                        if let Ok(content) = std::fs::read_to_string(&config_path) {
-                            if let Ok(config) = serde_json::from_str::<serde_json::Value>(&content) {
-                                config.get("enabled").and_then(|v| v.as_bool()).unwrap_or(false)
-                            } else {
-                                false
-                            }
-                        } else {
-                            false
-                        }
-                    } else {
-                        false
-                    }
-                } else {
-                    false
-                }
-            };
+            let auto_start_tunnels = app.state::<settings::SettingsState>().get().auto_start_tunnels;
             
             let show_item = MenuItemBuilder::with_id("show", "显示窗口").build(app)?;
             let auto_start_item = MenuItemBuilder::with_id(
@@ -87,49 +92,22 @@ pub fn run() {
                         }
                     }
                     "auto_start_tunnels" => {
-                        let app_handle = app.clone();
-                        
-                        // 获取当前设置
-                        let current_setting = {
-                            let app_data_dir = app_handle.path().app_data_dir().ok();
-                            if let Some(dir) = app_data_dir {
-                                let config_path = dir.join("auto_start_tunnels.json");
-                                if config_path.exists() {
-                                    if let Ok(content) = std::fs::read_to_string(&config_path) {
-                                        if let Ok(config) = serde_json::from_str::<serde_json::Value>(&content) {
-                                            config.get("enabled").and_then(|v| v.as_bool()).unwrap_or(false)
-                                        } else {
-                                            false
-                                        }
-                                    } else {
-                                        false
-                                    }
-                                } else {
-                                    false
-                                }
-                            } else {
-                                false
-                            }
-                        };
-                        
-                        // 切换设置
-                        let new_setting = !current_setting;
+                        let app_handle = AppContext::from(app.clone());
                         
-                        // 保存设置
-                        if let Some(app_data_dir) = app_handle.path().app_data_dir().ok() {
-                            if std::fs::create_dir_all(&app_data_dir).is_ok() {
-                                let config_path = app_data_dir.join("auto_start_tunnels.json");
-                                let config = serde_json::json!({
-                                    "enabled": new_setting
-                                });
-                                if std::fs::write(&config_path, serde_json::to_string_pretty(&config).unwrap()).is_ok() {
-                                    // 发送事件到前端
-                                    let _ = app_handle.emit("auto-start-tunnels-changed", new_setting);
-                                    
-                                    // 注意：菜单项文本可能无法动态更新，需要重新构建菜单
-                                    // 功能仍然正常工作，只是菜单项文本可能不更新
-                                }
+                        // 切换并保存设置
+                        let settings = app_handle.state::<settings::SettingsState>();
+                        match settings.update(&app_handle, |settings| {
+                            settings.auto_start_tunnels = !settings.auto_start_tunnels;
+                            settings.auto_start_tunnels
+                        }) {
+                            Ok(new_setting) => {
+                                // 发送事件到前端
+                                let _ = app_handle.emit("auto-start-tunnels-changed", new_setting);
+                                
+                                // 注意：菜单项文本可能无法动态更新，需要重新构建菜单
+                                // 功能仍然正常工作，只是菜单项文本可能不更新
                             }
+                            Err(e) => eprintln!("[设置] 保存自动启动隧道设置失败: {}", e),
                         }
                     }
                     "quit" => {
@@ -198,13 +176,13 @@ pub fn run() {
                 }
             });
 
-            let app_handle = app.handle().clone();
-            commands::process_guard::start_guard_monitor(app_handle);
+            start_services(&app_handle)?;
+
+            // 按设置启动本地控制接口
+            control_api::apply_settings(&app_handle);
 
             Ok(())
         })
-        .manage(FrpcProcesses::new())
-        .manage(ProcessGuardState::new())
         .invoke_handler(tauri::generate_handler![
             commands::check_frpc_exists,
             commands::get_frpc_directory,
@@ -214,12 +192,17 @@ pub fn run() {
             commands::stop_frpc,
             commands::is_frpc_running,
             commands::get_running_tunnels,
+            commands::get_running_tunnel_keys,
+            commands::get_tunnel_logs,
+            commands::get_tunnel_states,
             commands::test_log_event,
             commands::is_autostart_enabled,
             commands::set_autostart,
             commands::get_auto_start_tunnels,
             commands::get_tunnel_auto_start,
             commands::set_tunnel_auto_start,
+            commands::set_credentials,
+            commands::clear_credentials,
             commands::http_request,
             commands::hide_window,
             commands::show_window,
@@ -230,6 +213,9 @@ pub fn run() {
             commands::get_custom_tunnel_config,
             commands::delete_custom_tunnel,
             commands::update_custom_tunnel,
+            commands::convert_custom_tunnel_config,
+            commands::validate_custom_tunnel_config,
+            commands::verify_custom_tunnel_config,
             commands::start_custom_tunnel,
             commands::stop_custom_tunnel,
             commands::is_custom_tunnel_running,
@@ -237,13 +223,31 @@ pub fn run() {
             commands::get_background_video_path,
             commands::process_guard::set_process_guard_enabled,
             commands::process_guard::get_process_guard_enabled,
+            commands::process_guard::get_process_guard_config,
+            commands::process_guard::set_process_guard_config,
+            commands::process_guard::get_never_guard_tunnels,
+            commands::process_guard::set_tunnel_never_guard,
             commands::process_guard::add_guarded_process,
             commands::process_guard::add_guarded_custom_tunnel,
             commands::process_guard::remove_guarded_process,
             commands::process_guard::check_log_and_stop_guard,
-            commands::fix_frpc_ini_tls
+            commands::process_guard::list_guard_rules,
+            commands::process_guard::add_guard_rule,
+            commands::process_guard::remove_guard_rule,
+            commands::process_guard::reload_guard_rules,
+            commands::fix_frpc_ini_tls,
+            commands::list_tunnel_logs,
+            commands::read_tunnel_log,
+            commands::export_tunnel_logs,
+            commands::delete_tunnel_logs,
+            commands::take_storage_recoveries,
+            commands::get_settings,
+            commands::set_settings,
+            commands::get_control_api_status,
+            commands::get_control_api_token,
+            commands::regenerate_control_api_token
         ])
-        .build(tauri::generate_context!())
+        .build(context())
         .expect("error while building tauri application")
         .run(|app_handle, event| match event {
             #[cfg(target_os = "macos")]
@@ -255,9 +259,50 @@ pub fn run() {
                     }
                 }
             }
-            _ => {
-                #[cfg(not(target_os = "macos"))]
-                let _ = app_handle;
+            // 退出时停止控制接口，删除 Unix 套接字文件
+            tauri::RunEvent::Exit => {
+                app_handle.state::<control_api::ControlApiServer>().shutdown();
             }
+            _ => {}
         });
 }
+
+// 界面和守护模式共用的状态
+fn manage_state(app_handle: &AppContext) {
+    app_handle.manage(FrpcProcesses::new());
+    app_handle.manage(TunnelLogBuffers::new());
+    app_handle.manage(TunnelStates::new());
+    app_handle.manage(guard_rules::GuardRules::new());
+    app_handle.manage(ProcessGuardState::new());
+    app_handle.manage(tunnel_logs::TunnelLogFiles::new());
+    app_handle.manage(control_api::ControlApiServer::new());
+}
+
+// 只生成一次，避免前端资源在程序中嵌入两份
+fn context() -> tauri::Context<tauri::Wry> {
+    tauri::generate_context!()
+}
+
+// 读取设置，首次运行时导入旧版的设置文件，然后恢复守护设置
+fn load_settings(app_handle: &AppContext) -> Result<(), Box<dyn std::error::Error>> {
+    app_handle.manage(settings::SettingsState::load(&app_handle.app_data_dir()?));
+    commands::process_guard::apply_guard_settings(app_handle);
+    Ok(())
+}
+
+// 重新接管上次运行时留下的 frpc 进程并加载守护规则
+fn start_services(app_handle: &AppContext) -> Result<(), Box<dyn std::error::Error>> {
+    match process_registry::readopt_processes(app_handle) {
+        Ok(adopted) => {
+            commands::process_guard::guard_adopted_processes(app_handle, &adopted);
+            run_config::remove_stale(&app_handle.app_data_dir()?, &adopted);
+        }
+        Err(e) => eprintln!("[进程登记] 重新接管 frpc 进程失败: {}", e),
+    }
+
+    if let Err(e) = app_handle.state::<guard_rules::GuardRules>().reload(app_handle) {
+        eprintln!("[守护规则] 加载守护规则失败: {}", e);
+    }
+
+    Ok(())
+}
@@ -1,8 +1,90 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::process::Child;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::path::PathBuf;
 use std::sync::atomic::AtomicBool;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+// 隧道标识，API 隧道使用 ChmlFrp 的隧道 ID，自定义隧道使用配置名，两者不会冲突。
+// 序列化为 "api_<ID>" 或 "custom_<名称>"，与前端和自启动列表使用的键一致；
+// 反序列化时也接受数字，视为 API 隧道 ID
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(into = "String", try_from = "TunnelKeyRepr")]
+pub enum TunnelKey {
+    Api(i32),
+    Custom(String),
+}
+
+impl TunnelKey {
+    // 兼容旧版 frpc-log 等事件中的数字 tunnel_id：API 隧道为隧道 ID，
+    // 自定义隧道为由名称计算出的负数，不会与 API 隧道重复。
+    // 使用 FNV-1a，结果不随 Rust 版本变化，保存过的 ID 在升级后仍然一致
+    pub fn legacy_id(&self) -> i32 {
+        match self {
+            TunnelKey::Api(id) => *id,
+            TunnelKey::Custom(name) => -((fnv1a(name.as_bytes()) % i32::MAX as u64) as i32) - 1,
+        }
+    }
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+    bytes.iter().fold(OFFSET_BASIS, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(PRIME)
+    })
+}
+
+impl fmt::Display for TunnelKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TunnelKey::Api(id) => write!(f, "api_{}", id),
+            TunnelKey::Custom(name) => write!(f, "custom_{}", name),
+        }
+    }
+}
+
+impl From<TunnelKey> for String {
+    fn from(key: TunnelKey) -> Self {
+        key.to_string()
+    }
+}
+
+impl TryFrom<String> for TunnelKey {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        if let Some(id) = value.strip_prefix("api_") {
+            return id
+                .parse()
+                .map(TunnelKey::Api)
+                .map_err(|_| format!("无效的隧道标识: {}", value));
+        }
+        match value.strip_prefix("custom_") {
+            Some(name) if !name.is_empty() => Ok(TunnelKey::Custom(name.to_string())),
+            _ => Err(format!("无效的隧道标识: {}", value)),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TunnelKeyRepr {
+    Id(i32),
+    Key(String),
+}
+
+impl TryFrom<TunnelKeyRepr> for TunnelKey {
+    type Error = String;
+
+    fn try_from(value: TunnelKeyRepr) -> Result<Self, Self::Error> {
+        match value {
+            TunnelKeyRepr::Id(id) => Ok(TunnelKey::Api(id)),
+            TunnelKeyRepr::Key(key) => TunnelKey::try_from(key),
+        }
+    }
+}
 
 // 下载进度结构
 #[derive(Serialize, Clone)]
@@ -49,9 +131,115 @@ pub struct DownloadInfo {
     pub size: u64,
 }
 
+// 进程退出信息，由等待线程在进程退出后写入
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ExitInfo {
+    pub exit_code: Option<i32>,
+    pub signal: Option<i32>,
+}
+
+// 供停止操作等待进程退出
+#[derive(Default)]
+pub struct ProcessExit {
+    info: Mutex<Option<ExitInfo>>,
+    exited: Condvar,
+}
+
+impl ProcessExit {
+    pub fn set(&self, info: ExitInfo) {
+        if let Ok(mut guard) = self.info.lock() {
+            *guard = Some(info);
+        }
+        self.exited.notify_all();
+    }
+
+    pub fn get(&self) -> Option<ExitInfo> {
+        self.info.lock().ok().and_then(|guard| *guard)
+    }
+
+    pub fn wait_timeout(&self, timeout: Duration) -> Option<ExitInfo> {
+        let guard = match self.info.lock() {
+            Ok(guard) => guard,
+            Err(_) => return None,
+        };
+        match self
+            .exited
+            .wait_timeout_while(guard, timeout, |info| info.is_none())
+        {
+            Ok((guard, _)) => *guard,
+            Err(_) => None,
+        }
+    }
+}
+
+// 运行中的frpc进程
+pub struct FrpcProcess {
+    pub pid: u32,
+    pub started_at: Instant,
+    // 重新接管的进程登记的启动时间，用于排除 PID 被复用的情况，新启动的进程为 0
+    pub process_start_time: u64,
+    pub exit: Arc<ProcessExit>,
+    // 最近的日志，随退出事件一起发送
+    pub recent_logs: Arc<Mutex<VecDeque<String>>>,
+}
+
+impl FrpcProcess {
+    pub fn new(pid: u32) -> Self {
+        Self {
+            pid,
+            started_at: Instant::now(),
+            process_start_time: 0,
+            exit: Arc::new(ProcessExit::default()),
+            recent_logs: Arc::new(Mutex::new(VecDeque::new())),
+        }
+    }
+
+    pub fn adopted(pid: u32, process_start_time: u64) -> Self {
+        Self {
+            process_start_time,
+            ..Self::new(pid)
+        }
+    }
+
+    // 接管的进程由等待线程定期检查 PID，发现退出后同样记录到 exit。调用方通常持有
+    // 进程表的锁，这里不查询系统进程信息
+    pub fn is_running(&self) -> bool {
+        self.exit.get().is_none()
+    }
+
+    // 等待进程退出，超时返回 false
+    pub fn wait_for_exit(&self, timeout: Duration) -> bool {
+        self.exit.wait_timeout(timeout).is_some()
+    }
+}
+
+// 进程退出事件
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TunnelExitEvent {
+    pub tunnel_key: TunnelKey,
+    // 兼容旧版的数字 ID，见 TunnelKey::legacy_id
+    pub tunnel_id: i32,
+    pub pid: u32,
+    pub exit_code: Option<i32>,
+    pub signal: Option<i32>,
+    pub uptime_secs: u64,
+    pub last_logs: Vec<String>,
+}
+
+// 进程停止方式
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StopOutcome {
+    // 收到退出信号后自行退出
+    Graceful,
+    // 超过等待时间后被强制结束
+    Forced,
+    // 停止前进程已经退出
+    AlreadyExited,
+}
+
 // 存储运行中的frpc进程
 pub struct FrpcProcesses {
-    pub processes: Mutex<HashMap<i32, Child>>,
+    pub processes: Mutex<HashMap<TunnelKey, FrpcProcess>>,
 }
 
 impl FrpcProcesses {
@@ -62,25 +250,129 @@ impl FrpcProcesses {
     }
 }
 
-// 隧道类型
-#[derive(Clone, Debug)]
-pub enum TunnelType {
-    Api { user_token: String },
-    Custom { original_id: String },
+// 隧道生命周期状态
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "state", content = "reason", rename_all = "snake_case")]
+pub enum TunnelState {
+    // 正在启动 frpc 进程
+    Starting,
+    // 进程已启动，正在登录服务器并启动映射
+    LoggingIn,
+    // 映射启动成功
+    Connected,
+    // 与服务器断开，frpc 正在重连
+    Reconnecting,
+    Failed(String),
+    Stopping,
+    Stopped,
+    // 进程退出后等待守护进程重启
+    GuardedRestarting,
 }
 
-// 进程守护信息
-#[derive(Clone)]
-pub struct ProcessGuardInfo {
+// 隧道当前状态
+#[derive(Serialize, Clone, Debug)]
+pub struct TunnelStateInfo {
+    pub tunnel_key: TunnelKey,
+    // 兼容旧版的数字 ID，见 TunnelKey::legacy_id
     pub tunnel_id: i32,
-    pub tunnel_type: TunnelType,
+    #[serde(flatten)]
+    pub state: TunnelState,
+    // 进入该状态的时间
+    pub since: String,
+}
+
+// 所有隧道的状态
+#[derive(Default)]
+pub struct TunnelStates {
+    pub states: Mutex<HashMap<TunnelKey, TunnelStateInfo>>,
+}
+
+impl TunnelStates {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+// 进程守护信息，包含重新启动隧道所需的参数
+#[derive(Clone)]
+pub enum ProcessGuardInfo {
+    Api { tunnel_id: i32 },
+    Custom { tunnel_id: String },
+}
+
+impl ProcessGuardInfo {
+    pub fn tunnel_key(&self) -> TunnelKey {
+        match self {
+            ProcessGuardInfo::Api { tunnel_id } => TunnelKey::Api(*tunnel_id),
+            ProcessGuardInfo::Custom { tunnel_id } => TunnelKey::Custom(tunnel_id.clone()),
+        }
+    }
+}
+
+// 守护进程重启策略
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(default)]
+pub struct GuardConfig {
+    // 第一次重启前的等待时间，之后每次翻倍
+    pub initial_backoff_ms: u64,
+    pub max_backoff_ms: u64,
+    // 在 restart_window_secs 内最多重启的次数，超过即视为崩溃循环
+    pub max_restarts: u32,
+    pub restart_window_secs: u64,
+    // 稳定运行超过该时间后重置退避
+    pub stable_uptime_secs: u64,
+}
+
+impl Default for GuardConfig {
+    fn default() -> Self {
+        Self {
+            initial_backoff_ms: 1000,
+            max_backoff_ms: 60_000,
+            max_restarts: 5,
+            restart_window_secs: 300,
+            stable_uptime_secs: 60,
+        }
+    }
+}
+
+// 单个隧道的重启状态
+#[derive(Default, Clone, Debug)]
+pub struct RestartState {
+    pub consecutive_restarts: u32,
+    pub recent_restarts: VecDeque<Instant>,
+    pub restart_pending: bool,
+    pub crash_looping: bool,
+    // 守护规则要求下次重启不早于该时间
+    pub backoff_until: Option<Instant>,
+    // 自动修复 TLS 配置的进度，每次手动启动只修复一次
+    pub tls_remediation: Option<TlsRemediation>,
+    // 命令行模式下无法自动修复 TLS，已提示过用户
+    pub tls_fix_skipped: bool,
+    // 配置文件模式下最近一次成功获取的 API 隧道配置，重启时获取失败则使用它
+    pub last_config: Option<String>,
+}
+
+// 守护进程自动修复 TLS 配置后的重试
+#[derive(Clone, Debug)]
+pub struct TlsRemediation {
+    pub config_path: PathBuf,
+    // 修改前的配置内容，重试失败时写回
+    pub original: String,
+    // 修复时正在运行的进程，它的输出和退出不代表重试结果
+    pub fixed_pid: Option<u32>,
+    // 重试已经成功或已回滚
+    pub finished: bool,
 }
 
 // 守护进程状态管理
 pub struct ProcessGuardState {
     pub enabled: Arc<AtomicBool>,
-    pub guarded_processes: Arc<Mutex<HashMap<i32, ProcessGuardInfo>>>,
-    pub manually_stopped: Arc<Mutex<std::collections::HashSet<i32>>>,
+    pub guarded_processes: Arc<Mutex<HashMap<TunnelKey, ProcessGuardInfo>>>,
+    pub manually_stopped: Arc<Mutex<std::collections::HashSet<TunnelKey>>>,
+    pub config: Arc<Mutex<GuardConfig>>,
+    pub restart_states: Arc<Mutex<HashMap<TunnelKey, RestartState>>>,
+    // 设置为从不守护的隧道
+    pub never_guard: Arc<Mutex<std::collections::HashSet<TunnelKey>>>,
 }
 
 impl ProcessGuardState {
@@ -89,6 +381,9 @@ impl ProcessGuardState {
             enabled: Arc::new(AtomicBool::new(false)),
             guarded_processes: Arc::new(Mutex::new(HashMap::new())),
             manually_stopped: Arc::new(Mutex::new(std::collections::HashSet::new())),
+            config: Arc::new(Mutex::new(GuardConfig::default())),
+            restart_states: Arc::new(Mutex::new(HashMap::new())),
+            never_guard: Arc::new(Mutex::new(std::collections::HashSet::new())),
         }
     }
 }
@@ -96,9 +391,123 @@ impl ProcessGuardState {
 // 日志消息结构
 #[derive(Serialize, Clone)]
 pub struct LogMessage {
+    pub tunnel_key: TunnelKey,
+    // 兼容旧版的数字 ID，见 TunnelKey::legacy_id
     pub tunnel_id: i32,
     pub message: String,
     pub timestamp: String,
+    // 与 get_tunnel_logs 返回的序号一致，前端用来去重
+    pub seq: u64,
+}
+
+// 每个隧道在内存中保留的日志行数
+pub const LOG_BUFFER_CAPACITY: usize = 1000;
+
+// 日志缓冲中的一条日志
+#[derive(Serialize, Clone, Debug)]
+pub struct TunnelLogEntry {
+    pub seq: u64,
+    pub tunnel_key: TunnelKey,
+    // 兼容旧版的数字 ID，见 TunnelKey::legacy_id
+    pub tunnel_id: i32,
+    pub message: String,
+    // RFC 3339 格式，精确到毫秒
+    pub timestamp: String,
+}
+
+// get_tunnel_logs 的返回结果
+#[derive(Serialize, Clone, Debug)]
+pub struct TunnelLogBatch {
+    pub entries: Vec<TunnelLogEntry>,
+    // 当前最新的序号，没有日志时为 0
+    pub latest_seq: u64,
+    // since_seq 之后的部分日志已被挤出缓冲
+    pub truncated: bool,
+}
+
+#[derive(Default)]
+struct TunnelLogBuffer {
+    next_seq: u64,
+    entries: VecDeque<TunnelLogEntry>,
+}
+
+// 每个隧道最近日志的环形缓冲，窗口隐藏或重新加载后前端据此补齐日志
+#[derive(Default)]
+pub struct TunnelLogBuffers {
+    buffers: Mutex<HashMap<TunnelKey, TunnelLogBuffer>>,
+}
+
+impl TunnelLogBuffers {
+    pub fn new() -> Self {
+        Self {
+            buffers: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn push(
+        &self,
+        tunnel_key: &TunnelKey,
+        message: String,
+        time: chrono::DateTime<chrono::Local>,
+    ) -> TunnelLogEntry {
+        let mut buffers = match self.buffers.lock() {
+            Ok(buffers) => buffers,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        let buffer = buffers.entry(tunnel_key.clone()).or_default();
+        buffer.next_seq += 1;
+        let entry = TunnelLogEntry {
+            seq: buffer.next_seq,
+            tunnel_key: tunnel_key.clone(),
+            tunnel_id: tunnel_key.legacy_id(),
+            message,
+            timestamp: time.to_rfc3339_opts(chrono::SecondsFormat::Millis, false),
+        };
+        if buffer.entries.len() >= LOG_BUFFER_CAPACITY {
+            buffer.entries.pop_front();
+        }
+        buffer.entries.push_back(entry.clone());
+        entry
+    }
+
+    // 返回序号大于 since_seq 的日志，从旧到新最多 limit 条
+    pub fn since(&self, tunnel_key: &TunnelKey, since_seq: u64, limit: usize) -> TunnelLogBatch {
+        let buffers = match self.buffers.lock() {
+            Ok(buffers) => buffers,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        let buffer = match buffers.get(tunnel_key) {
+            Some(buffer) => buffer,
+            None => {
+                return TunnelLogBatch {
+                    entries: Vec::new(),
+                    latest_seq: 0,
+                    truncated: false,
+                }
+            }
+        };
+
+        // 序号比当前最新的还大，说明启动器重启过，从头返回
+        let restarted = since_seq > buffer.next_seq;
+        let since_seq = if restarted { 0 } else { since_seq };
+        let truncated = restarted
+            || buffer
+                .entries
+                .front()
+                .is_some_and(|first| first.seq > since_seq + 1);
+        let entries = buffer
+            .entries
+            .iter()
+            .filter(|entry| entry.seq > since_seq)
+            .take(limit)
+            .cloned()
+            .collect();
+        TunnelLogBatch {
+            entries,
+            latest_seq: buffer.next_seq,
+            truncated,
+        }
+    }
 }
 
 // HTTP请求选项
@@ -110,3 +519,33 @@ pub struct HttpRequestOptions {
     pub body: Option<String>,
     pub bypass_proxy: Option<bool>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn legacy_id_of_api_tunnel_is_its_id() {
+        assert_eq!(TunnelKey::Api(123).legacy_id(), 123);
+    }
+
+    #[test]
+    fn legacy_id_of_custom_tunnel_is_stable() {
+        // 固定值，哈希实现变化会导致已保存的 ID 不再匹配
+        assert_eq!(
+            TunnelKey::Custom("my-tunnel".to_string()).legacy_id(),
+            -1701430247
+        );
+        assert_eq!(
+            TunnelKey::Custom("隧道".to_string()).legacy_id(),
+            -368936488
+        );
+    }
+
+    #[test]
+    fn legacy_id_of_custom_tunnel_is_negative() {
+        for name in ["a", "b", "web", "ssh", "x".repeat(100).as_str()] {
+            assert!(TunnelKey::Custom(name.to_string()).legacy_id() < 0);
+        }
+    }
+}
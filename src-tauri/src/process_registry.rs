@@ -0,0 +1,287 @@
+// frpc 进程登记表
+//
+// 启动器崩溃或被更新程序重启时，内存中的 FrpcProcesses 会丢失，
+// 已经启动的 frpc 进程会变成无人管理的孤儿进程。这里把每个进程的
+// PID 等信息写入应用数据目录，下次启动时重新接管仍在运行的进程。
+
+use crate::app_context::AppContext;
+use crate::frpc_output::OutputFiles;
+use crate::json_store::{self, LockedFile};
+use crate::models::{FrpcProcess, FrpcProcesses, TunnelKey, TunnelState};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use sysinfo::{Pid, ProcessRefreshKind, System, UpdateKind};
+
+const REGISTRY_FILE: &str = "frpc_processes.json";
+
+/// 进程启动时间允许的误差（秒）
+const START_TIME_TOLERANCE_SECS: u64 = 2;
+
+/// 隧道类别
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TunnelKind {
+    Api,
+    Custom,
+}
+
+/// 登记的 frpc 进程信息
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ProcessRecord {
+    /// API 隧道的 ID，自定义隧道为兼容旧版的数字 ID
+    pub tunnel_id: i32,
+    pub kind: TunnelKind,
+    /// 自定义隧道的原始 ID
+    pub original_id: Option<String>,
+    pub pid: u32,
+    pub started_at: String,
+    /// 操作系统记录的进程启动时间（Unix 秒），用于排除 PID 被复用的情况
+    pub process_start_time: u64,
+    pub binary_path: String,
+    pub config_path: Option<String>,
+    /// frpc 输出文件，旧版本登记的进程输出到已经关闭的管道，没有这两项
+    #[serde(default)]
+    pub stdout_path: Option<String>,
+    #[serde(default)]
+    pub stderr_path: Option<String>,
+}
+
+impl ProcessRecord {
+    pub fn new(
+        tunnel_key: &TunnelKey,
+        pid: u32,
+        binary_path: &Path,
+        config_path: Option<&Path>,
+        output: &OutputFiles,
+    ) -> Self {
+        let (kind, original_id) = match tunnel_key {
+            TunnelKey::Api(_) => (TunnelKind::Api, None),
+            TunnelKey::Custom(name) => (TunnelKind::Custom, Some(name.clone())),
+        };
+        Self {
+            tunnel_id: tunnel_key.legacy_id(),
+            kind,
+            original_id,
+            pid,
+            started_at: chrono::Local::now().to_rfc3339(),
+            process_start_time: process_start_time(pid).unwrap_or(0),
+            binary_path: binary_path.to_string_lossy().to_string(),
+            config_path: config_path.map(|p| p.to_string_lossy().to_string()),
+            stdout_path: Some(output.stdout.to_string_lossy().to_string()),
+            stderr_path: Some(output.stderr.to_string_lossy().to_string()),
+        }
+    }
+
+    /// 旧版本登记的自定义隧道同样带有 original_id
+    pub fn key(&self) -> Option<TunnelKey> {
+        match (self.kind, &self.original_id) {
+            (TunnelKind::Api, _) => Some(TunnelKey::Api(self.tunnel_id)),
+            (TunnelKind::Custom, Some(name)) => Some(TunnelKey::Custom(name.clone())),
+            (TunnelKind::Custom, None) => None,
+        }
+    }
+
+    pub fn output_files(&self) -> Option<OutputFiles> {
+        match (&self.stdout_path, &self.stderr_path) {
+            (Some(stdout), Some(stderr)) => Some(OutputFiles {
+                stdout: PathBuf::from(stdout),
+                stderr: PathBuf::from(stderr),
+            }),
+            _ => None,
+        }
+    }
+
+    /// 进程是否正在使用该文件
+    pub fn uses_file(&self, path: &Path) -> bool {
+        [&self.config_path, &self.stdout_path, &self.stderr_path]
+            .into_iter()
+            .any(|p| p.as_deref().map(Path::new) == Some(path))
+    }
+}
+
+fn registry_path(app_handle: &AppContext) -> Result<PathBuf, String> {
+    Ok(app_handle.app_data_dir()?.join(REGISTRY_FILE))
+}
+
+// 登记表无法读取时按空表处理，不影响隧道启动
+fn load_records(file: &LockedFile) -> Vec<ProcessRecord> {
+    match file.read() {
+        Ok(records) => records.unwrap_or_default(),
+        Err(e) => {
+            eprintln!("[进程登记] {}", e);
+            Vec::new()
+        }
+    }
+}
+
+/// 登记新启动的进程，同一隧道的旧记录会被替换
+pub fn register(app_handle: &AppContext, record: ProcessRecord) -> Result<(), String> {
+    let path = registry_path(app_handle)?;
+    let file = json_store::lock(&path)?;
+
+    let mut records = load_records(&file);
+    let tunnel_key = record.key();
+    records.retain(|r| r.key() != tunnel_key);
+    records.push(record);
+    file.write(&records)
+}
+
+/// 移除隧道的登记记录
+pub fn unregister(app_handle: &AppContext, tunnel_key: &TunnelKey) -> Result<(), String> {
+    let path = registry_path(app_handle)?;
+    let file = json_store::lock(&path)?;
+
+    let mut records = load_records(&file);
+    let before = records.len();
+    records.retain(|r| r.key().as_ref() != Some(tunnel_key));
+    if records.len() == before {
+        return Ok(());
+    }
+    file.write(&records)
+}
+
+/// 隧道当前进程使用的配置文件
+pub fn config_path(app_handle: &AppContext, tunnel_key: &TunnelKey) -> Option<PathBuf> {
+    let file = json_store::lock(&registry_path(app_handle).ok()?).ok()?;
+    load_records(&file)
+        .into_iter()
+        .find(|r| r.key().as_ref() == Some(tunnel_key))
+        .and_then(|r| r.config_path)
+        .map(PathBuf::from)
+}
+
+/// 启动时重新接管上次登记且仍在运行的 frpc 进程，并清理失效记录
+pub fn readopt_processes(app_handle: &AppContext) -> Result<Vec<ProcessRecord>, String> {
+    let path = registry_path(app_handle)?;
+    let adopted: Vec<ProcessRecord> = {
+        let file = json_store::lock(&path)?;
+
+        let records = load_records(&file);
+        if records.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let live: Vec<ProcessRecord> = records
+            .into_iter()
+            .filter(record_matches_live_process)
+            .collect();
+        file.write(&live)?;
+        live
+    };
+
+    let app_dir = path.parent().unwrap_or(Path::new(""));
+    let processes = app_handle.state::<FrpcProcesses>();
+    let mut procs = processes
+        .processes
+        .lock()
+        .map_err(|e| format!("获取进程锁失败: {}", e))?;
+
+    for record in &adopted {
+        let tunnel_key = match record.key() {
+            Some(tunnel_key) => tunnel_key,
+            None => continue,
+        };
+        if procs.contains_key(&tunnel_key) {
+            continue;
+        }
+        eprintln!(
+            "[进程登记] 重新接管隧道 {} 的 frpc 进程 (PID: {})",
+            tunnel_key, record.pid
+        );
+        let process = FrpcProcess::adopted(record.pid, record.process_start_time);
+        let config_file = record
+            .config_path
+            .as_ref()
+            .map(PathBuf::from)
+            .filter(|path| crate::run_config::is_run_config(app_dir, path));
+        // 接管前的日志不会重新读取，按上次运行时已连接处理
+        crate::commands::tunnel_state::set_tunnel_state(
+            app_handle,
+            &tunnel_key,
+            TunnelState::Connected,
+        );
+        crate::commands::process::spawn_adopted_watcher(
+            app_handle.clone(),
+            tunnel_key.clone(),
+            &process,
+            config_file,
+            record.output_files(),
+        );
+        procs.insert(tunnel_key, process);
+    }
+
+    Ok(adopted)
+}
+
+/// 检查登记的 PID 是否仍是当初启动的那个 frpc 进程
+fn record_matches_live_process(record: &ProcessRecord) -> bool {
+    let mut system = System::new();
+    let pid = Pid::from_u32(record.pid);
+    if !system
+        .refresh_process_specifics(pid, ProcessRefreshKind::new().with_exe(UpdateKind::Always))
+    {
+        return false;
+    }
+    let process = match system.process(pid) {
+        Some(process) => process,
+        None => return false,
+    };
+
+    let exe_matches = match process.exe() {
+        Some(exe) => same_path(exe, Path::new(&record.binary_path)),
+        // 无权读取可执行文件路径时退回到进程名比较
+        None => process.name().to_lowercase().starts_with("frpc"),
+    };
+    if !exe_matches {
+        return false;
+    }
+
+    start_time_matches(process.start_time(), record.process_start_time)
+}
+
+// 旧版本登记时可能没有取到启动时间，此时不比较
+fn start_time_matches(actual: u64, recorded: u64) -> bool {
+    recorded == 0 || actual.abs_diff(recorded) <= START_TIME_TOLERANCE_SECS
+}
+
+fn same_path(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+fn process_start_time(pid: u32) -> Option<u64> {
+    let mut system = System::new();
+    let pid = Pid::from_u32(pid);
+    if !system.refresh_process_specifics(pid, ProcessRefreshKind::new()) {
+        return None;
+    }
+    system.process(pid).map(|p| p.start_time())
+}
+
+/// 检查接管的进程是否仍在运行，启动时间不同说明 PID 已被其他进程复用
+pub fn is_frpc_alive(pid: u32, start_time: u64) -> bool {
+    let mut system = System::new();
+    let pid = Pid::from_u32(pid);
+    if !system.refresh_process_specifics(pid, ProcessRefreshKind::new()) {
+        return false;
+    }
+    system
+        .process(pid)
+        .map(|p| {
+            p.name().to_lowercase().starts_with("frpc")
+                && start_time_matches(p.start_time(), start_time)
+        })
+        .unwrap_or(false)
+}
+
+/// 强制结束接管的进程
+pub fn kill_pid(pid: u32) -> bool {
+    let mut system = System::new();
+    let pid = Pid::from_u32(pid);
+    if !system.refresh_process_specifics(pid, ProcessRefreshKind::new()) {
+        return false;
+    }
+    system.process(pid).map(|p| p.kill()).unwrap_or(false)
+}
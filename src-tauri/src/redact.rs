@@ -0,0 +1,339 @@
+// 日志脱敏
+//
+// frpc 的输出可能带有用户令牌和配置中的密钥：服务器令牌、stcp/xtcp 的 sk、
+// 元数据和插件密码等。这里从凭据存储和隧道配置中收集密钥，用 Aho-Corasick
+// 自动机一次扫描找出全部出现位置，重叠或相邻的位置合并后替换为 ***。
+// 日志被截断时密钥可能只输出一部分，较长的密钥同时匹配足够长的前缀。
+//
+// 日志在写入缓冲区、发送事件和写入文件之前脱敏，读取和导出日志文件时再脱敏
+// 一次，覆盖升级前写入的日志。
+
+use crate::app_context::AppContext;
+use crate::frpc_config::{parse_document, strip_bom, ConfigFormat};
+use crate::ini::IniDocument;
+use crate::models::TunnelKey;
+use aho_corasick::AhoCorasick;
+use serde_json::Value;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::PathBuf;
+
+/// 替换密钥的文本
+pub const REDACTED: &str = "***";
+
+/// 短于该长度的值不当作密钥，避免把端口、布尔值等常见内容替换掉
+const MIN_SECRET_LEN: usize = 6;
+
+/// 密钥的前缀达到该长度时同样替换，处理日志中被截断的密钥
+const MIN_PARTIAL_LEN: usize = 8;
+
+/// INI 配置中的密钥，比较时忽略大小写
+const INI_SECRET_KEYS: &[&str] = &[
+    "token",
+    "sk",
+    "http_pwd",
+    "plugin_http_passwd",
+    "plugin_passwd",
+    "oidc_client_secret",
+];
+
+/// INI 配置中以这些前缀开头的键都是元数据
+const INI_SECRET_PREFIXES: &[&str] = &["meta_"];
+
+/// TOML、YAML 和 JSON 配置中的密钥
+const STRUCTURED_SECRET_KEYS: &[&str] = &[
+    "token",
+    "secretKey",
+    "httpPassword",
+    "password",
+    "clientSecret",
+];
+
+/// TOML、YAML 和 JSON 配置中其下的值都是元数据
+const STRUCTURED_SECRET_MAPS: &[&str] = &["metadatas", "metas"];
+
+/// 一组密钥的匹配器
+pub struct Redactor {
+    matcher: Option<AhoCorasick>,
+}
+
+impl Redactor {
+    pub fn new<I, S>(secrets: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut patterns = BTreeSet::new();
+        for secret in secrets {
+            let secret = secret.as_ref().trim();
+            if secret.len() < MIN_SECRET_LEN {
+                continue;
+            }
+            patterns.insert(secret.to_string());
+
+            // 令牌形如 <前半>.<后半>，两部分可能被分开输出
+            for part in secret.split('.') {
+                if part.len() >= MIN_SECRET_LEN {
+                    patterns.insert(part.to_string());
+                }
+            }
+
+            for (end, _) in secret.char_indices().skip(MIN_PARTIAL_LEN) {
+                patterns.insert(secret[..end].to_string());
+            }
+        }
+
+        let matcher = if patterns.is_empty() {
+            None
+        } else {
+            match AhoCorasick::new(&patterns) {
+                Ok(matcher) => Some(matcher),
+                Err(e) => {
+                    eprintln!("[脱敏] 创建匹配器失败: {}", e);
+                    None
+                }
+            }
+        };
+        Self { matcher }
+    }
+
+    /// 替换文本中出现的全部密钥
+    pub fn redact(&self, text: &str) -> String {
+        let matcher = match &self.matcher {
+            Some(matcher) => matcher,
+            None => return text.to_string(),
+        };
+
+        // 找出所有位置，包括互相重叠的，合并后整体替换，避免漏出重叠部分
+        let mut spans: Vec<(usize, usize)> = matcher
+            .find_overlapping_iter(text)
+            .map(|m| (m.start(), m.end()))
+            .collect();
+        if spans.is_empty() {
+            return text.to_string();
+        }
+        spans.sort_unstable();
+
+        let mut result = String::with_capacity(text.len());
+        let mut copied = 0;
+        let mut current = spans[0];
+        for &(start, end) in &spans[1..] {
+            if start <= current.1 {
+                current.1 = current.1.max(end);
+            } else {
+                result.push_str(&text[copied..current.0]);
+                result.push_str(REDACTED);
+                copied = current.1;
+                current = (start, end);
+            }
+        }
+        result.push_str(&text[copied..current.0]);
+        result.push_str(REDACTED);
+        result.push_str(&text[current.1..]);
+        result
+    }
+}
+
+/// 从配置内容中收集密钥，无法解析的配置不返回任何内容
+pub fn config_secrets(content: &str, format: ConfigFormat) -> Vec<String> {
+    let content = strip_bom(content);
+    let mut secrets = Vec::new();
+    match format {
+        ConfigFormat::Ini => {
+            let document = IniDocument::parse(content);
+            let entries = document
+                .orphans()
+                .iter()
+                .chain(document.sections().iter().flat_map(|s| s.entries.iter()));
+            for entry in entries {
+                let key = entry.key.to_ascii_lowercase();
+                if INI_SECRET_KEYS.contains(&key.as_str())
+                    || INI_SECRET_PREFIXES.iter().any(|p| key.starts_with(p))
+                {
+                    secrets.push(entry.value.clone());
+                }
+            }
+        }
+        _ => {
+            if let Ok(document) = parse_document(content, format) {
+                for (key, value) in &document {
+                    collect_structured(key, value, &mut secrets);
+                }
+            }
+        }
+    }
+    secrets
+}
+
+fn collect_structured(key: &str, value: &Value, secrets: &mut Vec<String>) {
+    if STRUCTURED_SECRET_MAPS.contains(&key) {
+        collect_strings(value, secrets);
+        return;
+    }
+    match value {
+        Value::String(text) if STRUCTURED_SECRET_KEYS.contains(&key) => {
+            secrets.push(text.clone());
+        }
+        Value::Object(map) => {
+            for (key, value) in map {
+                collect_structured(key, value, secrets);
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                collect_structured(key, item, secrets);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn collect_strings(value: &Value, secrets: &mut Vec<String>) {
+    match value {
+        Value::String(text) => secrets.push(text.clone()),
+        Value::Object(map) => map.values().for_each(|v| collect_strings(v, secrets)),
+        Value::Array(items) => items.iter().for_each(|v| collect_strings(v, secrets)),
+        _ => {}
+    }
+}
+
+/// 隧道的匹配器：已保存的用户令牌和隧道当前配置中的密钥
+pub fn tunnel_redactor(app_handle: &AppContext, tunnel_key: &TunnelKey) -> Redactor {
+    let mut secrets = Vec::new();
+    match crate::credentials::load_token(app_handle) {
+        Ok(Some(token)) => secrets.push(token),
+        Ok(None) => {}
+        Err(e) => eprintln!("[脱敏] 读取用户令牌失败: {}", e),
+    }
+
+    if let Some(path) = tunnel_config_path(app_handle, tunnel_key) {
+        if let Ok(content) = fs::read_to_string(&path) {
+            secrets.extend(config_secrets(&content, ConfigFormat::from_path(&path)));
+        }
+    }
+    Redactor::new(secrets)
+}
+
+// API 隧道使用进程登记的配置文件，自定义隧道使用保存的配置文件
+fn tunnel_config_path(app_handle: &AppContext, tunnel_key: &TunnelKey) -> Option<PathBuf> {
+    match tunnel_key {
+        TunnelKey::Api(_) => crate::process_registry::config_path(app_handle, tunnel_key),
+        TunnelKey::Custom(tunnel_id) => {
+            crate::commands::custom_tunnel::custom_tunnel_config_path(app_handle, tunnel_id).ok()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redacts_every_occurrence() {
+        let redactor = Redactor::new(["s3cretValue"]);
+        assert_eq!(
+            redactor.redact("a s3cretValue b s3cretValue"),
+            "a *** b ***"
+        );
+    }
+
+    #[test]
+    fn leaves_text_without_secrets_unchanged() {
+        let redactor = Redactor::new(["s3cretValue"]);
+        assert_eq!(redactor.redact("nothing to hide"), "nothing to hide");
+        assert_eq!(Redactor::new(Vec::<String>::new()).redact("abc"), "abc");
+    }
+
+    #[test]
+    fn ignores_short_values() {
+        let redactor = Redactor::new(["7000", "true", ""]);
+        assert_eq!(redactor.redact("port 7000 tls true"), "port 7000 tls true");
+    }
+
+    #[test]
+    fn redacts_truncated_secret() {
+        let redactor = Redactor::new(["abcdefghijklmnop"]);
+        assert_eq!(redactor.redact("token=abcdefghij..."), "token=***...");
+        // 短于 MIN_PARTIAL_LEN 的前缀不替换
+        assert_eq!(redactor.redact("abcdefg"), "abcdefg");
+    }
+
+    #[test]
+    fn redacts_token_parts_separately() {
+        let redactor = Redactor::new(["abc123def.xyz789uvw"]);
+        assert_eq!(redactor.redact("user xyz789uvw login"), "user *** login");
+        assert_eq!(redactor.redact("abc123def.xyz789uvw"), "***");
+    }
+
+    #[test]
+    fn merges_overlapping_matches() {
+        let redactor = Redactor::new(["abcdef123", "123xyzuvw"]);
+        assert_eq!(redactor.redact("[abcdef123xyzuvw]"), "[***]");
+    }
+
+    #[test]
+    fn merges_nested_and_adjacent_matches() {
+        let redactor = Redactor::new(["secret-outer-value", "outer-"]);
+        assert_eq!(redactor.redact("x secret-outer-value y"), "x *** y");
+
+        let redactor = Redactor::new(["aaaaaa", "bbbbbb"]);
+        assert_eq!(redactor.redact("aaaaaabbbbbb"), "***");
+    }
+
+    #[test]
+    fn handles_multibyte_text() {
+        let redactor = Redactor::new(["密钥密钥abc"]);
+        assert_eq!(redactor.redact("登录 密钥密钥abc 成功"), "登录 *** 成功");
+    }
+
+    #[test]
+    fn collects_ini_secrets() {
+        let content = "[common]\nserver_addr = example.com\ntoken = server-token\n\n\
+                       [ssh]\ntype = stcp\nsk = visitor-secret\nmeta_owner = alice-meta\n\
+                       plugin_http_passwd = plugin-pass\nhttp_pwd = http-pass\nlocal_port = 22\n";
+        let mut secrets = config_secrets(content, ConfigFormat::Ini);
+        secrets.sort();
+        assert_eq!(
+            secrets,
+            [
+                "alice-meta",
+                "http-pass",
+                "plugin-pass",
+                "server-token",
+                "visitor-secret"
+            ]
+        );
+    }
+
+    #[test]
+    fn collects_structured_secrets() {
+        let content = r#"
+serverAddr = "example.com"
+auth.token = "server-token"
+metadatas.owner = "alice-meta"
+
+[[proxies]]
+name = "web"
+type = "http"
+httpPassword = "http-pass"
+metas.team = "team-meta"
+
+[[visitors]]
+name = "ssh"
+type = "stcp"
+secretKey = "visitor-secret"
+"#;
+        let mut secrets = config_secrets(content, ConfigFormat::Toml);
+        secrets.sort();
+        assert_eq!(
+            secrets,
+            [
+                "alice-meta",
+                "http-pass",
+                "server-token",
+                "team-meta",
+                "visitor-secret"
+            ]
+        );
+    }
+}
@@ -0,0 +1,174 @@
+// API 隧道的单次运行配置文件
+//
+// 以 -u <令牌> 启动 frpc 时，本机的其他用户可以通过 ps 或 /proc/<pid>/cmdline
+// 看到令牌。配置文件模式下先从 ChmlFrp API 获取隧道的 frpc 配置，写入应用数据
+// 目录 run 子目录中只有当前用户可读的文件，再以 -c 启动 frpc。每次启动都会生成
+// 新文件，进程退出后删除；启动器异常退出留下的文件在下次启动时清理。
+// frpc verify 检查自定义隧道配置时使用的临时文件和 frpc 的输出文件（见
+// frpc_output）同样写在这里。
+
+use crate::models::TunnelKey;
+use crate::process_registry::ProcessRecord;
+use serde::Deserialize;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// ChmlFrp API 地址，与前端一致
+const API_BASE_URL: &str = "https://cf-v2.uapis.cn";
+
+const RUN_CONFIG_DIR: &str = "run";
+
+#[derive(Deserialize)]
+struct ApiResponse<T> {
+    code: i32,
+    msg: Option<String>,
+    data: Option<T>,
+}
+
+#[derive(Deserialize)]
+struct ApiTunnel {
+    id: i32,
+    name: String,
+    node: String,
+}
+
+/// 从 ChmlFrp API 获取隧道的 frpc 配置
+pub async fn fetch_api_tunnel_config(user_token: &str, tunnel_id: i32) -> Result<String, String> {
+    let client = reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(30))
+        .user_agent("ChmlFrpLauncher/1.0")
+        .build()
+        .map_err(|e| format!("创建请求失败: {}", e))?;
+
+    let tunnels: Vec<ApiTunnel> = api_get(&client, user_token, "/tunnel", &[]).await?;
+    let tunnel = tunnels
+        .into_iter()
+        .find(|tunnel| tunnel.id == tunnel_id)
+        .ok_or_else(|| format!("未找到隧道 {}", tunnel_id))?;
+
+    api_get(
+        &client,
+        user_token,
+        "/tunnel_config",
+        &[
+            ("node", tunnel.node.as_str()),
+            ("tunnel_names", tunnel.name.as_str()),
+        ],
+    )
+    .await
+}
+
+async fn api_get<T: serde::de::DeserializeOwned>(
+    client: &reqwest::Client,
+    user_token: &str,
+    endpoint: &str,
+    query: &[(&str, &str)],
+) -> Result<T, String> {
+    let response = client
+        .get(format!("{}{}", API_BASE_URL, endpoint))
+        .bearer_auth(user_token)
+        .query(query)
+        .send()
+        .await
+        .map_err(|e| format!("获取隧道配置失败: {}", e))?;
+    let body: ApiResponse<T> = response
+        .json()
+        .await
+        .map_err(|e| format!("解析隧道配置失败: {}", e))?;
+
+    match body {
+        ApiResponse {
+            code: 200,
+            data: Some(data),
+            ..
+        } => Ok(data),
+        ApiResponse { msg, .. } => Err(format!(
+            "获取隧道配置失败: {}",
+            msg.unwrap_or_else(|| "请求失败".to_string())
+        )),
+    }
+}
+
+/// 写入单次运行的配置文件
+pub fn write(app_dir: &Path, tunnel_key: &TunnelKey, content: &str) -> Result<PathBuf, String> {
+    write_named(
+        app_dir,
+        &format!("{}-{}.ini", tunnel_key, timestamp_millis()),
+        content,
+    )
+}
+
+/// 以指定文件名写入 run 目录，Unix 系统上只有当前用户可以读写。
+/// Windows 上应用数据目录位于用户目录中，默认只有当前用户和管理员可以访问
+pub fn write_named(app_dir: &Path, file_name: &str, content: &str) -> Result<PathBuf, String> {
+    let (path, mut file) = create_named(app_dir, file_name)?;
+    if let Err(e) = file.write_all(content.as_bytes()) {
+        drop(file);
+        remove(&path);
+        return Err(format!("写入配置文件失败: {}", e));
+    }
+    Ok(path)
+}
+
+/// 在 run 目录中创建新文件并以追加方式打开，权限与 write_named 相同
+pub fn create_named(app_dir: &Path, file_name: &str) -> Result<(PathBuf, fs::File), String> {
+    let dir = app_dir.join(RUN_CONFIG_DIR);
+    fs::create_dir_all(&dir).map_err(|e| format!("创建目录失败: {}", e))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let _ = fs::set_permissions(&dir, fs::Permissions::from_mode(0o700));
+    }
+
+    let path = dir.join(file_name);
+
+    let mut options = fs::OpenOptions::new();
+    options.append(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let file = options
+        .open(&path)
+        .map_err(|e| format!("创建文件 {} 失败: {}", file_name, e))?;
+    Ok((path, file))
+}
+
+/// 当前时间的毫秒数，用于生成不重复的文件名
+pub fn timestamp_millis() -> u128 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or_default()
+}
+
+/// 删除单次运行的配置文件
+pub fn remove(path: &Path) {
+    match fs::remove_file(path) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => eprintln!("[运行配置] 删除 {} 失败: {}", path.display(), e),
+    }
+}
+
+/// 路径是否为单次运行的配置文件
+pub fn is_run_config(app_dir: &Path, path: &Path) -> bool {
+    path.parent() == Some(app_dir.join(RUN_CONFIG_DIR).as_path())
+}
+
+/// 删除不属于任何运行中进程的文件，启动时在重新接管进程后调用
+pub fn remove_stale(app_dir: &Path, adopted: &[ProcessRecord]) {
+    let entries = match fs::read_dir(app_dir.join(RUN_CONFIG_DIR)) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let in_use = adopted.iter().any(|record| record.uses_file(&path));
+        if !in_use {
+            remove(&path);
+        }
+    }
+}
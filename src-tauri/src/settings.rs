@@ -0,0 +1,224 @@
+// 应用设置
+//
+// 设置统一保存在应用数据目录的 settings.json 中，启动时读入 SettingsState，
+// 命令读写内存中的副本，修改后写回文件并发送 settings-changed 事件。
+// 文件中的 version 记录设置结构的版本，旧版本的设置按 MIGRATIONS 逐个升级：
+// 第一次运行时导入之前分散保存的 auto_start_tunnels.json 和
+// tunnel_auto_start.json，旧文件保留不动，降级后旧版本仍然可以读取。
+
+use crate::app_context::AppContext;
+use crate::json_store;
+use crate::models::{GuardConfig, TunnelKey};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+pub const SETTINGS_FILE: &str = "settings.json";
+
+/// 设置结构的当前版本
+pub const SETTINGS_VERSION: u32 = 1;
+
+/// 设置修改后发送的事件，负载为修改后的完整设置
+pub const SETTINGS_CHANGED_EVENT: &str = "settings-changed";
+
+/// 旧版的全局自动启动开关，内容为 {"enabled": bool}
+const LEGACY_AUTO_START_TUNNELS_FILE: &str = "auto_start_tunnels.json";
+
+/// 旧版的隧道自动启动列表，内容为 {"api_123": true, "custom_name": false}
+const LEGACY_TUNNEL_AUTO_START_FILE: &str = "tunnel_auto_start.json";
+
+/// 把设置文件升级一个版本，可以读取应用数据目录中的其他文件
+type Migration = fn(&mut Map<String, Value>, &Path);
+
+/// 按版本排列的迁移，第 i 项把版本 i 的设置升级到版本 i + 1
+const MIGRATIONS: &[Migration] = &[import_legacy_files];
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(default)]
+pub struct Settings {
+    pub version: u32,
+    /// 启动软件时自动启动隧道
+    pub auto_start_tunnels: bool,
+    /// 标记为自动启动的隧道
+    pub tunnel_auto_start: BTreeSet<TunnelKey>,
+    /// 进程守护是否启用
+    pub process_guard_enabled: bool,
+    /// 进程守护的重启策略
+    pub guard_config: GuardConfig,
+    /// 设置为从不守护的隧道
+    pub never_guard: BTreeSet<TunnelKey>,
+    /// API 隧道的启动方式
+    pub api_launch_mode: ApiLaunchMode,
+    /// 本地控制接口
+    pub control_api: ControlApiSettings,
+}
+
+/// API 隧道的启动方式
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ApiLaunchMode {
+    /// 获取隧道配置写入单次运行的配置文件，以 -c 启动 frpc
+    #[default]
+    ConfigFile,
+    /// 以 -u <令牌> -p <隧道 ID> 启动 frpc，令牌会出现在进程命令行中
+    CommandLine,
+}
+
+/// 本地控制接口的默认监听地址
+pub const DEFAULT_CONTROL_API_LISTEN: &str = "127.0.0.1:7789";
+
+/// 本地控制接口，供脚本和命令行管理隧道
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(default)]
+pub struct ControlApiSettings {
+    pub enabled: bool,
+    /// 127.0.0.1:<端口>、[::1]:<端口> 或 unix:<套接字路径>
+    pub listen: String,
+}
+
+impl Default for ControlApiSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            listen: DEFAULT_CONTROL_API_LISTEN.to_string(),
+        }
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            version: SETTINGS_VERSION,
+            auto_start_tunnels: false,
+            tunnel_auto_start: BTreeSet::new(),
+            process_guard_enabled: false,
+            guard_config: GuardConfig::default(),
+            never_guard: BTreeSet::new(),
+            api_launch_mode: ApiLaunchMode::default(),
+            control_api: ControlApiSettings::default(),
+        }
+    }
+}
+
+/// 内存中的设置，作为 Tauri 状态管理
+pub struct SettingsState {
+    path: PathBuf,
+    settings: Mutex<Settings>,
+}
+
+impl SettingsState {
+    /// 读取设置并执行迁移，文件无法读取时使用默认设置
+    pub fn load(app_dir: &Path) -> Self {
+        let path = app_dir.join(SETTINGS_FILE);
+        let settings = match load_settings(&path, app_dir) {
+            Ok(settings) => settings,
+            Err(e) => {
+                eprintln!("[设置] 读取设置失败，使用默认设置: {}", e);
+                Settings::default()
+            }
+        };
+        Self {
+            path,
+            settings: Mutex::new(settings),
+        }
+    }
+
+    pub fn get(&self) -> Settings {
+        match self.settings.lock() {
+            Ok(settings) => settings.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+
+    /// 修改设置，内容有变化时保存并发送 settings-changed 事件
+    pub fn update<R>(
+        &self,
+        app_handle: &AppContext,
+        update: impl FnOnce(&mut Settings) -> R,
+    ) -> Result<R, String> {
+        let (result, updated) = {
+            let mut settings = self
+                .settings
+                .lock()
+                .map_err(|e| format!("获取设置锁失败: {}", e))?;
+            let mut next = settings.clone();
+            let result = update(&mut next);
+            next.version = SETTINGS_VERSION;
+            if next == *settings {
+                return Ok(result);
+            }
+            json_store::write_json(&self.path, &next)?;
+            *settings = next.clone();
+            (result, next)
+        };
+
+        if let Err(e) = app_handle.emit(SETTINGS_CHANGED_EVENT, &updated) {
+            eprintln!("[设置] 发送设置变更事件失败: {}", e);
+        }
+        Ok(result)
+    }
+}
+
+fn load_settings(path: &Path, app_dir: &Path) -> Result<Settings, String> {
+    let mut document: Map<String, Value> = json_store::read_json(path)?.unwrap_or_default();
+    let version = document.get("version").and_then(Value::as_u64).unwrap_or(0) as usize;
+
+    if version > SETTINGS_VERSION as usize {
+        eprintln!(
+            "[设置] 设置文件版本 {} 高于当前支持的版本 {}，未知的设置项会被忽略",
+            version, SETTINGS_VERSION
+        );
+    }
+
+    let pending = MIGRATIONS.get(version..).unwrap_or_default();
+    for (offset, migrate) in pending.iter().enumerate() {
+        migrate(&mut document, app_dir);
+        eprintln!("[设置] 已升级到版本 {}", version + offset + 1);
+    }
+
+    let mut settings: Settings = serde_json::from_value(Value::Object(document))
+        .map_err(|e| format!("解析设置失败: {}", e))?;
+    settings.version = SETTINGS_VERSION;
+
+    if !pending.is_empty() {
+        json_store::write_json(path, &settings)?;
+    }
+    Ok(settings)
+}
+
+// 版本 0 -> 1：导入旧版分散保存的自动启动设置
+fn import_legacy_files(document: &mut Map<String, Value>, app_dir: &Path) {
+    let read = |name: &str| match json_store::read_json::<Value>(&app_dir.join(name)) {
+        Ok(value) => value,
+        Err(e) => {
+            eprintln!("[设置] 导入 {} 失败: {}", name, e);
+            None
+        }
+    };
+
+    if let Some(enabled) = read(LEGACY_AUTO_START_TUNNELS_FILE)
+        .and_then(|config| config.get("enabled").and_then(Value::as_bool))
+    {
+        document.insert("auto_start_tunnels".to_string(), Value::Bool(enabled));
+    }
+
+    if let Some(Value::Object(config)) = read(LEGACY_TUNNEL_AUTO_START_FILE) {
+        let mut keys = BTreeSet::new();
+        for (key, enabled) in config {
+            if enabled.as_bool() != Some(true) {
+                continue;
+            }
+            match TunnelKey::try_from(key.clone()) {
+                Ok(tunnel_key) => {
+                    keys.insert(tunnel_key);
+                }
+                Err(e) => eprintln!("[设置] 跳过无法识别的自动启动隧道 {}: {}", key, e),
+            }
+        }
+        if let Ok(keys) = serde_json::to_value(keys) {
+            document.insert("tunnel_auto_start".to_string(), keys);
+        }
+    }
+}
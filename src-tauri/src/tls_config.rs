@@ -0,0 +1,78 @@
+// 配置文件中的 TLS 设置
+//
+// 部分节点要求客户端启用 TLS，配置文件中 tls_enable = false 时 frpc 会出现
+// i/o timeout 或 TLS 握手失败。这里把 [common] 中的 tls_enable 改为 true，
+// 兼容 "tls_enable=false"、多余空格、":" 分隔符和大小写不同的写法，
+// 缺少该项时补在 [common] 的最后一项之后。写回时先写临时文件再替换，
+// 写入中断不会留下截断的配置。
+
+use crate::frpc_config::COMMON_SECTION;
+use crate::ini::IniDocument;
+use crate::json_store;
+use std::fs;
+use std::path::Path;
+
+const TLS_KEY: &str = "tls_enable";
+
+/// 返回启用 TLS 后的配置内容，已经启用时返回 None
+pub fn enable_tls(content: &str) -> Option<String> {
+    let mut document = IniDocument::parse(content);
+
+    let common: Vec<usize> = document
+        .sections()
+        .iter()
+        .enumerate()
+        .filter(|(_, section)| section.name.eq_ignore_ascii_case(COMMON_SECTION))
+        .map(|(index, _)| index)
+        .collect();
+    let entries: Vec<(usize, bool)> = common
+        .iter()
+        .flat_map(|&index| &document.sections()[index].entries)
+        .filter(|entry| entry.key.eq_ignore_ascii_case(TLS_KEY))
+        .map(|entry| (entry.line, is_true(&entry.value)))
+        .collect();
+
+    if !entries.is_empty() {
+        // 只替换值，保留键名、分隔符两侧的空格和引号
+        let mut changed = false;
+        for (line, enabled) in entries {
+            if !enabled {
+                changed |= document.set_value(line, "true");
+            }
+        }
+        return changed.then(|| document.to_string());
+    }
+
+    match common.last() {
+        Some(&index) => document.insert_entry(index, TLS_KEY, "true"),
+        // 没有 [common] 时在开头补上
+        None => document.prepend_section(COMMON_SECTION, &[(TLS_KEY, "true")]),
+    }
+    Some(document.to_string())
+}
+
+/// 启用配置文件中的 TLS，修改了文件时返回修改前的内容，用于回滚
+pub fn enable_tls_in_config(config_path: &Path) -> Result<Option<String>, String> {
+    let content =
+        fs::read_to_string(config_path).map_err(|e| format!("读取配置文件失败: {}", e))?;
+
+    match enable_tls(&content) {
+        Some(modified) => {
+            json_store::write_file(config_path, modified.as_bytes())?;
+            Ok(Some(content))
+        }
+        None => Ok(None),
+    }
+}
+
+/// 写回修改前的配置
+pub fn restore_config(config_path: &Path, original: &str) -> Result<(), String> {
+    json_store::write_file(config_path, original.as_bytes())
+        .map_err(|e| format!("恢复配置文件失败: {}", e))
+}
+
+fn is_true(value: &str) -> bool {
+    ["true", "1", "yes", "on"]
+        .iter()
+        .any(|v| value.trim().eq_ignore_ascii_case(v))
+}
@@ -0,0 +1,540 @@
+// 隧道日志文件
+//
+// frpc 的输出除了通过 frpc-log 事件发给前端外，还会写入应用数据目录下
+// logs/<隧道>/frpc.log，窗口重新加载或启动器重启后仍可查看。当前文件超过
+// 大小或时间限制后会被重命名为 frpc-<时间>.log，旧文件按数量和天数清理。
+// 目录名由隧道标识生成：API 隧道为 tunnel_<ID>，自定义隧道为 custom_ 加上
+// 百分号编码后的名称，不同名称的目录不会重复，也能还原出隧道标识。
+
+use crate::app_context::AppContext;
+use crate::control_http::{percent_decode, percent_encode};
+
+use crate::models::TunnelKey;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+const LOGS_DIR: &str = "logs";
+const ACTIVE_LOG_FILE: &str = "frpc.log";
+const ROTATED_PREFIX: &str = "frpc-";
+const LOG_EXTENSION: &str = ".log";
+
+/// 日志文件轮转和保留策略
+#[derive(Clone, Debug)]
+pub struct LogFileConfig {
+    /// 单个文件的最大字节数
+    pub max_file_size: u64,
+    /// 单个文件最长写入时间
+    pub max_file_age: Duration,
+    /// 每个隧道最多保留的历史文件数
+    pub max_rotated_files: usize,
+    /// 历史文件最长保留时间
+    pub retention: Duration,
+}
+
+impl Default for LogFileConfig {
+    fn default() -> Self {
+        Self {
+            max_file_size: 5 * 1024 * 1024,
+            max_file_age: Duration::from_secs(24 * 60 * 60),
+            max_rotated_files: 10,
+            retention: Duration::from_secs(7 * 24 * 60 * 60),
+        }
+    }
+}
+
+/// 日志文件信息
+#[derive(Serialize, Clone, Debug)]
+pub struct TunnelLogFileInfo {
+    pub tunnel_key: TunnelKey,
+    pub file_name: String,
+    pub size: u64,
+    pub modified: String,
+    /// 是否为正在写入的文件
+    pub active: bool,
+}
+
+/// 单个隧道的日志写入器，文件在第一次写入时打开
+pub struct TunnelLogWriter {
+    dir: PathBuf,
+    config: LogFileConfig,
+    file: Option<File>,
+    size: u64,
+    opened_at: SystemTime,
+    // 写入失败只提示一次，避免每行日志都刷屏
+    failed: bool,
+}
+
+impl TunnelLogWriter {
+    fn new(dir: PathBuf, config: LogFileConfig) -> Self {
+        Self {
+            dir,
+            config,
+            file: None,
+            size: 0,
+            opened_at: SystemTime::now(),
+            failed: false,
+        }
+    }
+
+    pub fn append_line(&mut self, line: &str) {
+        match self.write_line(line) {
+            Ok(()) => self.failed = false,
+            Err(e) => {
+                if !self.failed {
+                    eprintln!("[日志文件] 写入 {} 失败: {}", self.dir.display(), e);
+                }
+                self.failed = true;
+                self.file = None;
+            }
+        }
+    }
+
+    fn write_line(&mut self, line: &str) -> Result<(), String> {
+        if self.file.is_none() {
+            self.open()?;
+        }
+        if self.needs_rotation() {
+            self.rotate()?;
+        }
+
+        let file = match self.file.as_mut() {
+            Some(file) => file,
+            None => return Err("日志文件未打开".to_string()),
+        };
+        let mut bytes = line.as_bytes().to_vec();
+        bytes.push(b'\n');
+        file.write_all(&bytes)
+            .map_err(|e| format!("写入日志文件失败: {}", e))?;
+        self.size += bytes.len() as u64;
+        Ok(())
+    }
+
+    fn open(&mut self) -> Result<(), String> {
+        fs::create_dir_all(&self.dir).map_err(|e| format!("创建日志目录失败: {}", e))?;
+        let path = self.dir.join(ACTIVE_LOG_FILE);
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|e| format!("打开日志文件失败: {}", e))?;
+        let metadata = file
+            .metadata()
+            .map_err(|e| format!("读取日志文件信息失败: {}", e))?;
+
+        self.size = metadata.len();
+        // 部分文件系统不支持创建时间，此时从本次打开开始计时
+        self.opened_at = if metadata.len() > 0 {
+            metadata.created().unwrap_or_else(|_| SystemTime::now())
+        } else {
+            SystemTime::now()
+        };
+        self.file = Some(file);
+        Ok(())
+    }
+
+    fn needs_rotation(&self) -> bool {
+        if self.size == 0 {
+            return false;
+        }
+        let age = SystemTime::now()
+            .duration_since(self.opened_at)
+            .unwrap_or_default();
+        self.size >= self.config.max_file_size || age >= self.config.max_file_age
+    }
+
+    fn rotate(&mut self) -> Result<(), String> {
+        self.file = None;
+        let active = self.dir.join(ACTIVE_LOG_FILE);
+        let stamp = chrono::Local::now().format("%Y%m%d-%H%M%S").to_string();
+        let mut target = self
+            .dir
+            .join(format!("{}{}{}", ROTATED_PREFIX, stamp, LOG_EXTENSION));
+        let mut suffix = 1;
+        while target.exists() {
+            target = self.dir.join(format!(
+                "{}{}-{}{}",
+                ROTATED_PREFIX, stamp, suffix, LOG_EXTENSION
+            ));
+            suffix += 1;
+        }
+        fs::rename(&active, &target).map_err(|e| format!("轮转日志文件失败: {}", e))?;
+        prune_rotated_files(&self.dir, &self.config);
+        self.open()
+    }
+
+    /// 关闭文件句柄，下次写入时重新打开
+    pub fn close(&mut self) {
+        self.file = None;
+    }
+}
+
+/// 所有隧道的日志写入器
+pub struct TunnelLogFiles {
+    config: LogFileConfig,
+    writers: Mutex<HashMap<TunnelKey, Arc<Mutex<TunnelLogWriter>>>>,
+}
+
+impl TunnelLogFiles {
+    pub fn new() -> Self {
+        Self {
+            config: LogFileConfig::default(),
+            writers: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 获取隧道的日志写入器，不存在时创建
+    pub fn writer(
+        &self,
+        app_handle: &AppContext,
+        tunnel_key: &TunnelKey,
+    ) -> Result<Arc<Mutex<TunnelLogWriter>>, String> {
+        let dir = tunnel_log_dir(app_handle, tunnel_key)?;
+        let mut writers = self
+            .writers
+            .lock()
+            .map_err(|e| format!("获取日志写入器锁失败: {}", e))?;
+        if let Some(writer) = writers.get(tunnel_key) {
+            return Ok(writer.clone());
+        }
+
+        prune_rotated_files(&dir, &self.config);
+        let writer = Arc::new(Mutex::new(TunnelLogWriter::new(dir, self.config.clone())));
+        writers.insert(tunnel_key.clone(), writer.clone());
+        Ok(writer)
+    }
+
+    /// 关闭隧道正在使用的日志文件后执行操作，用于删除日志
+    pub fn with_writer_closed<T>(
+        &self,
+        tunnel_key: &TunnelKey,
+        f: impl FnOnce() -> Result<T, String>,
+    ) -> Result<T, String> {
+        let writer = {
+            let writers = self
+                .writers
+                .lock()
+                .map_err(|e| format!("获取日志写入器锁失败: {}", e))?;
+            writers.get(tunnel_key).cloned()
+        };
+        match writer {
+            Some(writer) => {
+                let mut writer = writer
+                    .lock()
+                    .map_err(|e| format!("获取日志写入器锁失败: {}", e))?;
+                writer.close();
+                f()
+            }
+            None => f(),
+        }
+    }
+}
+
+/// 写入一行日志
+pub fn append_log_line(writer: &Mutex<TunnelLogWriter>, line: &str) {
+    if let Ok(mut writer) = writer.lock() {
+        writer.append_line(line);
+    }
+}
+
+/// 关闭日志文件，日志读取线程结束时调用
+pub fn close_log_file(writer: &Mutex<TunnelLogWriter>) {
+    if let Ok(mut writer) = writer.lock() {
+        writer.close();
+    }
+}
+
+/// 隧道的日志目录名，API 隧道为 tunnel_<ID>，自定义隧道为 custom_<编码后的名称>
+fn log_key(tunnel_key: &TunnelKey) -> String {
+    match tunnel_key {
+        TunnelKey::Api(id) => format!("tunnel_{}", id),
+        TunnelKey::Custom(name) => format!("custom_{}", percent_encode(name)),
+    }
+}
+
+/// 由日志目录名还原隧道标识，不是日志目录时返回 None
+pub fn tunnel_key_from_log_key(log_key: &str) -> Option<TunnelKey> {
+    if let Some(id) = log_key.strip_prefix("tunnel_") {
+        return id.parse().ok().map(TunnelKey::Api);
+    }
+    let name = percent_decode(log_key.strip_prefix("custom_")?);
+    // 只接受由 log_key 生成的目录名
+    let tunnel_key = TunnelKey::Custom(name);
+    (self::log_key(&tunnel_key) == log_key).then_some(tunnel_key)
+}
+
+// 检查前端传入的文件名，防止访问日志目录以外的文件
+fn validate_name(name: &str) -> Result<(), String> {
+    let valid = !name.is_empty()
+        && name != "."
+        && name != ".."
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.');
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("无效的日志名称: {}", name))
+    }
+}
+
+pub fn logs_root(app_handle: &AppContext) -> Result<PathBuf, String> {
+    Ok(app_handle.app_data_dir()?.join(LOGS_DIR))
+}
+
+pub fn tunnel_log_dir(app_handle: &AppContext, tunnel_key: &TunnelKey) -> Result<PathBuf, String> {
+    Ok(logs_root(app_handle)?.join(log_key(tunnel_key)))
+}
+
+/// 列出日志目录中的隧道，按目录名排序
+pub fn logged_tunnels(root: &Path) -> io::Result<Vec<TunnelKey>> {
+    let mut names: Vec<String> = fs::read_dir(root)?
+        .flatten()
+        .filter(|entry| entry.path().is_dir())
+        .map(|entry| entry.file_name().to_string_lossy().to_string())
+        .collect();
+    names.sort();
+    Ok(names
+        .iter()
+        .filter_map(|name| tunnel_key_from_log_key(name))
+        .collect())
+}
+
+fn is_log_file(name: &str) -> bool {
+    name == ACTIVE_LOG_FILE || is_rotated_file(name)
+}
+
+fn is_rotated_file(name: &str) -> bool {
+    name.starts_with(ROTATED_PREFIX) && name.ends_with(LOG_EXTENSION)
+}
+
+/// 列出隧道的日志文件，当前文件在前，历史文件按时间从新到旧
+pub fn list_log_files(dir: &Path, tunnel_key: &TunnelKey) -> Vec<TunnelLogFileInfo> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut files: Vec<TunnelLogFileInfo> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            if !is_log_file(&file_name) {
+                return None;
+            }
+            let metadata = entry.metadata().ok()?;
+            if !metadata.is_file() {
+                return None;
+            }
+            let modified = metadata
+                .modified()
+                .map(|t| chrono::DateTime::<chrono::Local>::from(t).to_rfc3339())
+                .unwrap_or_default();
+            Some(TunnelLogFileInfo {
+                tunnel_key: tunnel_key.clone(),
+                active: file_name == ACTIVE_LOG_FILE,
+                file_name,
+                size: metadata.len(),
+                modified,
+            })
+        })
+        .collect();
+
+    files.sort_by(|a, b| {
+        b.active
+            .cmp(&a.active)
+            .then_with(|| b.file_name.cmp(&a.file_name))
+    });
+    files
+}
+
+/// 按时间从旧到新返回日志文件路径，用于导出
+pub fn log_files_oldest_first(dir: &Path, tunnel_key: &TunnelKey) -> Vec<PathBuf> {
+    list_log_files(dir, tunnel_key)
+        .into_iter()
+        .rev()
+        .map(|info| dir.join(info.file_name))
+        .collect()
+}
+
+/// 检查文件名并返回日志文件路径
+pub fn log_file_path(dir: &Path, file_name: Option<&str>) -> Result<PathBuf, String> {
+    let file_name = file_name.unwrap_or(ACTIVE_LOG_FILE);
+    validate_name(file_name)?;
+    if !is_log_file(file_name) {
+        return Err(format!("无效的日志文件: {}", file_name));
+    }
+    Ok(dir.join(file_name))
+}
+
+/// 一页日志
+#[derive(Debug, PartialEq, Eq)]
+pub struct LogPage {
+    /// 第一行在文件中的行号（从 0 开始）
+    pub start_line: usize,
+    pub lines: Vec<String>,
+    pub total_lines: usize,
+}
+
+/// 逐行读取一页日志，只保留这一页的内容。tail 为 true 时 offset 从文件末尾倒数，
+/// 需要先数出总行数，再读取一遍取出这一页
+pub fn read_log_page(path: &Path, offset: usize, limit: usize, tail: bool) -> io::Result<LogPage> {
+    let (start, end, total_lines) = if tail {
+        let total_lines = scan_lines(path, |_, _| true)?;
+        let end = total_lines - offset.min(total_lines);
+        (end.saturating_sub(limit), end, total_lines)
+    } else {
+        (offset, offset.saturating_add(limit), 0)
+    };
+
+    let mut lines = Vec::new();
+    let counted = scan_lines(path, |index, line| {
+        if (start..end).contains(&index) {
+            lines.push(String::from_utf8_lossy(line).to_string());
+        }
+        // 倒数读取时已经知道总行数，取完这一页即可停止
+        !tail || index + 1 < end
+    })?;
+
+    let total_lines = if tail { total_lines } else { counted };
+    Ok(LogPage {
+        start_line: start.min(total_lines),
+        lines,
+        total_lines,
+    })
+}
+
+// 依次处理每一行（不含换行符），返回值为 false 时停止，返回已读的行数。
+// 文件不存在时视为空文件
+fn scan_lines(path: &Path, mut f: impl FnMut(usize, &[u8]) -> bool) -> io::Result<usize> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e),
+    };
+    let mut reader = BufReader::new(file);
+    let mut line = Vec::new();
+    let mut count = 0;
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            return Ok(count);
+        }
+        let end = line.len() - line.ends_with(b"\n") as usize;
+        let end = end - line[..end].ends_with(b"\r") as usize;
+        count += 1;
+        if !f(count - 1, &line[..end]) {
+            return Ok(count);
+        }
+    }
+}
+
+/// 清理超出数量或过期的历史文件
+fn prune_rotated_files(dir: &Path, config: &LogFileConfig) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+
+    let mut rotated: Vec<(String, SystemTime)> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if !is_rotated_file(&name) {
+                return None;
+            }
+            let modified = entry.metadata().and_then(|m| m.modified()).ok()?;
+            Some((name, modified))
+        })
+        .collect();
+    // 文件名带时间戳，按名称倒序即从新到旧
+    rotated.sort_by(|a, b| b.0.cmp(&a.0));
+
+    let now = SystemTime::now();
+    for (index, (name, modified)) in rotated.iter().enumerate() {
+        let expired = now.duration_since(*modified).unwrap_or_default() > config.retention;
+        if index >= config.max_rotated_files || expired {
+            if let Err(e) = fs::remove_file(dir.join(name)) {
+                eprintln!("[日志文件] 删除过期日志 {} 失败: {}", name, e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_log(name: &str, content: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "chmlfrp-tunnel-logs-{}-{}.log",
+            std::process::id(),
+            name
+        ));
+        fs::write(&path, content).unwrap();
+        path
+    }
+
+    fn page(path: &Path, offset: usize, limit: usize, tail: bool) -> (usize, Vec<String>, usize) {
+        let page = read_log_page(path, offset, limit, tail).unwrap();
+        (page.start_line, page.lines, page.total_lines)
+    }
+
+    fn lines(items: &[&str]) -> Vec<String> {
+        items.iter().map(|item| item.to_string()).collect()
+    }
+
+    #[test]
+    fn log_keys_are_distinct_and_reversible() {
+        let keys = [
+            TunnelKey::Api(12),
+            TunnelKey::Custom("a.b".to_string()),
+            TunnelKey::Custom("a_b".to_string()),
+            TunnelKey::Custom("a b".to_string()),
+            TunnelKey::Custom("a%2Eb".to_string()),
+            TunnelKey::Custom("../隧道".to_string()),
+        ];
+        let names: Vec<String> = keys.iter().map(log_key).collect();
+        for (index, name) in names.iter().enumerate() {
+            assert!(!names[..index].contains(name), "{}", name);
+            assert!(!name.contains('/') && !name.contains('\\'), "{}", name);
+            assert_eq!(tunnel_key_from_log_key(name).as_ref(), Some(&keys[index]));
+        }
+    }
+
+    #[test]
+    fn unknown_directories_are_not_tunnels() {
+        assert_eq!(tunnel_key_from_log_key("tunnel_x"), None);
+        assert_eq!(tunnel_key_from_log_key("other"), None);
+        // 不是由 log_key 生成的写法
+        assert_eq!(tunnel_key_from_log_key("custom_%61"), None);
+    }
+
+    #[test]
+    fn reads_pages_from_start() {
+        let path = temp_log("start", "a\r\nb\nc\nd");
+        assert_eq!(page(&path, 0, 2, false), (0, lines(&["a", "b"]), 4));
+        assert_eq!(page(&path, 3, 2, false), (3, lines(&["d"]), 4));
+        assert_eq!(page(&path, 9, 2, false), (4, lines(&[]), 4));
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn reads_pages_from_end() {
+        let path = temp_log("tail", "a\nb\nc\nd\n");
+        assert_eq!(page(&path, 0, 2, true), (2, lines(&["c", "d"]), 4));
+        assert_eq!(page(&path, 3, 2, true), (0, lines(&["a"]), 4));
+        assert_eq!(page(&path, 9, 2, true), (0, lines(&[]), 4));
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn missing_file_is_empty() {
+        let path = std::env::temp_dir().join("chmlfrp-tunnel-logs-missing.log");
+        assert_eq!(page(&path, 0, 10, true), (0, lines(&[]), 0));
+    }
+}
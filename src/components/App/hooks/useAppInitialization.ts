@@ -1,5 +1,13 @@
 import { useEffect } from "react";
+import { toast } from "sonner";
 import { logStore } from "@/services/logStore";
+import { getStoredUser, syncCredentials } from "@/services/api";
+
+interface StorageRecovery {
+  file: string;
+  error: string;
+  recovered_at: string;
+}
 
 /**
  * 应用初始化逻辑 hook
@@ -12,16 +20,46 @@ export function useAppInitialization() {
     const initProcessGuard = async () => {
       try {
         const { invoke } = await import("@tauri-apps/api/core");
-        const guardEnabled =
-          localStorage.getItem("processGuardEnabled") === "true";
-        await invoke("set_process_guard_enabled", { enabled: guardEnabled });
+        let guardEnabled = await invoke<boolean>("get_process_guard_enabled");
+
+        // 旧版只在 localStorage 中保存守护开关，第一次运行时导入到后端设置
+        const stored = localStorage.getItem("processGuardEnabled");
+        if (
+          stored !== null &&
+          localStorage.getItem("processGuardImported") === null
+        ) {
+          guardEnabled = stored === "true";
+          await invoke("set_process_guard_enabled", { enabled: guardEnabled });
+          localStorage.setItem("processGuardImported", "true");
+        }
+        localStorage.setItem("processGuardEnabled", guardEnabled.toString());
         console.log(`[守护进程] 初始化状态: ${guardEnabled ? "启用" : "禁用"}`);
       } catch (error) {
         console.error("Failed to initialize process guard:", error);
       }
     };
 
+    // 状态文件损坏后已从备份恢复时提示用户
+    const reportStorageRecoveries = async () => {
+      try {
+        const { invoke } = await import("@tauri-apps/api/core");
+        const recoveries = await invoke<StorageRecovery[]>(
+          "take_storage_recoveries",
+        );
+        for (const recovery of recoveries) {
+          toast.warning(`${recovery.file} 已损坏，已从备份恢复`, {
+            description: recovery.error,
+          });
+        }
+      } catch (error) {
+        console.error("Failed to check storage recoveries:", error);
+      }
+    };
+
     initProcessGuard();
+    reportStorageRecoveries();
+    // 旧版的令牌只保存在前端，启动时同步到后端
+    syncCredentials(getStoredUser()?.usertoken);
   }, []);
 
   useEffect(() => {
@@ -7,6 +7,7 @@ import {
   fetchTunnels,
   fetchUserInfo,
   saveStoredUser,
+  syncCredentials,
 } from "@/services/api";
 import { frpcDownloader } from "@/services/frpcDownloader.ts";
 import { frpcManager } from "@/services/frpcManager";
@@ -53,6 +54,7 @@ export function useDeepLink(
             };
 
             saveStoredUser(newUser);
+            await syncCredentials(data.usertoken);
             setUser(newUser);
             tokenToUse = data.usertoken;
 
@@ -75,6 +77,12 @@ export function useDeepLink(
           return;
         }
 
+        // 后端使用当前登录账号的令牌启动隧道
+        if (tokenToUse !== getStoredUser()?.usertoken) {
+          toast.error("链接中的 token 与当前登录的账户不一致，请先退出登录");
+          return;
+        }
+
         const tunnels = await fetchTunnels(tokenToUse);
         const tunnel = tunnels.find((t) => t.id === data.tunnelId);
 
@@ -131,7 +139,7 @@ export function useDeepLink(
           duration: Infinity,
         });
 
-        await frpcManager.startTunnel(data.tunnelId, tokenToUse);
+        await frpcManager.startTunnel(data.tunnelId);
 
         const isHttpType =
           tunnel.type.toUpperCase() === "HTTP" ||
@@ -12,19 +12,13 @@ export function useProcessGuard() {
   const [guardLoading, setGuardLoading] = useState(false);
 
   useEffect(() => {
-    // 同步状态到后端
-    const syncGuardState = async () => {
-      try {
-        await invoke("set_process_guard_enabled", {
-          enabled: guardEnabled,
-        });
-      } catch (error) {
-        console.error("Failed to sync process guard state:", error);
-      }
-    };
-
-    syncGuardState();
-  }, [guardEnabled]);
+    // 守护开关保存在后端设置中，localStorage 只是缓存
+    invoke<boolean>("get_process_guard_enabled")
+      .then(setGuardEnabled)
+      .catch((error) => {
+        console.error("Failed to load process guard state:", error);
+      });
+  }, []);
 
   const handleToggleGuard = async (enabled: boolean) => {
     setGuardLoading(true);
@@ -37,6 +37,17 @@ export function CustomTunnelDialog({
     try {
       setLoading(true);
 
+      // 先让 frpc 检查一遍配置，frpc 尚未下载时跳过
+      const verification = await customTunnelService
+        .verifyCustomTunnelConfig(configContent)
+        .catch(() => null);
+      if (verification && !verification.passed) {
+        toast.error(
+          verification.messages.join("\n") || "frpc 检查配置未通过",
+        );
+        return;
+      }
+
       const created = await customTunnelService.saveCustomTunnel(
         "",
         configContent,
@@ -69,7 +80,7 @@ export function CustomTunnelDialog({
         <DialogHeader>
           <DialogTitle>创建自定义隧道</DialogTitle>
           <DialogDescription>
-            填写 .ini 配置文件内容创建自定义隧道
+            填写 frpc 配置文件内容创建自定义隧道
           </DialogDescription>
         </DialogHeader>
 
@@ -88,7 +99,7 @@ export function CustomTunnelDialog({
               required
             />
             <p className="text-xs text-muted-foreground">
-              直接粘贴完整的 frpc INI 配置内容
+              支持 INI、TOML、YAML 和 JSON 格式，包含多个代理时会拆分为多个隧道
             </p>
           </div>
 
@@ -143,7 +143,7 @@ export function EditCustomTunnelDialog({
                   required
                 />
                 <p className="text-xs text-muted-foreground">
-                  直接粘贴完整的 frpc INI 配置内容
+                  支持 INI、TOML、YAML 和 JSON 格式
                 </p>
               </div>
             )}
@@ -44,11 +44,13 @@ export function TunnelCard({
 
   const isCustom = tunnel.type === "custom";
   const isApi = tunnel.type === "api";
+  // 自定义隧道通常只有一个代理，卡片展示第一个
+  const proxy = isCustom ? tunnel.data.proxies[0] : undefined;
 
-  const extractFirstDomain = (raw?: string) => {
+  const extractFirstDomain = (raw?: string[]) => {
     if (!raw) return "";
     const candidates = raw
-      .split(/[,;\s]+/g)
+      .flatMap((s) => s.split(/[,;\s]+/g))
       .map((s) => s.trim())
       .filter(Boolean);
 
@@ -78,12 +80,12 @@ export function TunnelCard({
       return { display, copy };
     }
 
-    const customType = (tunnel.data.tunnel_type || "").toLowerCase();
+    const customType = (proxy?.type || "").toLowerCase();
     const isHttpCustom = customType === "http" || customType === "https";
 
     if (isHttpCustom) {
-      const firstDomain = extractFirstDomain(tunnel.data.custom_domains);
-      const host = firstDomain || tunnel.data.subdomain || "";
+      const firstDomain = extractFirstDomain(proxy?.custom_domains);
+      const host = firstDomain || proxy?.subdomain || "";
       const display = host || tunnel.data.server_addr || "-";
 
       const protocol = customType === "https" ? "https" : "http";
@@ -92,7 +94,7 @@ export function TunnelCard({
       return { display, copy };
     }
 
-    const port = tunnel.data.remote_port ?? tunnel.data.server_port;
+    const port = proxy?.remote_port ?? tunnel.data.server_port;
     const addr = tunnel.data.server_addr || "-";
     const display = `${addr}:${port ?? "-"}`;
     return { display, copy: display };
@@ -200,9 +202,7 @@ export function TunnelCard({
                 </div>
                 <div className="flex items-center gap-2">
                   <span className="text-[10px] font-medium px-1.5 py-0.5 rounded text-muted-foreground bg-muted/10 uppercase tracking-wider">
-                    {isCustom
-                      ? tunnel.data.tunnel_type || "自定义"
-                      : tunnel.data.type}
+                    {isCustom ? proxy?.type || "自定义" : tunnel.data.type}
                   </span>
                   <span className="text-xs text-muted-foreground truncate flex items-center gap-1 opacity-80">
                     {isApi ? tunnel.data.node : tunnel.data.server_addr || "-"}
@@ -261,8 +261,8 @@ export function TunnelCard({
                       <span>本地</span>
                     </div>
                     <span className="font-mono text-foreground/80">
-                      {tunnel.data.local_ip || "127.0.0.1"}:
-                      {tunnel.data.local_port || "-"}
+                      {proxy?.local_ip || "127.0.0.1"}:
+                      {proxy?.local_port || "-"}
                     </span>
                   </div>
                   <div
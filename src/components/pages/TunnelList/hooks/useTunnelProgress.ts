@@ -114,7 +114,7 @@ export function useTunnelProgress(
             // 忽略错误
           }
 
-          await frpcManager.startTunnel(tunnelId, user.usertoken);
+          await frpcManager.startTunnel(tunnelId);
           setRunningTunnels((prev) => new Set(prev).add(tunnelKey));
 
           let hasChecked = false;
@@ -250,7 +250,7 @@ export function useTunnelProgress(
           return next;
         });
 
-        await frpcManager.startTunnel(tunnelId, user.usertoken);
+        await frpcManager.startTunnel(tunnelId);
         setRunningTunnels((prev) => new Set(prev).add(tunnelKey));
 
         let hasChecked = false;
@@ -563,7 +563,11 @@ export function useTunnelProgress(
             return prev;
           }
 
-          if (!fixingTlsTunnels.has(tunnelId)) {
+          // 守护进程开启时由后端自动修复并重启，这里只提示结果
+          const guardEnabled =
+            localStorage.getItem("processGuardEnabled") === "true";
+
+          if (!guardEnabled && !fixingTlsTunnels.has(tunnelId)) {
             processedErrorsRef.current.add(errorKey);
             setTimeout(
               () => {
@@ -593,6 +597,44 @@ export function useTunnelProgress(
     tunnels,
   ]);
 
+  // 守护进程自动修复 TLS 配置的结果
+  useEffect(() => {
+    let unlisten: (() => void) | undefined;
+    frpcManager
+      .listenToTlsRemediation((event) => {
+        switch (event.status) {
+          case "applied":
+            toast.info("检测到 TLS 问题，守护进程正在自动修复....", {
+              duration: 10000,
+            });
+            break;
+          case "succeeded":
+            toast.success("TLS 配置已自动修复，隧道已重新启动", {
+              duration: 5000,
+            });
+            break;
+          case "rolled_back":
+          case "failed":
+            toast.error(`${event.message}，请尝试更换节点`, {
+              duration: 8000,
+            });
+            break;
+          case "not_applicable":
+            toast.warning(event.message, {
+              duration: 8000,
+            });
+            break;
+        }
+      })
+      .then((fn) => {
+        unlisten = fn;
+      });
+
+    return () => {
+      unlisten?.();
+    };
+  }, []);
+
   useEffect(() => {
     const timeouts = timeoutRefs.current;
     const successTimeouts = successTimeoutRefs.current;
@@ -78,11 +78,7 @@ export function useTunnelToggle({
 
         let message: string;
         if (tunnel.type === "api") {
-          const user = getStoredUser();
-          message = await frpcManager.startTunnel(
-            tunnel.data.id,
-            user!.usertoken!,
-          );
+          message = await frpcManager.startTunnel(tunnel.data.id);
         } else {
           message = await customTunnelService.startCustomTunnel(tunnel.data.id);
           if (startingTunnelKeyRef.current === tunnelKey) {
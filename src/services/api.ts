@@ -182,11 +182,30 @@ export const getStoredUser = (): StoredUser | null => {
 export const saveStoredUser = (user: StoredUser) => {
   if (!isBrowser) return;
   localStorage.setItem("chmlfrp_user", JSON.stringify(user));
+  void syncCredentials(user.usertoken);
 };
 
 export const clearStoredUser = () => {
   if (!isBrowser) return;
   localStorage.removeItem("chmlfrp_user");
+  void syncCredentials();
+};
+
+/**
+ * 将用户令牌同步到后端，启动隧道和守护进程重启时由后端读取令牌
+ * @param usertoken 为空时删除后端保存的令牌
+ */
+export const syncCredentials = async (usertoken?: string) => {
+  try {
+    const { invoke } = await import("@tauri-apps/api/core");
+    if (usertoken) {
+      await invoke("set_credentials", { userToken: usertoken });
+    } else {
+      await invoke("clear_credentials");
+    }
+  } catch (error) {
+    console.error("Failed to sync credentials:", error);
+  }
 };
 
 export async function login(
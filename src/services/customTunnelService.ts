@@ -1,29 +1,71 @@
 import { invoke } from "@tauri-apps/api/core";
 
+export interface ProxyDescriptor {
+  name: string;
+  type?: string;
+  local_ip?: string;
+  local_port?: number;
+  remote_port?: number;
+  custom_domains: string[];
+  subdomain?: string;
+  plugin?: string;
+}
+
 export interface CustomTunnel {
   id: string;
   name: string;
   config_file: string;
   server_addr?: string;
   server_port?: number;
-  tunnels: string[];
-  tunnel_type?: string;
-  custom_domains?: string;
-  subdomain?: string;
-  local_ip?: string;
-  local_port?: number;
-  remote_port?: number;
+  proxies: ProxyDescriptor[];
   created_at: string;
 }
 
+export type ConfigFormat = "ini" | "toml" | "yaml" | "json";
+
+export interface UnmappedKey {
+  section: string;
+  key: string;
+  value: string;
+  line: number;
+}
+
+export interface ConfigConversion {
+  format: ConfigFormat;
+  content: string;
+  unmapped_keys: UnmappedKey[];
+  applied: boolean;
+}
+
+export interface ConfigDiagnostic {
+  section: string;
+  /** 从 1 开始的行号，无法定位时为 0 */
+  line: number;
+  message: string;
+}
+
+export interface ConfigValidation {
+  format: ConfigFormat;
+  valid: boolean;
+  errors: ConfigDiagnostic[];
+  warnings: ConfigDiagnostic[];
+}
+
+export interface ConfigVerification {
+  passed: boolean;
+  messages: string[];
+}
+
 export class CustomTunnelService {
   async saveCustomTunnel(
     tunnelName: string,
     configContent: string,
+    force = false,
   ): Promise<CustomTunnel[]> {
     return await invoke<CustomTunnel[]>("save_custom_tunnel", {
       tunnelName,
       configContent,
+      force,
     });
   }
 
@@ -42,10 +84,46 @@ export class CustomTunnelService {
   async updateCustomTunnel(
     tunnelId: string,
     configContent: string,
+    force = false,
   ): Promise<CustomTunnel> {
     return await invoke<CustomTunnel>("update_custom_tunnel", {
       tunnelId,
       configContent,
+      force,
+    });
+  }
+
+  async validateCustomTunnelConfig(
+    configContent: string,
+  ): Promise<ConfigValidation> {
+    return await invoke<ConfigValidation>("validate_custom_tunnel_config", {
+      configContent,
+    });
+  }
+
+  /**
+   * 转换配置格式，apply 为 false 时只返回预览
+   */
+  async convertCustomTunnelConfig(
+    tunnelId: string,
+    targetFormat: ConfigFormat,
+    apply = false,
+  ): Promise<ConfigConversion> {
+    return await invoke<ConfigConversion>("convert_custom_tunnel_config", {
+      tunnelId,
+      targetFormat,
+      apply,
+    });
+  }
+
+  /**
+   * 用 frpc verify 检查配置，需要已下载 frpc
+   */
+  async verifyCustomTunnelConfig(
+    configContent: string,
+  ): Promise<ConfigVerification> {
+    return await invoke<ConfigVerification>("verify_custom_tunnel_config", {
+      configContent,
     });
   }
 
@@ -1,19 +1,116 @@
 import { invoke } from "@tauri-apps/api/core";
 import { listen, type UnlistenFn, type Event } from "@tauri-apps/api/event";
 
+// 隧道标识："api_<隧道ID>" 或 "custom_<配置名>"
+export type TunnelKey = string;
+
 export interface LogMessage {
+  tunnel_key?: TunnelKey;
+  // 兼容旧版的数字 ID，自定义隧道为负数
+  tunnel_id: number;
+  message: string;
+  timestamp: string;
+  seq?: number;
+}
+
+export type FrpcLogLevel = "trace" | "debug" | "info" | "warn" | "error";
+
+export type FrpcEventKind =
+  | "login_success"
+  | "proxy_started"
+  | "proxy_rejected"
+  | "reconnecting"
+  | "tls_error"
+  | "io_timeout"
+  | "connection_reset"
+  | "auth_failed"
+  | "api_rejected"
+  | "rate_limited";
+
+// frpc 日志解析结果，seq 与对应 frpc-log 的序号一致
+export interface FrpcEvent {
+  tunnel_key: TunnelKey;
+  tunnel_id: number;
+  seq: number;
+  kind: FrpcEventKind | null;
+  date: string | null;
+  level: FrpcLogLevel | null;
+  source: string | null;
+  proxy_name: string | null;
+  body: string;
+}
+
+export type TunnelState =
+  | "starting"
+  | "logging_in"
+  | "connected"
+  | "reconnecting"
+  | "failed"
+  | "stopping"
+  | "stopped"
+  | "guarded_restarting";
+
+export interface TunnelStateInfo {
+  tunnel_key: TunnelKey;
   tunnel_id: number;
+  state: TunnelState;
+  // 仅 failed 状态有值
+  reason?: string;
+  since: string;
+}
+
+export type TlsRemediationStatus =
+  | "applied"
+  | "succeeded"
+  | "rolled_back"
+  | "failed"
+  | "not_applicable";
+
+export interface TlsRemediationEvent {
+  tunnel_key: TunnelKey;
+  tunnel_id: number;
+  status: TlsRemediationStatus;
+  config_path: string;
   message: string;
   timestamp: string;
 }
 
+export interface TunnelLogEntry {
+  seq: number;
+  tunnel_key: TunnelKey;
+  tunnel_id: number;
+  message: string;
+  timestamp: string;
+}
+
+export interface TunnelLogBatch {
+  entries: TunnelLogEntry[];
+  latest_seq: number;
+  truncated: boolean;
+}
+
+export interface TunnelLogFileInfo {
+  tunnel_key: TunnelKey;
+  file_name: string;
+  size: number;
+  modified: string;
+  active: boolean;
+}
+
+export interface TunnelLogPage {
+  file_name: string;
+  start_line: number;
+  lines: string[];
+  total_lines: number;
+}
+
 export class FrpcManager {
   private unlisten?: UnlistenFn;
+  private unlistenEvents?: UnlistenFn;
 
-  async startTunnel(tunnelId: number, userToken: string): Promise<string> {
+  async startTunnel(tunnelId: number): Promise<string> {
     return await invoke<string>("start_frpc", {
       tunnelId,
-      userToken,
     });
   }
 
@@ -41,10 +138,104 @@ export class FrpcManager {
     }
   }
 
+  async getRunningTunnelKeys(): Promise<TunnelKey[]> {
+    try {
+      return await invoke<TunnelKey[]>("get_running_tunnel_keys");
+    } catch {
+      return [];
+    }
+  }
+
+  // tunnelKey 也可以是 API 隧道的数字 ID
+  async getTunnelLogs(
+    tunnelKey: TunnelKey | number,
+    sinceSeq?: number,
+    limit?: number,
+  ): Promise<TunnelLogBatch> {
+    return await invoke<TunnelLogBatch>("get_tunnel_logs", {
+      tunnelKey,
+      sinceSeq,
+      limit,
+    });
+  }
+
+  async getTunnelStates(): Promise<TunnelStateInfo[]> {
+    try {
+      return await invoke<TunnelStateInfo[]>("get_tunnel_states");
+    } catch {
+      return [];
+    }
+  }
+
+  async listenToStateChanges(
+    onChange: (info: TunnelStateInfo) => void,
+  ): Promise<UnlistenFn> {
+    return await listen<TunnelStateInfo>(
+      "tunnel-state-changed",
+      (event: Event<TunnelStateInfo>) => {
+        onChange(event.payload);
+      },
+    );
+  }
+
+  async listenToTlsRemediation(
+    onRemediation: (event: TlsRemediationEvent) => void,
+  ): Promise<UnlistenFn> {
+    return await listen<TlsRemediationEvent>(
+      "tunnel-tls-remediation",
+      (event: Event<TlsRemediationEvent>) => {
+        onRemediation(event.payload);
+      },
+    );
+  }
+
   async fixFrpcIniTls(): Promise<string> {
     return await invoke<string>("fix_frpc_ini_tls");
   }
 
+  async listTunnelLogs(tunnelKey?: TunnelKey): Promise<TunnelLogFileInfo[]> {
+    return await invoke<TunnelLogFileInfo[]>("list_tunnel_logs", {
+      tunnelKey,
+    });
+  }
+
+  async readTunnelLog(
+    tunnelKey: TunnelKey,
+    options: {
+      fileName?: string;
+      offset?: number;
+      limit?: number;
+      tail?: boolean;
+    } = {},
+  ): Promise<TunnelLogPage> {
+    return await invoke<TunnelLogPage>("read_tunnel_log", {
+      tunnelKey,
+      ...options,
+    });
+  }
+
+  async exportTunnelLogs(
+    tunnelKey: TunnelKey,
+    targetPath: string,
+    fileName?: string,
+  ): Promise<string> {
+    return await invoke<string>("export_tunnel_logs", {
+      tunnelKey,
+      fileName,
+      targetPath,
+    });
+  }
+
+  async deleteTunnelLogs(
+    tunnelKey: TunnelKey,
+    fileName?: string,
+  ): Promise<void> {
+    await invoke("delete_tunnel_logs", {
+      tunnelKey,
+      fileName,
+    });
+  }
+
   async listenToLogs(onLog: (log: LogMessage) => void): Promise<void> {
     if (this.unlisten) {
       this.unlisten();
@@ -58,11 +249,28 @@ export class FrpcManager {
     );
   }
 
+  async listenToEvents(onEvent: (event: FrpcEvent) => void): Promise<void> {
+    if (this.unlistenEvents) {
+      this.unlistenEvents();
+    }
+
+    this.unlistenEvents = await listen<FrpcEvent>(
+      "frpc-event",
+      (event: Event<FrpcEvent>) => {
+        onEvent(event.payload);
+      },
+    );
+  }
+
   stopListening() {
     if (this.unlisten) {
       this.unlisten();
       this.unlisten = undefined;
     }
+    if (this.unlistenEvents) {
+      this.unlistenEvents();
+      this.unlistenEvents = undefined;
+    }
   }
 }
 
@@ -1,4 +1,4 @@
-import { frpcManager, type LogMessage } from "./frpcManager";
+import { frpcManager, type LogMessage, type TunnelKey } from "./frpcManager";
 
 type LogListener = (logs: LogMessage[]) => void;
 
@@ -6,6 +6,8 @@ class LogStore {
   private logs: LogMessage[] = [];
   private listeners: Set<LogListener> = new Set();
   private isListening = false;
+  // 每个隧道已收到的最新日志序号
+  private lastSeq: Map<TunnelKey, number> = new Map();
 
   async startListening() {
     if (this.isListening) {
@@ -15,9 +17,66 @@ class LogStore {
     this.isListening = true;
 
     await frpcManager.listenToLogs((log: LogMessage) => {
-      this.logs.push(log);
-      this.notifyListeners();
+      if (this.acceptSeq(log)) {
+        this.logs.push(log);
+        this.notifyListeners();
+      }
+    });
+
+    // 窗口从托盘恢复时补齐隐藏期间错过的日志
+    document.addEventListener("visibilitychange", () => {
+      if (document.visibilityState === "visible") {
+        void this.resync();
+      }
     });
+    await this.resync();
+  }
+
+  // 从后端日志缓冲拉取错过的日志
+  async resync(tunnelKeys?: TunnelKey[]) {
+    const keys = tunnelKeys ?? (await frpcManager.getRunningTunnelKeys());
+    let changed = false;
+
+    for (const tunnelKey of keys) {
+      try {
+        const batch = await frpcManager.getTunnelLogs(
+          tunnelKey,
+          this.lastSeq.get(tunnelKey) ?? 0,
+        );
+        for (const entry of batch.entries) {
+          const log: LogMessage = {
+            tunnel_key: entry.tunnel_key,
+            tunnel_id: entry.tunnel_id,
+            message: entry.message,
+            timestamp: formatTime(entry.timestamp),
+            seq: entry.seq,
+          };
+          if (this.acceptSeq(log)) {
+            this.logs.push(log);
+            changed = true;
+          }
+        }
+      } catch (error) {
+        console.error(`同步隧道 ${tunnelKey} 的日志失败:`, error);
+      }
+    }
+
+    if (changed) {
+      this.notifyListeners();
+    }
+  }
+
+  private acceptSeq(log: LogMessage): boolean {
+    if (log.seq === undefined) {
+      return true;
+    }
+    const key = log.tunnel_key ?? `api_${log.tunnel_id}`;
+    const last = this.lastSeq.get(key) ?? 0;
+    if (log.seq <= last) {
+      return false;
+    }
+    this.lastSeq.set(key, log.seq);
+    return true;
   }
 
   subscribe(listener: LogListener): () => void {
@@ -51,5 +110,15 @@ class LogStore {
   }
 }
 
+function formatTime(timestamp: string): string {
+  const date = new Date(timestamp);
+  if (Number.isNaN(date.getTime())) {
+    return timestamp;
+  }
+  return [date.getHours(), date.getMinutes(), date.getSeconds()]
+    .map((n) => n.toString().padStart(2, "0"))
+    .join(":");
+}
+
 // 导出单例
 export const logStore = new LogStore();
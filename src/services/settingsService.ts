@@ -0,0 +1,123 @@
+import { invoke } from "@tauri-apps/api/core";
+import { listen, type UnlistenFn } from "@tauri-apps/api/event";
+
+/** 进程守护的重启策略 */
+export interface GuardConfig {
+  /** 第一次重启前的等待时间，之后每次翻倍 */
+  initial_backoff_ms: number;
+  max_backoff_ms: number;
+  /** 在 restart_window_secs 内最多重启的次数 */
+  max_restarts: number;
+  restart_window_secs: number;
+  /** 稳定运行超过该时间后重置退避 */
+  stable_uptime_secs: number;
+}
+
+export interface ControlApiSettings {
+  enabled: boolean;
+  /** 127.0.0.1:<端口>、[::1]:<端口> 或 unix:<套接字路径> */
+  listen: string;
+}
+
+/** 本地控制接口的运行状态 */
+export interface ControlApiStatus {
+  enabled: boolean;
+  listen: string;
+  running: boolean;
+  /** 最近一次启动失败的原因 */
+  error: string | null;
+}
+
+export interface Settings {
+  version: number;
+  auto_start_tunnels: boolean;
+  /** 隧道标识，格式为 "api_<ID>" 或 "custom_<名称>" */
+  tunnel_auto_start: string[];
+  process_guard_enabled: boolean;
+  guard_config: GuardConfig;
+  /** 从不守护的隧道，格式同 tunnel_auto_start */
+  never_guard: string[];
+  /**
+   * API 隧道的启动方式
+   * config_file: 写入只有当前用户可读的临时配置文件，令牌不出现在进程命令行中
+   * command_line: 以 -u <令牌> -p <隧道 ID> 启动
+   */
+  api_launch_mode: "config_file" | "command_line";
+  /** 本地控制接口，供脚本和命令行管理隧道 */
+  control_api: ControlApiSettings;
+}
+
+export class SettingsService {
+  /**
+   * 获取全部设置
+   */
+  async getSettings(): Promise<Settings> {
+    return await invoke<Settings>("get_settings");
+  }
+
+  /**
+   * 修改设置，只需传入要修改的项
+   * @returns 修改后的完整设置
+   */
+  async setSettings(patch: Partial<Settings>): Promise<Settings> {
+    return await invoke<Settings>("set_settings", { patch });
+  }
+
+  /**
+   * 修改进程守护的重启策略
+   */
+  async setGuardConfig(config: GuardConfig): Promise<void> {
+    await invoke("set_process_guard_config", { config });
+  }
+
+  /**
+   * 设置隧道是否从不守护
+   * @param tunnelKey 隧道标识，格式为 "api_<ID>" 或 "custom_<名称>"
+   */
+  async setTunnelNeverGuard(
+    tunnelKey: string,
+    neverGuard: boolean,
+  ): Promise<void> {
+    await invoke("set_tunnel_never_guard", { tunnelKey, neverGuard });
+  }
+
+  /**
+   * 修改本地控制接口的开关和监听地址，修改后服务按新设置重启
+   */
+  async setControlApi(control_api: ControlApiSettings): Promise<Settings> {
+    return await this.setSettings({ control_api });
+  }
+
+  /**
+   * 获取本地控制接口的运行状态
+   */
+  async getControlApiStatus(): Promise<ControlApiStatus> {
+    return await invoke<ControlApiStatus>("get_control_api_status");
+  }
+
+  /**
+   * 获取本地控制接口的令牌
+   */
+  async getControlApiToken(): Promise<string> {
+    return await invoke<string>("get_control_api_token");
+  }
+
+  /**
+   * 重新生成本地控制接口的令牌，旧令牌立即失效
+   */
+  async regenerateControlApiToken(): Promise<string> {
+    return await invoke<string>("regenerate_control_api_token");
+  }
+
+  /**
+   * 监听设置变化事件
+   * @param callback 回调函数
+   */
+  async listen(callback: (settings: Settings) => void): Promise<UnlistenFn> {
+    return await listen<Settings>("settings-changed", (event) => {
+      callback(event.payload);
+    });
+  }
+}
+
+export const settingsService = new SettingsService();